@@ -13,6 +13,9 @@ extern crate alloc;
 extern crate test;
 
 mod benchmarks;
+mod pangenome;
+mod subgraph;
+mod validate;
 
 #[macro_use]
 mod utils;
@@ -30,9 +33,11 @@ use parallel_processor::memory_data_size::MemoryDataSize;
 use rayon::ThreadPoolBuilder;
 use std::fs::{create_dir_all, File};
 use std::io::{BufReader, BufWriter, Write};
+use std::num::ParseIntError;
 use std::panic;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::str::FromStr;
 use std::sync::atomic::Ordering;
 use std::time::Duration;
 use structopt::StructOpt;
@@ -74,9 +79,17 @@ arg_enum! {
 use ::utils::{compute_best_m, DEBUG_LEVEL};
 use colors::bundles::graph_querying::ColorBundleGraphQuerying;
 use colors::non_colored::NonColoredManager;
+use colors::parsers::records::RecordColorsMode;
 use colors::storage::deserializer::ColorsDeserializer;
 use colors::DefaultColorsSerializer;
-use config::{ColorIndexType, FLUSH_QUEUE_FACTOR, KEEP_FILES, PREFER_MEMORY};
+use config::{
+    ColorIndexType, SwapPriority, COLORS_ABUNDANCES, FLUSH_QUEUE_FACTOR,
+    INTERMEDIATE_ZSTD_SWAP_PRIORITIES, IUPAC_EXPANSION_MAX_BASES, KEEP_FILES, MIN_BASE_QUALITY,
+    MIN_KMER_CORRECTNESS_PROBABILITY, PARALLEL_DECOMPRESSION_MEMORY, PREFER_MEMORY,
+    SPLIT_UNITIGS_BY_COLORS, STREAMED_INPUTS_SIZE_HINT, STRICT_INPUT_PARSING,
+    UNITIGS_ABUNDANCE_FULL_TAGS, UNITIGS_ABUNDANCE_TAGS,
+};
+use hashbrown::HashMap;
 use hashes::cn_nthash::CanonicalNtHashIteratorFactory;
 use hashes::fw_nthash::ForwardNtHashIteratorFactory;
 use parallel_processor::memory_fs::MemoryFs;
@@ -91,6 +104,13 @@ enum CliArgs {
     Query(QueryArgs),
     DumpColors(DumpColorsArgs),
     Matches(MatchesArgs),
+    Classify(ClassifyArgs),
+    Diff(DiffArgs),
+    Subgraph(SubgraphArgs),
+    /// Checks the invariants of a graph built by ggcat.
+    /// All the kmers of the graph and of the input reads are kept in memory, so it is meant for small graphs
+    Validate(ValidateArgs),
+    Compare(CompareArgs),
     // Utils(CmdUtilsArgs),
 }
 
@@ -103,6 +123,140 @@ struct MatchesArgs {
     match_color: String,
 }
 
+#[derive(StructOpt, Debug)]
+struct ClassifyArgs {
+    /// The input colored graph, with its colormap in the same folder
+    input_graph: PathBuf,
+
+    #[structopt(short = "o", long = "output-prefix", default_value = "classification")]
+    output_prefix: PathBuf,
+
+    /// Minimum fraction of colors containing a unitig to classify it as core
+    #[structopt(long = "core-threshold", default_value = "0.95")]
+    core_threshold: f64,
+
+    /// Unitigs contained in less than this fraction of colors are classified as cloud, the others as shell
+    #[structopt(long = "cloud-threshold", default_value = "0.15")]
+    cloud_threshold: f64,
+
+    /// Also write all the unitigs with a CL:Z:<class> header tag to <prefix>.fasta
+    #[structopt(long = "header-tags")]
+    header_tags: bool,
+
+    /// Write the core unitigs to <prefix>.core.fasta and the shell and cloud ones to <prefix>.accessory.fasta
+    #[structopt(long = "split-fasta")]
+    split_fasta: bool,
+}
+
+#[derive(StructOpt, Debug)]
+struct DiffArgs {
+    /// The input colored graph, with its colormap in the same folder
+    input_graph: PathBuf,
+
+    /// File with the names of the colors of the first group, one per line
+    #[structopt(long = "group-a")]
+    group_a: PathBuf,
+
+    /// File with the names of the colors of the second group, one per line
+    #[structopt(long = "group-b")]
+    group_b: PathBuf,
+
+    #[structopt(short = "o", long = "output-file", default_value = "diff.fasta")]
+    output_file: PathBuf,
+
+    /// Select the kmers present in any color of the first group, instead of all of them
+    #[structopt(long = "any-a")]
+    any_a: bool,
+
+    /// Write only the whole unitigs made of selected kmers, instead of the maximal runs of selected kmers
+    #[structopt(long = "whole-unitigs")]
+    whole_unitigs: bool,
+}
+
+#[derive(StructOpt, Debug)]
+struct SubgraphArgs {
+    /// The input graph, built with links (-e)
+    input_graph: PathBuf,
+
+    /// The input query as a .fasta file
+    input_query: PathBuf,
+
+    /// The output file, the subgraph is written in GFA format if it has the .gfa extension, otherwise in FASTA format
+    #[structopt(short = "o", long = "output-file", default_value = "subgraph.fasta")]
+    output_file: PathBuf,
+
+    /// Specifies the k-mers length of the graph
+    #[structopt(short, default_value = "32")]
+    klen: usize,
+
+    /// The graph was built treating reverse complementary kmers as different
+    #[structopt(short = "f", long = "forward-only")]
+    forward_only: bool,
+
+    /// Also extract the unitigs up to this number of links away from the ones matching the query
+    #[structopt(long = "hops", default_value = "1")]
+    hops: usize,
+}
+
+#[derive(StructOpt, Debug)]
+struct ValidateArgs {
+    /// The input graph
+    input_graph: PathBuf,
+
+    /// Specifies the k-mers length of the graph
+    #[structopt(short, default_value = "32")]
+    klen: usize,
+
+    /// The graph was built treating reverse complementary kmers as different
+    #[structopt(short = "f", long = "forward-only")]
+    forward_only: bool,
+
+    /// The graph contains matchtigs, eulertigs or pathtigs, skip the unique kmers and maximality checks
+    #[structopt(long = "tigs")]
+    tigs: bool,
+
+    /// Also check that all the solid kmers of these input files are present in the graph
+    #[structopt(long = "input-reads")]
+    input_reads: Vec<PathBuf>,
+
+    /// Minimum multiplicity of the solid kmers of the input reads
+    #[structopt(short = "s", long = "min-multiplicity", default_value = "2")]
+    min_multiplicity: usize,
+}
+
+#[derive(StructOpt, Debug)]
+struct CompareArgs {
+    /// The first input graph
+    graph_a: PathBuf,
+
+    /// The second input graph
+    graph_b: PathBuf,
+
+    /// Also write the runs of kmers present in only one of the graphs to <prefix>.only-a.fasta and <prefix>.only-b.fasta
+    #[structopt(short = "o", long = "output-file-prefix")]
+    output_file_prefix: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    common_args: CommonArgs,
+}
+
+fn read_colors_group(file: &PathBuf) -> Vec<String> {
+    let file = File::open(file).unwrap_or_else(|err| {
+        println!(
+            "ERROR: Cannot open colors group file {}: {}",
+            file.display(),
+            err
+        );
+        exit(1);
+    });
+
+    BufReader::new(file)
+        .lines()
+        .map(|line| line.unwrap().trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
 #[derive(StructOpt, Debug)]
 struct CommonArgs {
     /// Specifies the k-mers length
@@ -144,10 +298,26 @@ struct CommonArgs {
     #[structopt(short = "b", long = "buckets-count-log")]
     pub buckets_count_log: Option<usize>,
 
-    /// The level of lz4 compression to be used for the intermediate files
+    /// Estimated total size (GB) of the inputs read from the standard input (-) or from named pipes,
+    /// used to choose the number of buckets
+    #[structopt(long = "input-size-hint")]
+    pub input_size_hint: Option<f64>,
+
+    /// The level of lz4 (or zstd) compression to be used for the intermediate files
     #[structopt(long = "intermediate-compression-level")]
     pub intermediate_compression_level: Option<u32>,
 
+    /// Compress with zstd instead of lz4 the intermediate files of the given swap priorities
+    /// (comma separated, from 0 for the minimizer buckets to 5 for the colored query buckets, or "all"),
+    /// reducing the temporary files size when the disk bandwidth is the bottleneck
+    #[structopt(long = "intermediate-zstd")]
+    pub intermediate_zstd: Option<SwapPriorities>,
+
+    /// Fail at the first malformed record or read error of the input files,
+    /// instead of skipping them with a warning
+    #[structopt(long = "strict")]
+    pub strict: bool,
+
     /// The level of debugging
     #[structopt(short = "d", long = "debug-level", default_value = "0", hidden = true)]
     pub debug_level: usize,
@@ -156,6 +326,30 @@ struct CommonArgs {
     pub only_bstats: bool,
 }
 
+#[derive(Copy, Clone, Debug)]
+struct SwapPriorities(u64);
+
+impl FromStr for SwapPriorities {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("all") {
+            return Ok(SwapPriorities(u64::MAX));
+        }
+
+        let mut mask = 0;
+        for priority in s.split(',') {
+            match priority.trim().parse::<usize>() {
+                Ok(priority) if priority <= SwapPriority::ColoredQueryBuckets => {
+                    mask |= 1 << priority
+                }
+                _ => return Err(format!("Invalid swap priority: '{}'", priority)),
+            }
+        }
+        Ok(SwapPriorities(mask))
+    }
+}
+
 #[derive(StructOpt, Debug)]
 #[structopt(group = ArgGroup::with_name("output-mode").required(false))]
 struct AssemblerArgs {
@@ -166,17 +360,63 @@ struct AssemblerArgs {
     #[structopt(short = "l", long = "input-lists")]
     pub input_lists: Vec<PathBuf>,
 
+    /// The lists of input files with their colors, one <color_name><TAB><file> pair per line.
+    /// Files sharing the same color name are assigned to the same color
+    #[structopt(long = "colored-input-lists")]
+    pub colored_input_lists: Vec<PathBuf>,
+
     /// Enable colors
     #[structopt(short, long)]
     pub colors: bool,
 
-    /// Minimum multiplicity required to keep a kmer
+    /// Also store in the colormap how many times each kmer occurs in each color, quantized in logarithmic bins.
+    /// Each distinct combination of colors and abundances is stored as a separate subset,
+    /// so the colormap can grow by orders of magnitude compared to the colors subsets alone
+    #[structopt(long = "colors-abundances")]
+    pub colors_abundances: bool,
+
+    /// Split the unitigs at every colors subset change, so that each output sequence has a single colors subset
+    #[structopt(long = "split-unitigs-by-colors")]
+    pub split_unitigs_by_colors: bool,
+
+    /// Assign a color to each sequence record instead of each input file, named after the record identifier.
+    /// The input files are read twice, so they cannot be the standard input or named pipes
+    #[structopt(long = "colors-by-record")]
+    pub colors_by_record: bool,
+
+    /// Name the record colors after the part of the identifier preceding this delimiter,
+    /// so that records sharing the same prefix share the same color (implies --colors-by-record)
+    #[structopt(long = "record-color-delimiter", conflicts_with = "record-color-regex")]
+    pub record_color_delimiter: Option<char>,
+
+    /// Name the record colors after the first capture group (or the whole match) of this regex
+    /// applied to the record header (implies --colors-by-record)
+    #[structopt(long = "record-color-regex")]
+    pub record_color_regex: Option<String>,
+
+    /// Minimum multiplicity required to keep a kmer, or 'auto' to use the first valley of the kmers histogram
+    /// (requires an additional counting pass)
     #[structopt(short = "s", long = "min-multiplicity", default_value = "2")]
-    pub min_multiplicity: usize,
+    pub min_multiplicity: MinMultiplicity,
+
+    /// Write the kmers frequency histogram to <output_file>.histo, always done with '-s auto'
+    #[structopt(long = "histogram")]
+    pub histogram: bool,
+
+    /// Treat as N the FASTQ bases with a Phred quality lower than this value, splitting the reads at them
+    #[structopt(long = "min-base-quality")]
+    pub min_base_quality: Option<u8>,
+
+    /// Minimum correctness probability for each kmer (using fastq quality checks),
+    /// computed as the product of the correctness probabilities of its bases
+    #[structopt(short = "q", long = "quality-threshold")]
+    pub quality_threshold: Option<f64>,
+
+    /// Expand the isolated IUPAC ambiguity codes standing for at most this number of bases (2 to 4)
+    /// into all the compatible kmers, instead of splitting the sequences at them
+    #[structopt(long = "expand-iupac")]
+    pub expand_iupac: Option<usize>,
 
-    // /// Minimum correctness probability for each kmer (using fastq quality checks)
-    // #[structopt(short = "q", long = "quality-threshold")]
-    // pub quality_threshold: Option<f64>,
     #[structopt(short = "n", long, default_value = "0", hidden = true)]
     pub number: usize,
 
@@ -189,6 +429,14 @@ struct AssemblerArgs {
     #[structopt(long = "last-step", default_value = "BuildUnitigs")]
     pub last_step: AssemblerStartingStep,
 
+    /// Annotate each unitig with its mean kmer abundance, in BCALM2 format km:f:<mean>
+    #[structopt(short = "a", long = "abundance-tags")]
+    pub abundance_tags: bool,
+
+    /// Also annotate each unitig with its total kmers count (KC:i:) and its minimum and maximum kmer abundances (mn:i:, mx:i:)
+    #[structopt(long = "full-abundance-tags")]
+    pub full_abundance_tags: bool,
+
     /// Generate maximal unitigs connections references, in BCALM2 format L:<+/->:<other id>:<+/->
     #[structopt(
         short = "e",
@@ -209,14 +457,80 @@ struct AssemblerArgs {
     #[structopt(long = "pathtigs", group = "output-mode")]
     pub pathtigs: bool,
 
+    /// Build the tigs only joining unitigs with the same colors subset, so that each tig has a single colors subset.
+    /// Implies --split-unitigs-by-colors
+    #[structopt(long = "color-coherent-tigs")]
+    pub color_coherent_tigs: bool,
+
+    /// Clip the dead-end tips up to this length (in kmers) after building the graph.
+    /// Graph cleaning implies -e when no tigs are requested
+    #[structopt(long = "clip-tips")]
+    pub clip_tips: Option<usize>,
+
+    /// Clip a tip only if its mean abundance is lower than this fraction of the best alternative branch
+    #[structopt(long = "tips-max-ratio", default_value = "0.5")]
+    pub tips_max_ratio: f64,
+
+    /// Pop the simple bubbles branches with a mean abundance lower than this fraction of the best parallel branch
+    #[structopt(long = "pop-bubbles")]
+    pub pop_bubbles: Option<f64>,
+
+    /// Write a binary adjacency index of the graph to <output>.idx, that can be loaded with the unitig_graph crate.
+    /// Implies -e. The whole unitigs graph is kept in memory to write the unitigs in id order
+    #[structopt(long = "graph-index")]
+    pub graph_index: bool,
+
+    /// Dump all the solid kmers with their counts (and colors subsets, if colors are enabled) to this file
+    #[structopt(long = "dump-kmers")]
+    pub dump_kmers: Option<PathBuf>,
+
+    /// Format of the kmers dump, Text writes one kmer<TAB>count line for each kmer as jellyfish and KMC do
+    #[structopt(long = "dump-kmers-format", default_value = "Text")]
+    pub dump_kmers_format: KmersDumpFormat,
+
+    /// Only dump the kmers, without building the unitigs
+    #[structopt(long = "kmers-only")]
+    pub kmers_only: bool,
+
     #[structopt(flatten)]
     pub common_args: CommonArgs,
 }
 
+#[derive(Copy, Clone, Debug)]
+enum MinMultiplicity {
+    Auto,
+    Value(usize),
+}
+
+impl FromStr for MinMultiplicity {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            Ok(MinMultiplicity::Auto)
+        } else {
+            Ok(MinMultiplicity::Value(s.parse()?))
+        }
+    }
+}
+
 #[derive(StructOpt, Debug)]
 struct DumpColorsArgs {
     input_colormap: PathBuf,
     output_file: PathBuf,
+
+    /// Also write the colors of each subset, along with their quantized abundances if stored in the colormap
+    #[structopt(long = "subsets")]
+    subsets: bool,
+}
+
+arg_enum! {
+    /// Format of the kmers dump
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum KmersDumpFormat {
+        Text,
+        Binary,
+    }
 }
 
 arg_enum! {
@@ -267,7 +581,25 @@ fn initialize(args: &CommonArgs, out_file: &PathBuf) {
     KEEP_FILES.store(args.keep_temp_files, Ordering::Relaxed);
 
     PREFER_MEMORY.store(args.prefer_memory, Ordering::Relaxed);
+    STRICT_INPUT_PARSING.store(args.strict, Ordering::Relaxed);
+    INTERMEDIATE_ZSTD_SWAP_PRIORITIES.store(
+        args.intermediate_zstd
+            .map(|priorities| priorities.0)
+            .unwrap_or(0),
+        Ordering::Relaxed,
+    );
     DEBUG_LEVEL.store(args.debug_level, Ordering::Relaxed);
+    STREAMED_INPUTS_SIZE_HINT.store(
+        args.input_size_hint
+            .map(|hint| (hint * (MemoryDataSize::OCTET_GIBIOCTET_FACTOR as f64)) as u64)
+            .unwrap_or(0),
+        Ordering::Relaxed,
+    );
+    // The inputs are decompressed while filling the buckets, so use only a fraction of the memory
+    PARALLEL_DECOMPRESSION_MEMORY.store(
+        (args.memory * (MemoryDataSize::OCTET_GIBIOCTET_FACTOR as f64) / 4.0) as usize,
+        Ordering::Relaxed,
+    );
 
     ThreadPoolBuilder::new()
         .num_threads(args.threads_count)
@@ -404,10 +736,92 @@ fn convert_colored_query_format(
     }
 }
 
+fn convert_kmers_dump_format(format: KmersDumpFormat) -> assembler::KmersDumpFormat {
+    match format {
+        KmersDumpFormat::Text => assembler::KmersDumpFormat::Text,
+        KmersDumpFormat::Binary => assembler::KmersDumpFormat::Binary,
+    }
+}
+
+/// Adds the files of a colored input list, made of <color_name><TAB><file> lines, to the inputs.
+/// The files with the same color name share the same color
+fn parse_colored_input_list(
+    list: &Path,
+    reader: impl BufRead,
+    color_names: &mut Vec<String>,
+    colors_by_name: &mut HashMap<String, ColorIndexType>,
+    inputs: &mut Vec<(PathBuf, ColorIndexType)>,
+) -> Result<(), String> {
+    for (line_index, input) in reader.lines().enumerate() {
+        let input = input.map_err(|err| {
+            format!(
+                "Cannot read line {} of colored input list {}: {}",
+                line_index + 1,
+                list.display(),
+                err
+            )
+        })?;
+
+        // Lists written on Windows keep the carriage return at the end of the file path
+        let input = input.trim_end_matches('\r');
+
+        if input.trim().is_empty() {
+            continue;
+        }
+
+        let (color_name, file) = input.split_once('\t').ok_or_else(|| {
+            format!(
+                "Invalid line {} in colored input list {}, expected <color_name><TAB><file>",
+                line_index + 1,
+                list.display()
+            )
+        })?;
+
+        let color = *colors_by_name
+            .entry(color_name.to_string())
+            .or_insert_with(|| {
+                color_names.push(color_name.to_string());
+                (color_names.len() - 1) as ColorIndexType
+            });
+
+        inputs.push((PathBuf::from(file), color));
+    }
+
+    Ok(())
+}
+
 fn run_assembler_from_args(
     generics: (StaticDispatch<()>, StaticDispatch<()>, StaticDispatch<()>),
     args: AssemblerArgs,
 ) {
+    UNITIGS_ABUNDANCE_TAGS.store(
+        args.abundance_tags || args.full_abundance_tags,
+        Ordering::Relaxed,
+    );
+    UNITIGS_ABUNDANCE_FULL_TAGS.store(args.full_abundance_tags, Ordering::Relaxed);
+    COLORS_ABUNDANCES.store(args.colors_abundances, Ordering::Relaxed);
+    SPLIT_UNITIGS_BY_COLORS.store(
+        args.split_unitigs_by_colors || args.color_coherent_tigs,
+        Ordering::Relaxed,
+    );
+
+    if let Some(threshold) = args.quality_threshold {
+        if !(threshold > 0.0 && threshold <= 1.0) {
+            println!("ERROR: the quality threshold must be a probability in (0, 1]");
+            exit(1);
+        }
+        MIN_KMER_CORRECTNESS_PROBABILITY.store(threshold.to_bits(), Ordering::Relaxed);
+    }
+    MIN_BASE_QUALITY.store(args.min_base_quality.unwrap_or(0) as u32, Ordering::Relaxed);
+
+    if let Some(max_bases) = args.expand_iupac {
+        if !(2..=4).contains(&max_bases) {
+            println!("ERROR: the IUPAC codes to expand must stand for 2 to 4 bases");
+            exit(1);
+        }
+        IUPAC_EXPANSION_MAX_BASES.store(max_bases, Ordering::Relaxed);
+    }
+
     let mut inputs = args.input.clone();
 
     for list in args.input_lists {
@@ -418,11 +832,156 @@ fn run_assembler_from_args(
         }
     }
 
+    // Each plain input file gets its own color, named after the file
+    let mut color_names: Vec<_> = inputs
+        .iter()
+        .map(|f| f.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+
+    let mut inputs: Vec<_> = inputs
+        .into_iter()
+        .enumerate()
+        .map(|(index, file)| (file, index as ColorIndexType))
+        .collect();
+
+    let mut colors_by_name = HashMap::new();
+
+    if !args.colored_input_lists.is_empty() && !args.colors {
+        println!("Warning: colored input lists are specified, but colors are not enabled");
+    }
+
+    for list in args.colored_input_lists {
+        let file = File::open(&list).unwrap_or_else(|err| {
+            println!(
+                "ERROR: Cannot open colored input list {}: {}",
+                list.display(),
+                err
+            );
+            exit(1);
+        });
+
+        parse_colored_input_list(
+            &list,
+            BufReader::new(file),
+            &mut color_names,
+            &mut colors_by_name,
+            &mut inputs,
+        )
+        .unwrap_or_else(|err| {
+            println!("ERROR: {}", err);
+            exit(1);
+        });
+    }
+
     if inputs.is_empty() {
         println!("ERROR: No input files specified!");
         exit(1);
     }
 
+    if inputs
+        .iter()
+        .filter(|(file, _)| io::is_stdin_input(file))
+        .count()
+        > 1
+    {
+        println!("ERROR: The standard input can be specified only once");
+        exit(1);
+    }
+
+    let record_colors_mode = if let Some(regex) = &args.record_color_regex {
+        Some(RecordColorsMode::from_regex(regex).unwrap_or_else(|err| {
+            println!("ERROR: Invalid record color regex: {}", err);
+            exit(1);
+        }))
+    } else if let Some(delimiter) = args.record_color_delimiter {
+        if !delimiter.is_ascii() {
+            println!("ERROR: The record color delimiter must be an ascii character");
+            exit(1);
+        }
+        Some(RecordColorsMode::IdentifierPrefix(delimiter as u8))
+    } else if args.colors_by_record {
+        Some(RecordColorsMode::Identifier)
+    } else {
+        None
+    };
+
+    if record_colors_mode.is_some() && !args.colors {
+        println!("Warning: colors by record are requested, but colors are not enabled");
+    }
+
+    // The record names are collected with an additional pass over the inputs, that cannot be streamed twice
+    if record_colors_mode.is_some() {
+        if let Some((file, _)) = inputs.iter().find(|(file, _)| {
+            io::is_stdin_input(file)
+                || std::fs::metadata(file)
+                    .map(|metadata| !metadata.is_file())
+                    .unwrap_or(false)
+        }) {
+            println!(
+                "ERROR: Colors by record require regular input files, as they are read twice, but {} is streamed",
+                file.display()
+            );
+            exit(1);
+        }
+    }
+
+    if args.colors_abundances && !args.colors {
+        println!("Warning: colors abundances are requested, but colors are not enabled");
+    }
+
+    if args.split_unitigs_by_colors {
+        if !args.colors {
+            println!(
+                "Warning: unitigs splitting by colors is requested, but colors are not enabled"
+            );
+        }
+
+        if args.greedy_matchtigs || args.eulertigs || args.pathtigs {
+            println!("ERROR: Unitigs splitting by colors cannot be used with matchtigs, use --color-coherent-tigs instead");
+            exit(1);
+        }
+    }
+
+    if args.color_coherent_tigs {
+        if !args.colors {
+            println!("Warning: color coherent tigs are requested, but colors are not enabled");
+        }
+
+        if !(args.greedy_matchtigs || args.eulertigs || args.pathtigs) {
+            println!("ERROR: Color coherent tigs require one of -g, --eulertigs or --pathtigs");
+            exit(1);
+        }
+    }
+
+    if (args.split_unitigs_by_colors || args.color_coherent_tigs)
+        && (args.clip_tips.is_some() || args.pop_bubbles.is_some())
+    {
+        println!("ERROR: Unitigs splitting by colors cannot be used with graph cleaning, as it would join the split unitigs");
+        exit(1);
+    }
+
+    if args.graph_index && (args.greedy_matchtigs || args.eulertigs || args.pathtigs) {
+        println!("ERROR: The graph index can be built only for maximal unitigs");
+        exit(1);
+    }
+
+    if args.kmers_only && args.dump_kmers.is_none() {
+        println!(
+            "ERROR: --kmers-only requires the kmers dump file to be specified with --dump-kmers"
+        );
+        exit(1);
+    }
+
+    for ratio in [Some(args.tips_max_ratio), args.pop_bubbles]
+        .into_iter()
+        .flatten()
+    {
+        if !(0.0..=1.0).contains(&ratio) {
+            println!("ERROR: The graph cleaning abundance ratios must be between 0 and 1");
+            exit(1);
+        }
+    }
+
     assembler::dynamic_dispatch::run_assembler(
         generics,
         args.common_args.klen,
@@ -432,10 +991,15 @@ fn run_assembler_from_args(
         convert_assembler_step(args.step),
         convert_assembler_step(args.last_step),
         inputs,
+        color_names,
+        record_colors_mode,
         args.output_file,
         args.common_args.temp_dir,
         args.common_args.threads_count,
-        args.min_multiplicity,
+        match args.min_multiplicity {
+            MinMultiplicity::Auto => None,
+            MinMultiplicity::Value(value) => Some(value),
+        },
         args.common_args.buckets_count_log,
         Some(args.number),
         args.common_args.intermediate_compression_level,
@@ -449,6 +1013,23 @@ fn run_assembler_from_args(
         } else {
             None
         },
+        if args.clip_tips.is_some() || args.pop_bubbles.is_some() {
+            Some(assembler::GraphCleaningConfig {
+                tips_max_kmers: args.clip_tips.unwrap_or(0),
+                tips_max_ratio: args.tips_max_ratio,
+                bubbles_max_ratio: args.pop_bubbles.unwrap_or(0.0),
+            })
+        } else {
+            None
+        },
+        args.graph_index,
+        args.dump_kmers.map(|path| assembler::KmersDumpConfig {
+            path,
+            format: convert_kmers_dump_format(args.dump_kmers_format),
+            canonical: !args.common_args.forward_only,
+            skip_unitigs: args.kmers_only,
+        }),
+        args.histogram,
         args.common_args.only_bstats,
     );
 }
@@ -575,6 +1156,11 @@ fn main() {
         CliArgs::Query(args) => {
             initialize(&args.common_args, &args.output_file_prefix);
 
+            if io::get_input_file_size(&args.input_query).is_none() {
+                println!("ERROR: The query must be a regular file, as it is read more than once");
+                exit(1);
+            }
+
             if !args.colors && args.colored_query_output_format.is_some() {
                 println!("Warning: colored query output format is specified, but the graph is not colored");
             }
@@ -604,8 +1190,121 @@ fn main() {
         } // CliArgs::Utils(args) => {
         //     process_cmdutils(args);
         // }
+        CliArgs::Classify(args) => {
+            if !(0.0..=1.0).contains(&args.core_threshold)
+                || !(0.0..=1.0).contains(&args.cloud_threshold)
+                || args.cloud_threshold > args.core_threshold
+            {
+                println!("ERROR: The thresholds must be between 0 and 1, with the cloud threshold not greater than the core one");
+                exit(1);
+            }
+
+            pangenome::classify_unitigs(
+                args.input_graph,
+                args.output_prefix,
+                args.core_threshold,
+                args.cloud_threshold,
+                args.header_tags,
+                args.split_fasta,
+            );
+
+            return;
+        }
+        CliArgs::Diff(args) => {
+            pangenome::diff_groups(
+                args.input_graph,
+                &read_colors_group(&args.group_a),
+                &read_colors_group(&args.group_b),
+                args.output_file,
+                args.any_a,
+                args.whole_unitigs,
+            );
+
+            return;
+        }
+        CliArgs::Subgraph(args) => {
+            if args.klen == 0 || args.klen > subgraph::SUBGRAPH_MAX_K {
+                println!(
+                    "ERROR: Subgraph extraction supports kmers lengths between 1 and {}",
+                    subgraph::SUBGRAPH_MAX_K
+                );
+                exit(1);
+            }
+
+            subgraph::extract_subgraph(
+                args.input_graph,
+                args.input_query,
+                args.output_file,
+                args.klen,
+                args.forward_only,
+                args.hops,
+            );
+
+            return;
+        }
+        CliArgs::Validate(args) => {
+            if args.klen == 0 || args.klen > subgraph::SUBGRAPH_MAX_K {
+                println!(
+                    "ERROR: Graph validation supports kmers lengths between 1 and {}",
+                    subgraph::SUBGRAPH_MAX_K
+                );
+                exit(1);
+            }
+
+            if validate::validate_graph(
+                args.input_graph,
+                args.klen,
+                args.forward_only,
+                args.tigs,
+                &args.input_reads,
+                args.min_multiplicity,
+            ) {
+                println!("The graph is valid");
+            } else {
+                println!("ERROR: The graph is not valid");
+                exit(1);
+            }
+
+            return;
+        }
+        CliArgs::Compare(args) => {
+            initialize(
+                &args.common_args,
+                args.output_file_prefix
+                    .as_ref()
+                    .unwrap_or(&PathBuf::from("compare")),
+            );
+
+            let bucketing_hash = if args.common_args.forward_only {
+                <ForwardNtHashIteratorFactory as MinimizerHashFunctionFactory>::STATIC_DISPATCH_ID
+            } else {
+                <CanonicalNtHashIteratorFactory as MinimizerHashFunctionFactory>::STATIC_DISPATCH_ID
+            };
+
+            querier::compare::dynamic_dispatch::run_compare(
+                (
+                    bucketing_hash,
+                    get_hash_static_id(
+                        args.common_args.hash_type,
+                        args.common_args.klen,
+                        args.common_args.forward_only,
+                    ),
+                ),
+                args.common_args.klen,
+                args.common_args
+                    .mlen
+                    .unwrap_or(compute_best_m(args.common_args.klen)),
+                args.graph_a,
+                args.graph_b,
+                args.output_file_prefix,
+                args.common_args.temp_dir,
+                args.common_args.buckets_count_log,
+                args.common_args.threads_count,
+                args.common_args.intermediate_compression_level,
+            );
+        }
         CliArgs::DumpColors(args) => {
-            let colors_deserializer =
+            let mut colors_deserializer =
                 ColorsDeserializer::<DefaultColorsSerializer>::new(args.input_colormap, true);
 
             let output_file_name = args.output_file.with_extension("jsonl");
@@ -622,6 +1321,30 @@ fn main() {
                 .unwrap();
             }
 
+            if args.subsets {
+                let mut colors = Vec::new();
+                let mut abundances = Vec::new();
+
+                for subset_idx in 0..colors_deserializer.colors_subsets_count() {
+                    colors_deserializer.get_color_mappings_with_abundances(
+                        subset_idx as ColorIndexType,
+                        &mut colors,
+                        &mut abundances,
+                    );
+
+                    write!(
+                        output_file,
+                        "{{subset_index:{}, colors:{:?}",
+                        subset_idx, colors
+                    )
+                    .unwrap();
+                    if colors_deserializer.has_abundances() {
+                        write!(output_file, ", abundance_bins:{:?}", abundances).unwrap();
+                    }
+                    writeln!(output_file, " }}").unwrap();
+                }
+            }
+
             drop(output_file);
             println!("Colors written to {}", output_file_name.display());
 
@@ -631,3 +1354,59 @@ fn main() {
 
     MemoryFs::terminate();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_list(
+        list: &str,
+        color_names: &mut Vec<String>,
+    ) -> Result<Vec<(PathBuf, ColorIndexType)>, String> {
+        let mut colors_by_name = HashMap::new();
+        let mut inputs = vec![];
+        parse_colored_input_list(
+            Path::new("list.txt"),
+            list.as_bytes(),
+            color_names,
+            &mut colors_by_name,
+            &mut inputs,
+        )?;
+        Ok(inputs)
+    }
+
+    #[test]
+    fn colored_input_list_grouping() {
+        let mut color_names = vec!["plain.fa".to_string()];
+
+        let inputs = parse_list(
+            "red\ta.fa\nblue\tb.fa\n\n  \nred\tc.fa\r\nblue\td e.fa\r\n",
+            &mut color_names,
+        )
+        .unwrap();
+
+        assert_eq!(color_names, vec!["plain.fa", "red", "blue"]);
+        assert_eq!(
+            inputs,
+            vec![
+                (PathBuf::from("a.fa"), 1),
+                (PathBuf::from("b.fa"), 2),
+                (PathBuf::from("c.fa"), 1),
+                (PathBuf::from("d e.fa"), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn colored_input_list_malformed_line() {
+        let mut color_names = vec![];
+
+        assert_eq!(
+            parse_list("red\ta.fa\nb.fa\n", &mut color_names),
+            Err(
+                "Invalid line 2 in colored input list list.txt, expected <color_name><TAB><file>"
+                    .to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,458 @@
+use colors::colors_manager::ColorMapReader;
+use colors::storage::deserializer::ColorsDeserializer;
+use colors::DefaultColorsSerializer;
+use config::ColorIndexType;
+use hashbrown::HashMap;
+use io::sequences_reader::{FastaSequence, SequencesReader};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Returns the colors subsets and the kmers counts of the C:<subset>:<count> tags of a colored unitig header
+pub(crate) fn parse_colors_runs(header: &[u8]) -> impl Iterator<Item = (ColorIndexType, u64)> + '_ {
+    header
+        .split(|c| c.is_ascii_whitespace())
+        .filter_map(|field| {
+            let field = std::str::from_utf8(field.strip_prefix(b"C:")?).ok()?;
+            let (subset, count) = field.split_once(':')?;
+            Some((
+                ColorIndexType::from_str_radix(subset, 16).ok()?,
+                count.parse().ok()?,
+            ))
+        })
+}
+
+/// Returns the unitig identifier, without the leading '>'
+pub(crate) fn unitig_identifier(header: &[u8]) -> &[u8] {
+    let header = header.strip_prefix(b">").unwrap_or(header);
+    header
+        .split(|c| c.is_ascii_whitespace())
+        .next()
+        .unwrap_or(header)
+}
+
+/// Reads the unitigs of a colored graph, decoding the colors of their C: tags with the graph colormap
+pub struct ColoredUnitigsReader {
+    input_graph: PathBuf,
+    colormap: ColorsDeserializer<DefaultColorsSerializer>,
+    subsets_cache: HashMap<ColorIndexType, Vec<ColorIndexType>>,
+}
+
+impl ColoredUnitigsReader {
+    pub fn new(input_graph: impl AsRef<Path>) -> Self {
+        let colormap_file = input_graph.as_ref().with_extension("colors.dat");
+
+        if !colormap_file.exists() {
+            println!(
+                "ERROR: Colormap file {} not found, the input graph must be colored",
+                colormap_file.display()
+            );
+            std::process::exit(1);
+        }
+
+        Self {
+            input_graph: input_graph.as_ref().to_path_buf(),
+            colormap: ColorsDeserializer::new(colormap_file, true),
+            subsets_cache: HashMap::new(),
+        }
+    }
+
+    pub fn colors_count(&self) -> usize {
+        self.colormap.colors_count()
+    }
+
+    pub fn get_color_name(&self, color: ColorIndexType) -> &str {
+        self.colormap.get_color_name(color, false)
+    }
+
+    /// Calls the callback for each unitig, along with the sorted list of the colors of all its kmers
+    pub fn for_each_unitig(&mut self, mut callback: impl FnMut(FastaSequence, &[ColorIndexType])) {
+        let colormap = &mut self.colormap;
+        let subsets_cache = &mut self.subsets_cache;
+        let mut unitig_colors = vec![];
+
+        SequencesReader::new().process_file_extended(
+            &self.input_graph,
+            |sequence| {
+                unitig_colors.clear();
+
+                for (subset, _) in parse_colors_runs(sequence.ident) {
+                    let colors = subsets_cache.entry(subset).or_insert_with(|| {
+                        let mut colors = vec![];
+                        colormap.get_color_mappings(subset, &mut colors);
+                        colors
+                    });
+                    unitig_colors.extend_from_slice(colors);
+                }
+
+                unitig_colors.sort_unstable();
+                unitig_colors.dedup();
+
+                callback(sequence, &unitig_colors);
+            },
+            None,
+            true,
+            false,
+        );
+    }
+
+    /// Calls the callback for each unitig with its colors runs in sequence order, each one with its length in kmers.
+    /// The colors of each run are mapped with `map_subset`, that is called only once for each colors subset
+    pub fn for_each_unitig_runs<T: Copy>(
+        &mut self,
+        mut map_subset: impl FnMut(&[ColorIndexType]) -> T,
+        mut callback: impl FnMut(FastaSequence, &[(T, u64)]),
+    ) {
+        let colormap = &mut self.colormap;
+        let mut mapped_subsets = HashMap::new();
+        let mut subset_colors = vec![];
+        let mut unitig_runs = vec![];
+
+        SequencesReader::new().process_file_extended(
+            &self.input_graph,
+            |sequence| {
+                unitig_runs.clear();
+
+                for (subset, count) in parse_colors_runs(sequence.ident) {
+                    let mapped = *mapped_subsets.entry(subset).or_insert_with(|| {
+                        subset_colors.clear();
+                        colormap.get_color_mappings(subset, &mut subset_colors);
+                        map_subset(&subset_colors)
+                    });
+                    unitig_runs.push((mapped, count));
+                }
+
+                callback(sequence, &unitig_runs);
+            },
+            None,
+            true,
+            false,
+        );
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum UnitigClass {
+    Core,
+    Shell,
+    Cloud,
+}
+
+impl UnitigClass {
+    fn name(&self) -> &'static str {
+        match self {
+            UnitigClass::Core => "core",
+            UnitigClass::Shell => "shell",
+            UnitigClass::Cloud => "cloud",
+        }
+    }
+}
+
+pub(crate) fn create_output_file(path: impl AsRef<Path>) -> BufWriter<File> {
+    BufWriter::new(File::create(&path).unwrap_or_else(|err| {
+        println!(
+            "ERROR: Cannot create output file {}: {}",
+            path.as_ref().display(),
+            err
+        );
+        std::process::exit(1);
+    }))
+}
+
+fn write_tagged_unitig(
+    file: &mut impl Write,
+    sequence: &FastaSequence,
+    class: UnitigClass,
+) -> std::io::Result<()> {
+    let header = sequence.ident.strip_prefix(b">").unwrap_or(sequence.ident);
+    file.write_all(b">")?;
+    file.write_all(header)?;
+    writeln!(file, " CL:Z:{}", class.name())?;
+    file.write_all(sequence.seq)?;
+    file.write_all(b"\n")
+}
+
+/// Classifies each unitig of a colored graph as core (present in at least `core_threshold` of the colors),
+/// cloud (present in less than `cloud_threshold` of the colors) or shell, writing the classification to <prefix>.tsv.
+/// A unitig is present in a color if at least one of its kmers is.
+pub fn classify_unitigs(
+    input_graph: impl AsRef<Path>,
+    output_prefix: impl AsRef<Path>,
+    core_threshold: f64,
+    cloud_threshold: f64,
+    header_tags: bool,
+    split_fasta: bool,
+) {
+    let mut reader = ColoredUnitigsReader::new(&input_graph);
+    let colors_count = reader.colors_count();
+
+    let output_prefix = output_prefix.as_ref().to_string_lossy().to_string();
+    let create_output = |suffix: &str| create_output_file(format!("{}.{}", output_prefix, suffix));
+
+    let mut tsv_file = create_output("tsv");
+    let mut tagged_file = header_tags.then(|| create_output("fasta"));
+    let mut split_files = split_fasta.then(|| {
+        (
+            create_output("core.fasta"),
+            create_output("accessory.fasta"),
+        )
+    });
+
+    writeln!(tsv_file, "unitig\tcolors\tfraction\tclass").unwrap();
+
+    let mut class_counts = [0usize; 3];
+
+    reader.for_each_unitig(|sequence, colors| {
+        let fraction = colors.len() as f64 / colors_count as f64;
+        let class = if fraction >= core_threshold {
+            UnitigClass::Core
+        } else if fraction < cloud_threshold {
+            UnitigClass::Cloud
+        } else {
+            UnitigClass::Shell
+        };
+        class_counts[class as usize] += 1;
+
+        tsv_file
+            .write_all(unitig_identifier(sequence.ident))
+            .unwrap();
+        writeln!(
+            tsv_file,
+            "\t{}\t{:.4}\t{}",
+            colors.len(),
+            fraction,
+            class.name()
+        )
+        .unwrap();
+
+        if let Some(tagged_file) = &mut tagged_file {
+            write_tagged_unitig(tagged_file, &sequence, class).unwrap();
+        }
+
+        if let Some((core_file, accessory_file)) = &mut split_files {
+            let file = if class == UnitigClass::Core {
+                core_file
+            } else {
+                accessory_file
+            };
+            write_tagged_unitig(file, &sequence, class).unwrap();
+        }
+    });
+
+    println!(
+        "Classified unitigs over {} colors: {} core, {} shell, {} cloud",
+        colors_count,
+        class_counts[UnitigClass::Core as usize],
+        class_counts[UnitigClass::Shell as usize],
+        class_counts[UnitigClass::Cloud as usize]
+    );
+    println!("Classification written to {}.tsv", output_prefix);
+}
+
+/// Writes the maximal runs of kmers present in the colors of group A (all of them, or any with `any_a`)
+/// and absent from all the colors of group B. With `whole_unitigs` only the unitigs made entirely of such kmers are written
+pub fn diff_groups(
+    input_graph: impl AsRef<Path>,
+    group_a: &[String],
+    group_b: &[String],
+    output_file: impl AsRef<Path>,
+    any_a: bool,
+    whole_unitigs: bool,
+) {
+    const GROUP_A: u8 = 1;
+    const GROUP_B: u8 = 2;
+
+    let mut reader = ColoredUnitigsReader::new(&input_graph);
+
+    let colors_by_name: HashMap<_, _> = (0..reader.colors_count())
+        .map(|color| {
+            (
+                reader.get_color_name(color as ColorIndexType).to_string(),
+                color,
+            )
+        })
+        .collect();
+
+    let mut colors_groups = vec![0u8; reader.colors_count()];
+    for (names, group) in [(group_a, GROUP_A), (group_b, GROUP_B)] {
+        for name in names {
+            match colors_by_name.get(name) {
+                Some(color) => colors_groups[*color] |= group,
+                None => {
+                    println!("ERROR: Color {} not found in the graph colormap", name);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+
+    if colors_groups.iter().any(|g| *g == GROUP_A | GROUP_B) {
+        println!("ERROR: The two groups must not share any color");
+        std::process::exit(1);
+    }
+
+    let group_a_size = colors_groups.iter().filter(|g| **g == GROUP_A).count();
+
+    let mut output = create_output_file(&output_file);
+    let mut written_count = 0;
+
+    reader.for_each_unitig_runs(
+        |colors| {
+            let mut in_a = 0;
+            for color in colors {
+                match colors_groups[*color as usize] {
+                    GROUP_A => in_a += 1,
+                    GROUP_B => return false,
+                    _ => {}
+                }
+            }
+            if any_a {
+                in_a > 0
+            } else {
+                in_a == group_a_size
+            }
+        },
+        |sequence, runs| {
+            let kmers_count: u64 = runs.iter().map(|(_, count)| *count).sum();
+            if kmers_count == 0 {
+                return;
+            }
+            // Consecutive kmers overlap by k - 1 bases
+            let overlap = match sequence.seq.len().checked_sub(kmers_count as usize) {
+                Some(overlap) => overlap,
+                None => {
+                    println!(
+                        "ERROR: Unitig {} has {} kmers in its colors tags, but only {} bases",
+                        String::from_utf8_lossy(unitig_identifier(sequence.ident)),
+                        kmers_count,
+                        sequence.seq.len()
+                    );
+                    std::process::exit(1);
+                }
+            };
+
+            let mut write_region = |start: usize, end: usize| {
+                let bases_end = end + overlap;
+                output.write_all(b">").unwrap();
+                output.write_all(unitig_identifier(sequence.ident)).unwrap();
+                writeln!(
+                    output,
+                    ":{}-{} LN:i:{}",
+                    start + 1,
+                    bases_end,
+                    bases_end - start
+                )
+                .unwrap();
+                output.write_all(&sequence.seq[start..bases_end]).unwrap();
+                output.write_all(b"\n").unwrap();
+                written_count += 1;
+            };
+
+            if whole_unitigs {
+                if runs.iter().all(|(selected, _)| *selected) {
+                    write_region(0, kmers_count as usize);
+                }
+                return;
+            }
+
+            let mut position = 0;
+            for group in runs.group_by(|a, b| a.0 == b.0) {
+                let group_kmers: u64 = group.iter().map(|(_, count)| *count).sum();
+                if group[0].0 {
+                    write_region(position, position + group_kmers as usize);
+                }
+                position += group_kmers as usize;
+            }
+        },
+    );
+
+    println!(
+        "Written {} sequences to {}",
+        written_count,
+        output_file.as_ref().display()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pangenome::{diff_groups, parse_colors_runs};
+    use colors::storage::serializer::ColorsSerializer;
+    use colors::DefaultColorsSerializer;
+    use config::ColorIndexType;
+    use std::path::PathBuf;
+
+    #[test]
+    fn colors_runs_parsing() {
+        let runs: Vec<_> =
+            parse_colors_runs(b">7 LN:i:9 C:1a:5 KC:i:12 km:f:2.4 C:2:3 C:x:1 L:+:3:-").collect();
+        assert_eq!(runs, vec![(0x1a, 5), (2, 3)]);
+
+        assert_eq!(parse_colors_runs(b">7 LN:i:9").count(), 0);
+    }
+
+    /// Writes a colored graph with colors a, b and c, with k = 3
+    fn write_colored_graph(name: &str) -> PathBuf {
+        let graph =
+            std::env::temp_dir().join(format!("ggcat_{}_{}.fasta", name, std::process::id()));
+
+        {
+            let subsets: [&[ColorIndexType]; 4] = [&[0, 1], &[0, 1, 2], &[0], &[2]];
+            let serializer = ColorsSerializer::<DefaultColorsSerializer>::new(
+                graph.with_extension("colors.dat"),
+                vec!["a".to_string(), "b".to_string(), "c".to_string()],
+                false,
+            );
+            for subset in subsets {
+                serializer.serialize_colors(subset);
+            }
+        }
+
+        std::fs::write(
+            &graph,
+            concat!(
+                ">0 LN:i:7 C:0:2 C:1:2 C:2:1\nACGTACG\n",
+                ">1 LN:i:5 C:3:3\nTTGCA\n",
+                ">2 LN:i:4 C:0:2\nGGCA\n",
+            ),
+        )
+        .unwrap();
+
+        graph
+    }
+
+    fn diff_groups_output(graph: &PathBuf, any_a: bool, whole_unitigs: bool) -> String {
+        let output = graph.with_extension("diff.fasta");
+        diff_groups(
+            graph,
+            &["a".to_string(), "b".to_string()],
+            &["c".to_string()],
+            &output,
+            any_a,
+            whole_unitigs,
+        );
+        let sequences = std::fs::read_to_string(&output).unwrap();
+        std::fs::remove_file(&output).unwrap();
+        sequences
+    }
+
+    #[test]
+    fn groups_difference() {
+        let graph = write_colored_graph("diff_groups");
+
+        assert_eq!(
+            diff_groups_output(&graph, false, false),
+            ">0:1-4 LN:i:4\nACGT\n>2:1-4 LN:i:4\nGGCA\n"
+        );
+        assert_eq!(
+            diff_groups_output(&graph, true, false),
+            ">0:1-4 LN:i:4\nACGT\n>0:5-7 LN:i:3\nACG\n>2:1-4 LN:i:4\nGGCA\n"
+        );
+        assert_eq!(
+            diff_groups_output(&graph, false, true),
+            ">2:1-4 LN:i:4\nGGCA\n"
+        );
+
+        std::fs::remove_file(graph.with_extension("colors.dat")).unwrap();
+        std::fs::remove_file(&graph).unwrap();
+    }
+}
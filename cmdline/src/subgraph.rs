@@ -0,0 +1,344 @@
+use crate::pangenome::{create_output_file, unitig_identifier};
+use hashbrown::HashSet;
+use io::sequences_reader::{FastaSequence, SequencesReader};
+use std::io::Write;
+use std::path::Path;
+
+/// Maximum kmer length supported by the 2-bit packed kmers representation
+pub const SUBGRAPH_MAX_K: usize = 64;
+
+/// Calls the callback with the packed 2-bit representation of each kmer of the sequence (canonical unless `forward_only`),
+/// kmers containing non ACGT bases are skipped
+pub(crate) fn for_each_kmer(
+    sequence: &[u8],
+    k: usize,
+    forward_only: bool,
+    mut callback: impl FnMut(u128),
+) {
+    let mask = if k == SUBGRAPH_MAX_K {
+        u128::MAX
+    } else {
+        (1u128 << (2 * k)) - 1
+    };
+
+    let mut forward = 0u128;
+    let mut reverse = 0u128;
+    let mut valid_bases = 0;
+
+    for base in sequence {
+        let value = match base {
+            b'A' => 0,
+            b'C' => 1,
+            b'G' => 2,
+            b'T' => 3,
+            _ => {
+                valid_bases = 0;
+                continue;
+            }
+        };
+
+        forward = ((forward << 2) | value) & mask;
+        reverse = (reverse >> 2) | ((3 - value) << (2 * (k - 1)));
+        valid_bases += 1;
+
+        if valid_bases >= k {
+            callback(if forward_only {
+                forward
+            } else {
+                forward.min(reverse)
+            });
+        }
+    }
+}
+
+/// Returns the links of a unitig header in BCALM2 format L:<+/->:<other id>:<+/->, as (forward, other id, other forward)
+pub(crate) fn parse_links(header: &[u8]) -> impl Iterator<Item = (bool, u64, bool)> + '_ {
+    header
+        .split(|c| c.is_ascii_whitespace())
+        .filter_map(|field| {
+            let field = std::str::from_utf8(field.strip_prefix(b"L:")?).ok()?;
+            let mut parts = field.split(':');
+            let forward = parts.next()? == "+";
+            let other = parts.next()?.parse().ok()?;
+            let other_forward = parts.next()? == "+";
+            Some((forward, other, other_forward))
+        })
+}
+
+pub(crate) fn parse_unitig_id(sequence: &FastaSequence) -> u64 {
+    let identifier = unitig_identifier(sequence.ident);
+    std::str::from_utf8(identifier)
+        .ok()
+        .and_then(|id| id.parse().ok())
+        .unwrap_or_else(|| {
+            println!(
+                "ERROR: Invalid unitig identifier '{}'",
+                String::from_utf8_lossy(identifier)
+            );
+            std::process::exit(1);
+        })
+}
+
+fn for_each_unitig(input_graph: &Path, callback: impl FnMut(FastaSequence)) {
+    SequencesReader::new().process_file_extended(input_graph, callback, None, true, false);
+}
+
+fn strand_sign(forward: bool) -> char {
+    if forward {
+        '+'
+    } else {
+        '-'
+    }
+}
+
+/// Writes a unitig as a GFA segment, keeping the SAM-like header tags and storing the colors in a co:Z: tag
+fn write_gfa_segment(output: &mut impl Write, id: u64, sequence: &FastaSequence) {
+    write!(output, "S\t{}\t", id).unwrap();
+    output.write_all(sequence.seq).unwrap();
+
+    let mut colors = vec![];
+    for field in sequence.ident.split(|c| c.is_ascii_whitespace()).skip(1) {
+        if let Some(color) = field.strip_prefix(b"C:") {
+            colors.push(color);
+        } else if !field.starts_with(b"L:") && field.len() > 5 && field[2] == b':' {
+            output.write_all(b"\t").unwrap();
+            output.write_all(field).unwrap();
+        }
+    }
+
+    if !colors.is_empty() {
+        output.write_all(b"\tco:Z:").unwrap();
+        for (index, color) in colors.iter().enumerate() {
+            if index > 0 {
+                output.write_all(b",").unwrap();
+            }
+            output.write_all(color).unwrap();
+        }
+    }
+    output.write_all(b"\n").unwrap();
+}
+
+/// Extracts the unitigs sharing at least one kmer with the query, along with the unitigs reachable from them
+/// with up to `hops` links. The subgraph is written in GFA format if the output file has the .gfa extension,
+/// otherwise the selected unitigs are written in FASTA format with their original headers
+pub fn extract_subgraph(
+    input_graph: impl AsRef<Path>,
+    input_query: impl AsRef<Path>,
+    output_file: impl AsRef<Path>,
+    k: usize,
+    forward_only: bool,
+    hops: usize,
+) {
+    let input_graph = input_graph.as_ref();
+
+    let mut query_kmers = HashSet::new();
+    SequencesReader::new().process_file_extended(
+        input_query,
+        |sequence| {
+            for_each_kmer(sequence.seq, k, forward_only, |kmer| {
+                query_kmers.insert(kmer);
+            })
+        },
+        None,
+        false,
+        false,
+    );
+
+    // Find the unitigs matching the query, the first hop is done in the same pass as the links are symmetric
+    let mut selected = HashSet::new();
+    let mut frontier = HashSet::new();
+
+    for_each_unitig(input_graph, |sequence| {
+        let mut matches = false;
+        for_each_kmer(sequence.seq, k, forward_only, |kmer| {
+            matches |= query_kmers.contains(&kmer);
+        });
+
+        if matches {
+            selected.insert(parse_unitig_id(&sequence));
+            if hops > 0 {
+                frontier.extend(parse_links(sequence.ident).map(|(_, other, _)| other));
+            }
+        }
+    });
+
+    let seeds_count = selected.len();
+
+    for hop in 1..=hops {
+        frontier.retain(|id| !selected.contains(id));
+        if frontier.is_empty() {
+            break;
+        }
+        selected.extend(frontier.iter().copied());
+
+        if hop == hops {
+            break;
+        }
+
+        let mut next_frontier = HashSet::new();
+        for_each_unitig(input_graph, |sequence| {
+            if frontier.contains(&parse_unitig_id(&sequence)) {
+                next_frontier.extend(parse_links(sequence.ident).map(|(_, other, _)| other));
+            }
+        });
+        frontier = next_frontier;
+    }
+
+    let write_gfa = output_file
+        .as_ref()
+        .extension()
+        .map(|ext| ext == "gfa")
+        .unwrap_or(false);
+
+    let mut output = create_output_file(&output_file);
+    let mut links = vec![];
+
+    if write_gfa {
+        writeln!(output, "H\tVN:Z:1.0").unwrap();
+    }
+
+    for_each_unitig(input_graph, |sequence| {
+        let id = parse_unitig_id(&sequence);
+        if !selected.contains(&id) {
+            return;
+        }
+
+        if write_gfa {
+            write_gfa_segment(&mut output, id, &sequence);
+            // Keep each link only once, from its lowest oriented endpoint
+            links.extend(
+                parse_links(sequence.ident)
+                    .filter(|(forward, other, other_forward)| {
+                        selected.contains(other) && (id, *forward) <= (*other, !*other_forward)
+                    })
+                    .map(|(forward, other, other_forward)| (id, forward, other, other_forward)),
+            );
+        } else {
+            output.write_all(sequence.ident).unwrap();
+            output.write_all(b"\n").unwrap();
+            output.write_all(sequence.seq).unwrap();
+            output.write_all(b"\n").unwrap();
+        }
+    });
+
+    for (id, forward, other, other_forward) in links {
+        writeln!(
+            output,
+            "L\t{}\t{}\t{}\t{}\t{}M",
+            id,
+            strand_sign(forward),
+            other,
+            strand_sign(other_forward),
+            k - 1
+        )
+        .unwrap();
+    }
+
+    println!(
+        "Extracted {} unitigs ({} matching the query) to {}",
+        selected.len(),
+        seeds_count,
+        output_file.as_ref().display()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::subgraph::{extract_subgraph, for_each_kmer, parse_links};
+    use std::path::PathBuf;
+
+    const GRAPH: &str = concat!(
+        ">0 LN:i:4 KC:i:9 km:f:4.5 L:+:1:+ C:0:1 C:2:1\nAACC\n",
+        ">1 LN:i:4 weird:tag L:-:0:- L:+:2:+\nCCGT\n",
+        ">2 LN:i:4 L:-:1:- L:+:3:-\nGTTA\n",
+        ">3 LN:i:4 L:+:2:-\nGGTA\n",
+        ">4 LN:i:4\nCATG\n",
+    );
+
+    fn temp_path(name: &str, extension: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "ggcat_subgraph_{}_{}.{}",
+            name,
+            std::process::id(),
+            extension
+        ))
+    }
+
+    fn collect_kmers(sequence: &[u8], k: usize, forward_only: bool) -> Vec<u128> {
+        let mut kmers = vec![];
+        for_each_kmer(sequence, k, forward_only, |kmer| kmers.push(kmer));
+        kmers
+    }
+
+    #[test]
+    fn kmers_packing() {
+        // The first base is in the high bits
+        assert_eq!(collect_kmers(b"CAA", 3, true), [0b010000]);
+        // The kmers are restarted after a non ACGT base
+        assert_eq!(collect_kmers(b"ACGNTTT", 3, true), [0b000110, 0b111111]);
+        assert_eq!(collect_kmers(b"ACGNTTT", 3, false), [0b000110, 0]);
+        assert_eq!(
+            collect_kmers(b"CCG", 3, false),
+            collect_kmers(b"CGG", 3, false)
+        );
+
+        let mut sequence = vec![b'A'; 64];
+        sequence.push(b'C');
+        assert_eq!(collect_kmers(&sequence, 64, true), [0, 1]);
+
+        // The reverse complement of T..TG is CA..A
+        let mut sequence = vec![b'T'; 63];
+        sequence.push(b'G');
+        assert_eq!(collect_kmers(&sequence, 64, true), [u128::MAX - 1]);
+        assert_eq!(collect_kmers(&sequence, 64, false), [1 << 126]);
+    }
+
+    #[test]
+    fn links_parsing() {
+        let links: Vec<_> = parse_links(b">0 LN:i:4 L:+:1:- C:0:4 L:-:12:+").collect();
+        assert_eq!(links, [(true, 1, false), (false, 12, true)]);
+    }
+
+    #[test]
+    fn subgraph_extraction() {
+        let graph = temp_path("graph", "fasta");
+        std::fs::write(&graph, GRAPH).unwrap();
+        // Reverse complement of the unitig 1
+        let query = temp_path("query", "fasta");
+        std::fs::write(&query, ">query\nACGG\n").unwrap();
+
+        let selected_ids = |hops: usize| {
+            let output = temp_path(&format!("hops_{}", hops), "fasta");
+            extract_subgraph(&graph, &query, &output, 3, false, hops);
+            let contents = std::fs::read_to_string(&output).unwrap();
+            std::fs::remove_file(&output).unwrap();
+            contents
+                .lines()
+                .filter_map(|line| line.strip_prefix('>'))
+                .map(|header| header.split(' ').next().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(selected_ids(0), ["1"]);
+        assert_eq!(selected_ids(1), ["0", "1", "2"]);
+        assert_eq!(selected_ids(2), ["0", "1", "2", "3"]);
+
+        let output = temp_path("gfa", "gfa");
+        extract_subgraph(&graph, &query, &output, 3, false, 1);
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            concat!(
+                "H\tVN:Z:1.0\n",
+                "S\t0\tAACC\tLN:i:4\tKC:i:9\tkm:f:4.5\tco:Z:0:1,2:1\n",
+                "S\t1\tCCGT\tLN:i:4\n",
+                "S\t2\tGTTA\tLN:i:4\n",
+                "L\t0\t+\t1\t+\t2M\n",
+                "L\t1\t+\t2\t+\t2M\n",
+            )
+        );
+
+        std::fs::remove_file(&output).unwrap();
+        std::fs::remove_file(&graph).unwrap();
+        std::fs::remove_file(&query).unwrap();
+    }
+}
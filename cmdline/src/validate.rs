@@ -0,0 +1,380 @@
+use crate::pangenome::parse_colors_runs;
+use crate::subgraph::{for_each_kmer, parse_links, parse_unitig_id};
+use hashbrown::{HashMap, HashSet};
+use io::sequences_reader::SequencesReader;
+use std::path::{Path, PathBuf};
+
+/// Maximum number of errors printed for each check, the others are only counted
+const MAX_REPORTED_ERRORS: usize = 10;
+
+struct Check {
+    name: &'static str,
+    errors: usize,
+}
+
+impl Check {
+    fn new(name: &'static str) -> Self {
+        Self { name, errors: 0 }
+    }
+
+    fn fail(&mut self, message: impl FnOnce() -> String) {
+        if self.errors < MAX_REPORTED_ERRORS {
+            println!("ERROR: {}", message());
+        }
+        self.errors += 1;
+    }
+}
+
+/// First and last kmers of a unitig, in its forward orientation
+struct UnitigEnds {
+    first: u128,
+    last: u128,
+}
+
+fn encode_kmer(bases: &[u8]) -> Option<u128> {
+    let mut kmer = 0;
+    for base in bases {
+        kmer = (kmer << 2)
+            | match base {
+                b'A' => 0,
+                b'C' => 1,
+                b'G' => 2,
+                b'T' => 3,
+                _ => return None,
+            };
+    }
+    Some(kmer)
+}
+
+fn reverse_complement(mut kmer: u128, k: usize) -> u128 {
+    let mut result = 0;
+    for _ in 0..k {
+        result = (result << 2) | (3 - (kmer & 3));
+        kmer >>= 2;
+    }
+    result
+}
+
+struct KmersGraph {
+    k: usize,
+    forward_only: bool,
+    kmers: HashMap<u128, u64>,
+}
+
+impl KmersGraph {
+    fn mask(&self) -> u128 {
+        if self.k == 64 {
+            u128::MAX
+        } else {
+            (1 << (2 * self.k)) - 1
+        }
+    }
+
+    fn lookup(&self, kmer: u128) -> Option<u64> {
+        let kmer = if self.forward_only {
+            kmer
+        } else {
+            kmer.min(reverse_complement(kmer, self.k))
+        };
+        self.kmers.get(&kmer).copied()
+    }
+
+    /// Returns the number of kmers following the given one in the graph, along with the last one found
+    fn successors(&self, kmer: u128) -> (usize, Option<u128>) {
+        let mut count = 0;
+        let mut last = None;
+        for base in 0..4 {
+            let next = ((kmer << 2) | base) & self.mask();
+            if self.lookup(next).is_some() {
+                count += 1;
+                last = Some(next);
+            }
+        }
+        (count, last)
+    }
+
+    /// Returns the number of kmers preceding the given one in the graph, along with the last one found
+    fn predecessors(&self, kmer: u128) -> (usize, Option<u128>) {
+        let mut count = 0;
+        let mut last = None;
+        for base in 0..4 {
+            let previous = (kmer >> 2) | (base << (2 * (self.k - 1)));
+            if self.lookup(previous).is_some() {
+                count += 1;
+                last = Some(previous);
+            }
+        }
+        (count, last)
+    }
+
+    /// Checks that a unitig cannot be extended unambiguously past any of its ends
+    fn is_maximal(&self, id: u64, ends: &UnitigEnds) -> bool {
+        if let (1, Some(next)) = self.successors(ends.last) {
+            if self.predecessors(next).0 == 1 && self.lookup(next) != Some(id) {
+                return false;
+            }
+        }
+
+        if let (1, Some(previous)) = self.predecessors(ends.first) {
+            if self.successors(previous).0 == 1 && self.lookup(previous) != Some(id) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Checks the invariants of a graph built by ggcat: the LN:i: tags match the sequences lengths,
+/// the C: runs cover all the kmers, the links are symmetric and join overlapping unitigs and,
+/// unless `tigs` is set, each kmer appears only once and the unitigs are maximal.
+/// If input reads are given, also checks that all their kmers with at least `min_multiplicity`
+/// occurrences are present in the graph. Returns true if the graph is valid
+pub fn validate_graph(
+    input_graph: impl AsRef<Path>,
+    k: usize,
+    forward_only: bool,
+    tigs: bool,
+    input_reads: &[PathBuf],
+    min_multiplicity: usize,
+) -> bool {
+    let mut sequences_check = Check::new("sequences");
+    let mut lengths_check = Check::new("LN:i: lengths");
+    let mut colors_check = Check::new("C: colors runs");
+    let mut unique_kmers_check = Check::new("unique kmers");
+    let mut maximality_check = Check::new("maximal unitigs");
+    let mut links_check = Check::new("links");
+    let mut reads_check = Check::new("input reads kmers");
+
+    let mut graph = KmersGraph {
+        k,
+        forward_only,
+        kmers: HashMap::new(),
+    };
+    let mut unitigs = HashMap::new();
+    let mut links = HashSet::new();
+
+    SequencesReader::new().process_file_extended(
+        &input_graph,
+        |sequence| {
+            let id = parse_unitig_id(&sequence);
+
+            for field in sequence.ident.split(|c| c.is_ascii_whitespace()) {
+                if let Some(length) = field.strip_prefix(b"LN:i:") {
+                    let length = std::str::from_utf8(length)
+                        .ok()
+                        .and_then(|l| l.parse().ok());
+                    if length != Some(sequence.seq.len()) {
+                        lengths_check.fail(|| {
+                            format!(
+                                "Unitig {} has length {} but its LN:i: tag is {}",
+                                id,
+                                sequence.seq.len(),
+                                String::from_utf8_lossy(field)
+                            )
+                        });
+                    }
+                }
+            }
+
+            if sequence.seq.len() < k {
+                sequences_check.fail(|| format!("Unitig {} is shorter than k", id));
+                return;
+            }
+
+            let (first, last) = match (
+                encode_kmer(&sequence.seq[..k]),
+                encode_kmer(&sequence.seq[sequence.seq.len() - k..]),
+            ) {
+                (Some(first), Some(last)) if sequence.seq.iter().all(|b| b"ACGT".contains(b)) => {
+                    (first, last)
+                }
+                _ => {
+                    sequences_check.fail(|| format!("Unitig {} contains non ACGT bases", id));
+                    return;
+                }
+            };
+
+            if unitigs.insert(id, UnitigEnds { first, last }).is_some() {
+                sequences_check.fail(|| format!("Duplicated unitig id {}", id));
+            }
+
+            let kmers_count = (sequence.seq.len() - k + 1) as u64;
+            let colors_kmers: u64 = parse_colors_runs(sequence.ident)
+                .map(|(_, count)| count)
+                .sum();
+            if colors_kmers != 0 && colors_kmers != kmers_count {
+                colors_check.fail(|| {
+                    format!(
+                        "Unitig {} has {} kmers but its C: runs cover {}",
+                        id, kmers_count, colors_kmers
+                    )
+                });
+            }
+
+            for_each_kmer(sequence.seq, k, forward_only, |kmer| {
+                if let Some(other) = graph.kmers.insert(kmer, id) {
+                    if !tigs {
+                        unique_kmers_check
+                            .fail(|| format!("Unitigs {} and {} share the same kmer", other, id));
+                    }
+                }
+            });
+
+            links.extend(
+                parse_links(sequence.ident)
+                    .map(|(forward, other, other_forward)| (id, forward, other, other_forward)),
+            );
+        },
+        None,
+        true,
+        false,
+    );
+
+    if !tigs {
+        for (id, ends) in unitigs.iter() {
+            if !graph.is_maximal(*id, ends) {
+                maximality_check.fail(|| format!("Unitig {} can be extended", id));
+            }
+        }
+    }
+
+    let overlap_mask = graph.mask() >> 2;
+    for (id, forward, other, other_forward) in links.iter().copied() {
+        let (ends, other_ends) = match (unitigs.get(&id), unitigs.get(&other)) {
+            (Some(ends), Some(other_ends)) => (ends, other_ends),
+            _ => {
+                links_check.fail(|| format!("Link from {} to the missing unitig {}", id, other));
+                continue;
+            }
+        };
+
+        if !links.contains(&(other, !other_forward, id, !forward)) {
+            links_check.fail(|| format!("Link from {} to {} is not symmetric", id, other));
+        }
+
+        let end = if forward {
+            ends.last
+        } else {
+            reverse_complement(ends.first, k)
+        };
+        let other_start = if other_forward {
+            other_ends.first
+        } else {
+            reverse_complement(other_ends.last, k)
+        };
+
+        if end & overlap_mask != other_start >> 2 {
+            links_check.fail(|| {
+                format!(
+                    "Link from {} to {} joins sequences not overlapping by k - 1 bases",
+                    id, other
+                )
+            });
+        }
+    }
+
+    if !input_reads.is_empty() {
+        let mut reads_kmers = HashMap::new();
+        for input in input_reads {
+            SequencesReader::new().process_file_extended(
+                input,
+                |sequence| {
+                    for_each_kmer(sequence.seq, k, forward_only, |kmer| {
+                        *reads_kmers.entry(kmer).or_insert(0usize) += 1;
+                    });
+                },
+                None,
+                false,
+                false,
+            );
+        }
+
+        for (kmer, count) in reads_kmers {
+            if count >= min_multiplicity && !graph.kmers.contains_key(&kmer) {
+                reads_check.fail(|| {
+                    format!(
+                        "Kmer with multiplicity {} in the input reads is missing from the graph",
+                        count
+                    )
+                });
+            }
+        }
+    }
+
+    println!(
+        "Checked {} unitigs with {} distinct kmers and {} links",
+        unitigs.len(),
+        graph.kmers.len(),
+        links.len()
+    );
+
+    let checks = [
+        sequences_check,
+        lengths_check,
+        colors_check,
+        unique_kmers_check,
+        maximality_check,
+        links_check,
+        reads_check,
+    ];
+
+    for check in checks.iter().filter(|c| c.errors > 0) {
+        println!("Check '{}' failed with {} errors", check.name, check.errors);
+    }
+
+    checks.iter().all(|c| c.errors == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::validate::validate_graph;
+    use std::path::PathBuf;
+
+    const GRAPH: &str = concat!(
+        ">0 LN:i:5 L:+:1:+ L:+:2:+\nACCGA\n",
+        ">1 LN:i:3 L:-:0:-\nGAT\n",
+        ">2 LN:i:3 L:-:0:-\nGAG\n",
+    );
+
+    fn write_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "ggcat_validate_{}_{}.fasta",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn valid_graph() {
+        let graph = write_file("valid_graph", GRAPH);
+        assert!(validate_graph(&graph, 3, false, false, &[], 2));
+        std::fs::remove_file(&graph).unwrap();
+    }
+
+    #[test]
+    fn missing_kmer() {
+        let graph = write_file("missing_kmer", GRAPH);
+        // The last kmer, ATT, is missing from the graph
+        let reads = [write_file("missing_kmer_reads", ">read\nACCGATT\n")];
+
+        assert!(!validate_graph(&graph, 3, false, false, &reads, 1));
+        assert!(validate_graph(&graph, 3, false, false, &reads, 2));
+
+        std::fs::remove_file(&graph).unwrap();
+        std::fs::remove_file(&reads[0]).unwrap();
+    }
+
+    #[test]
+    fn duplicated_kmer() {
+        let graph = write_file("duplicated_kmer", &format!("{}>3 LN:i:3\nCGG\n", GRAPH));
+
+        assert!(!validate_graph(&graph, 3, false, false, &[], 2));
+        // Repeated kmers are allowed in tigs
+        assert!(validate_graph(&graph, 3, false, true, &[], 2));
+
+        std::fs::remove_file(&graph).unwrap();
+    }
+}
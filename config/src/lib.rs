@@ -1,10 +1,10 @@
 // use crate::RunLengthColorsSerializer;
 use parallel_processor::buckets::writers::compressed_binary_writer::{
-    CompressedCheckpointSize, CompressionLevelInfo,
+    CompressedCheckpointSize, CompressionCodec, CompressionLevelInfo,
 };
 use parallel_processor::memory_data_size::MemoryDataSize;
 use parallel_processor::memory_fs::file::internal::MemoryFileMode;
-use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
 use std::time::Duration;
 
 pub type BucketIndexType = u16;
@@ -63,6 +63,8 @@ pub const MAX_INTERMEDIATE_MAP_SIZE: u64 = 1024 * 1024 * 32;
 pub const READ_FLAG_INCL_BEGIN: u8 = 1 << 0;
 pub const READ_FLAG_INCL_END: u8 = 1 << 1;
 
+pub const DEFAULT_MIN_MULTIPLICITY: usize = 2;
+
 pub const COLORS_SINGLE_BATCH_SIZE: u64 = 20000;
 pub const QUERIES_COUNT_MIN_BATCH: u64 = 1000;
 
@@ -86,7 +88,33 @@ impl SwapPriority {
 pub static KEEP_FILES: AtomicBool = AtomicBool::new(false);
 pub static INTERMEDIATE_COMPRESSION_LEVEL_SLOW: AtomicU32 = AtomicU32::new(3);
 pub static INTERMEDIATE_COMPRESSION_LEVEL_FAST: AtomicU32 = AtomicU32::new(0);
+/// Bitmask of the swap priorities whose intermediate files are compressed with zstd instead of lz4
+pub static INTERMEDIATE_ZSTD_SWAP_PRIORITIES: AtomicU64 = AtomicU64::new(0);
 pub static PREFER_MEMORY: AtomicBool = AtomicBool::new(false);
+pub static UNITIGS_ABUNDANCE_TAGS: AtomicBool = AtomicBool::new(false);
+/// Tracks the unitigs kmer abundances also if their tags are not written, e.g. for the graph cleaning
+pub static TRACK_UNITIGS_ABUNDANCE: AtomicBool = AtomicBool::new(false);
+pub static UNITIGS_ABUNDANCE_FULL_TAGS: AtomicBool = AtomicBool::new(false);
+pub static COLORS_ABUNDANCES: AtomicBool = AtomicBool::new(false);
+pub static SPLIT_UNITIGS_BY_COLORS: AtomicBool = AtomicBool::new(false);
+/// Estimated total size in bytes of the inputs read from the standard input or from named pipes, 0 if unknown
+pub static STREAMED_INPUTS_SIZE_HINT: AtomicU64 = AtomicU64::new(0);
+/// Maximum memory in bytes used to decompress each gzip input file with multiple threads
+pub static PARALLEL_DECOMPRESSION_MEMORY: AtomicUsize = AtomicUsize::new(1024 * 1024 * 512);
+/// Minimum Phred quality of the FASTQ bases, the ones below it are treated as N (0 to disable)
+pub static MIN_BASE_QUALITY: AtomicU32 = AtomicU32::new(0);
+/// Minimum product of the correctness probabilities of the bases of each kmer, as f64 bits (0 to disable)
+pub static MIN_KMER_CORRECTNESS_PROBABILITY: AtomicU64 = AtomicU64::new(0);
+/// Maximum number of bases of the IUPAC ambiguity codes expanded into all the compatible kmers (0 to disable)
+pub static IUPAC_EXPANSION_MAX_BASES: AtomicUsize = AtomicUsize::new(0);
+/// Stop at the first malformed record or read error of the input files, instead of skipping them with a warning
+pub static STRICT_INPUT_PARSING: AtomicBool = AtomicBool::new(false);
+
+/// The FASTQ quality lines are parsed only if a quality filter is enabled
+pub fn use_fastq_qualities() -> bool {
+    MIN_BASE_QUALITY.load(Ordering::Relaxed) != 0
+        || MIN_KMER_CORRECTNESS_PROBABILITY.load(Ordering::Relaxed) != 0
+}
 
 pub fn get_memory_mode(swap_priority: usize) -> MemoryFileMode {
     if PREFER_MEMORY.load(Ordering::Relaxed) {
@@ -96,9 +124,16 @@ pub fn get_memory_mode(swap_priority: usize) -> MemoryFileMode {
     }
 }
 
-pub fn get_compression_level_info() -> CompressionLevelInfo {
+pub fn get_compression_level_info(swap_priority: usize) -> CompressionLevelInfo {
     CompressionLevelInfo {
         fast_disk: INTERMEDIATE_COMPRESSION_LEVEL_FAST.load(Ordering::Relaxed),
         slow_disk: INTERMEDIATE_COMPRESSION_LEVEL_SLOW.load(Ordering::Relaxed),
+        codec: if INTERMEDIATE_ZSTD_SWAP_PRIORITIES.load(Ordering::Relaxed) & (1 << swap_priority)
+            != 0
+        {
+            CompressionCodec::Zstd
+        } else {
+            CompressionCodec::Lz4
+        },
     }
 }
@@ -12,19 +12,35 @@ struct GzipParams {
     input: PathBuf,
     #[structopt(short)]
     simulate: bool,
+    /// Decompress using multiple threads
+    #[structopt(short, long)]
+    parallel: bool,
+    /// Maximum memory used by the parallel decompression (MB)
+    #[structopt(short, long, default_value = "1024")]
+    memory: usize,
 }
 
 fn main() {
     let params: GzipParams = GzipParams::from_args();
 
+    let parallel = params.parallel;
+    let max_memory = params.memory * 1024 * 1024;
+    let decompress = |input: PathBuf, func: &mut dyn FnMut(&[u8]) -> Result<(), ()>, buf_size| {
+        if parallel {
+            decompress_file_parallel(input, func, buf_size, max_memory)
+        } else {
+            decompress_file_buffered(input, func, buf_size)
+        }
+    };
+
     if params.simulate {
-        decompress_file_buffered(params.input, move |data| Ok(()), 1024 * 512).unwrap();
+        decompress(params.input, &mut |data| Ok(()), 1024 * 512).unwrap();
     } else {
         let mut write_file = File::create(&params.input.with_extension("")).unwrap();
 
-        decompress_file_buffered(
+        decompress(
             params.input,
-            move |data| write_file.write_all(data).map_err(|_| ()),
+            &mut |data| write_file.write_all(data).map_err(|_| ()),
             1024 * 512 * 1024,
         )
         .unwrap();
@@ -0,0 +1,763 @@
+/*
+ * DEFLATE compressor, following the structure of libdeflate's greedy and lazy compressors:
+ * matches are found with hash chains over the last 32KB, and each block is written with the
+ * cheapest among dynamic Huffman, static Huffman and uncompressed encodings.
+ */
+
+use crate::deflate_constants::*;
+
+/// Parameters of each compression level, from 1 (fastest) to 9 (best compression)
+struct LevelParams {
+    /// Maximum number of hash chain entries checked for each position
+    max_search_depth: usize,
+    /// Matches at least this long are taken without searching further
+    nice_match_length: usize,
+    /// Defer the choice of a match if the next position has a longer one
+    lazy: bool,
+}
+
+const LEVELS_PARAMS: [LevelParams; 9] = [
+    LevelParams {
+        max_search_depth: 4,
+        nice_match_length: 16,
+        lazy: false,
+    },
+    LevelParams {
+        max_search_depth: 8,
+        nice_match_length: 32,
+        lazy: false,
+    },
+    LevelParams {
+        max_search_depth: 16,
+        nice_match_length: 48,
+        lazy: false,
+    },
+    LevelParams {
+        max_search_depth: 16,
+        nice_match_length: 32,
+        lazy: true,
+    },
+    LevelParams {
+        max_search_depth: 32,
+        nice_match_length: 64,
+        lazy: true,
+    },
+    LevelParams {
+        max_search_depth: 64,
+        nice_match_length: 128,
+        lazy: true,
+    },
+    LevelParams {
+        max_search_depth: 128,
+        nice_match_length: 258,
+        lazy: true,
+    },
+    LevelParams {
+        max_search_depth: 512,
+        nice_match_length: 258,
+        lazy: true,
+    },
+    LevelParams {
+        max_search_depth: 2048,
+        nice_match_length: 258,
+        lazy: true,
+    },
+];
+
+pub const MAX_COMPRESSION_LEVEL: u32 = 9;
+
+const HASH_ORDER: usize = 15;
+const HASH_SIZE: usize = 1 << HASH_ORDER;
+const WINDOW_MASK: usize = DEFLATE_MAX_WINDOW_SIZE - 1;
+const NO_POSITION: u32 = u32::MAX;
+
+/// Matches are found by hashing 4 bytes, so shorter ones are never used
+const MIN_MATCH_LEN: usize = 4;
+
+/// Maximum number of literals and matches in a block
+const MAX_BLOCK_ITEMS: usize = 1 << 15;
+
+/// Maximum length of an uncompressed block
+const MAX_STORED_BLOCK_LEN: usize = 65535;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const OFFSET_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const OFFSET_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+const PRECODE_LENS_PERMUTATION: [usize; DEFLATE_NUM_PRECODE_SYMS] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+const PRECODE_EXTRA_BITS: [u8; DEFLATE_NUM_PRECODE_SYMS] =
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 3, 7];
+
+/// Index of the last entry of `bases` not greater than `value`
+const fn find_slot(bases: &[u16], value: usize) -> u8 {
+    let mut slot = 0;
+    while slot + 1 < bases.len() && bases[slot + 1] as usize <= value {
+        slot += 1;
+    }
+    slot as u8
+}
+
+const LENGTH_SLOTS: [u8; DEFLATE_MAX_MATCH_LEN + 1] = {
+    let mut slots = [0; DEFLATE_MAX_MATCH_LEN + 1];
+    let mut length = DEFLATE_MIN_MATCH_LEN;
+    while length <= DEFLATE_MAX_MATCH_LEN {
+        slots[length] = find_slot(&LENGTH_BASE, length);
+        length += 1;
+    }
+    slots
+};
+
+/// Slots of the offsets up to 256, and of the longer ones with a granularity of 128
+/// (their slots bases are all aligned to 128)
+const OFFSET_SLOTS: [[u8; 256]; 2] = {
+    let mut slots = [[0; 256]; 2];
+    let mut i = 0;
+    while i < 256 {
+        slots[0][i] = find_slot(&OFFSET_BASE, i + 1);
+        slots[1][i] = find_slot(&OFFSET_BASE, (i << 7) + 1);
+        i += 1;
+    }
+    slots
+};
+
+#[inline(always)]
+fn offset_slot(offset: usize) -> usize {
+    if offset <= 256 {
+        OFFSET_SLOTS[0][offset - 1] as usize
+    } else {
+        OFFSET_SLOTS[1][(offset - 1) >> 7] as usize
+    }
+}
+
+/// A literal (offset 0) or a match
+#[derive(Copy, Clone)]
+struct Item {
+    litlen: u16,
+    offset: u16,
+}
+
+struct HuffmanCode<const N: usize> {
+    lens: [u8; N],
+    codewords: [u16; N],
+}
+
+impl<const N: usize> HuffmanCode<N> {
+    fn new() -> Self {
+        Self {
+            lens: [0; N],
+            codewords: [0; N],
+        }
+    }
+
+    /// Builds a length-limited Huffman code for the given symbols frequencies.
+    /// The code is always complete, using at least two symbols
+    fn build(&mut self, freqs: &[u32; N], max_len: usize) {
+        self.lens = [0; N];
+
+        let mut symbols: Vec<_> = (0..N).filter(|&s| freqs[s] > 0).collect();
+        if symbols.len() < 2 {
+            let used = symbols.first().copied().unwrap_or(0);
+            self.lens[used] = 1;
+            self.lens[if used == 0 { 1 } else { 0 }] = 1;
+        } else {
+            symbols.sort_by_key(|&s| (freqs[s], s));
+            let leaves_count = symbols.len();
+
+            // Huffman tree built with two queues, as the internal nodes are created in order of weight
+            let mut weights = Vec::with_capacity(leaves_count * 2);
+            weights.extend(symbols.iter().map(|&s| freqs[s] as u64));
+            let mut parents = vec![0; leaves_count * 2 - 1];
+            let mut next_leaf = 0;
+            let mut next_node = leaves_count;
+
+            for node in leaves_count..(leaves_count * 2 - 1) {
+                let mut weight = 0;
+                for _ in 0..2 {
+                    let child = if next_leaf < leaves_count
+                        && (next_node >= node || weights[next_leaf] <= weights[next_node])
+                    {
+                        next_leaf += 1;
+                        next_leaf - 1
+                    } else {
+                        next_node += 1;
+                        next_node - 1
+                    };
+                    parents[child] = node;
+                    weight += weights[child];
+                }
+                weights.push(weight);
+            }
+
+            let mut depths = vec![0; leaves_count * 2 - 1];
+            let mut lens_count = [0usize; DEFLATE_MAX_CODEWORD_LEN + 1];
+            for node in (0..(leaves_count * 2 - 2)).rev() {
+                depths[node] = depths[parents[node]] + 1;
+                if node < leaves_count {
+                    lens_count[depths[node].min(max_len)] += 1;
+                }
+            }
+
+            // Shorten the codewords over the maximum length, keeping the code complete
+            let mut kraft_sum: usize = (1..=max_len)
+                .map(|len| lens_count[len] << (max_len - len))
+                .sum();
+            while kraft_sum > (1 << max_len) {
+                lens_count[max_len] -= 1;
+                for len in (1..max_len).rev() {
+                    if lens_count[len] > 0 {
+                        lens_count[len] -= 1;
+                        lens_count[len + 1] += 2;
+                        break;
+                    }
+                }
+                kraft_sum -= 1;
+            }
+
+            // The least frequent symbols get the longest codewords
+            let mut symbols = symbols.into_iter();
+            for len in (1..=max_len).rev() {
+                for symbol in symbols.by_ref().take(lens_count[len]) {
+                    self.lens[symbol] = len as u8;
+                }
+            }
+        }
+
+        self.assign_codewords();
+    }
+
+    fn from_lens(lens: [u8; N]) -> Self {
+        let mut code = Self {
+            lens,
+            codewords: [0; N],
+        };
+        code.assign_codewords();
+        code
+    }
+
+    /// Assigns the canonical codewords, bit reversed as they are written starting from the least significant bit
+    fn assign_codewords(&mut self) {
+        let mut lens_count = [0u16; DEFLATE_MAX_CODEWORD_LEN + 1];
+        for &len in self.lens.iter() {
+            lens_count[len as usize] += 1;
+        }
+        lens_count[0] = 0;
+
+        let mut next_codeword = [0u16; DEFLATE_MAX_CODEWORD_LEN + 1];
+        for len in 1..=DEFLATE_MAX_CODEWORD_LEN {
+            next_codeword[len] = (next_codeword[len - 1] + lens_count[len - 1]) << 1;
+        }
+
+        for (symbol, &len) in self.lens.iter().enumerate() {
+            if len > 0 {
+                let codeword = next_codeword[len as usize];
+                next_codeword[len as usize] += 1;
+                self.codewords[symbol] = codeword.reverse_bits() >> (16 - len);
+            }
+        }
+    }
+
+    fn cost(&self, freqs: &[u32; N]) -> usize {
+        freqs
+            .iter()
+            .zip(self.lens.iter())
+            .map(|(&freq, &len)| freq as usize * len as usize)
+            .sum()
+    }
+}
+
+struct BitWriter<'a> {
+    output: &'a mut Vec<u8>,
+    bitbuf: u64,
+    bitcount: u32,
+}
+
+impl<'a> BitWriter<'a> {
+    #[inline(always)]
+    fn put_bits(&mut self, bits: u32, count: u32) {
+        self.bitbuf |= (bits as u64) << self.bitcount;
+        self.bitcount += count;
+        if self.bitcount >= 32 {
+            self.output
+                .extend_from_slice(&(self.bitbuf as u32).to_le_bytes());
+            self.bitbuf >>= 32;
+            self.bitcount -= 32;
+        }
+    }
+
+    /// Pads the output to a byte boundary
+    fn flush(&mut self) {
+        let bytes = ((self.bitcount + 7) / 8) as usize;
+        self.output
+            .extend_from_slice(&self.bitbuf.to_le_bytes()[..bytes]);
+        self.bitbuf = 0;
+        self.bitcount = 0;
+    }
+}
+
+/*
+ * The DEFLATE compressor structure, holding the matchfinder tables and the current block.
+ * It can be reused for multiple independent streams.
+ */
+pub struct LibdeflateCompressor {
+    level: u32,
+    hash_heads: Vec<u32>,
+    prev_positions: Vec<u32>,
+    items: Vec<Item>,
+    litlen_freqs: [u32; DEFLATE_NUM_LITLEN_SYMS],
+    offset_freqs: [u32; DEFLATE_NUM_OFFSET_SYMS],
+    static_litlen_code: HuffmanCode<DEFLATE_NUM_LITLEN_SYMS>,
+    static_offset_code: HuffmanCode<DEFLATE_NUM_OFFSET_SYMS>,
+}
+
+/// Allocates a compressor for the given level, from 0 (no compression) to 9 (best compression)
+pub fn libdeflate_alloc_compressor(level: u32) -> LibdeflateCompressor {
+    assert!(
+        level <= MAX_COMPRESSION_LEVEL,
+        "Invalid compression level: {}",
+        level
+    );
+
+    let mut static_litlen_lens = [0; DEFLATE_NUM_LITLEN_SYMS];
+    static_litlen_lens[0..144].fill(8);
+    static_litlen_lens[144..256].fill(9);
+    static_litlen_lens[256..280].fill(7);
+    static_litlen_lens[280..288].fill(8);
+
+    LibdeflateCompressor {
+        level,
+        hash_heads: vec![NO_POSITION; HASH_SIZE],
+        prev_positions: vec![NO_POSITION; DEFLATE_MAX_WINDOW_SIZE],
+        items: Vec::with_capacity(MAX_BLOCK_ITEMS),
+        litlen_freqs: [0; DEFLATE_NUM_LITLEN_SYMS],
+        offset_freqs: [0; DEFLATE_NUM_OFFSET_SYMS],
+        static_litlen_code: HuffmanCode::from_lens(static_litlen_lens),
+        static_offset_code: HuffmanCode::from_lens([5; DEFLATE_NUM_OFFSET_SYMS]),
+    }
+}
+
+#[inline(always)]
+fn hash4(data: &[u8], position: usize) -> usize {
+    let value = u32::from_le_bytes(data[position..position + 4].try_into().unwrap());
+    (value.wrapping_mul(0x1E35A7BD) >> (32 - HASH_ORDER)) as usize
+}
+
+#[inline(always)]
+fn match_length(data: &[u8], first: usize, second: usize, max_len: usize) -> usize {
+    let mut length = 0;
+    while length + 8 <= max_len {
+        let a = u64::from_le_bytes(data[first + length..first + length + 8].try_into().unwrap());
+        let b = u64::from_le_bytes(
+            data[second + length..second + length + 8]
+                .try_into()
+                .unwrap(),
+        );
+        if a != b {
+            return length + ((a ^ b).trailing_zeros() / 8) as usize;
+        }
+        length += 8;
+    }
+    while length < max_len && data[first + length] == data[second + length] {
+        length += 1;
+    }
+    length
+}
+
+impl LibdeflateCompressor {
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    /// Inserts the positions up to `end` (excluded) in the hash chains
+    #[inline(always)]
+    fn insert_positions(&mut self, data: &[u8], next_insert: &mut usize, end: usize) {
+        let end = end.min((data.len() + 1).saturating_sub(MIN_MATCH_LEN));
+        while *next_insert < end {
+            let hash = hash4(data, *next_insert);
+            self.prev_positions[*next_insert & WINDOW_MASK] = self.hash_heads[hash];
+            self.hash_heads[hash] = *next_insert as u32;
+            *next_insert += 1;
+        }
+    }
+
+    /// Returns the longest match (length and offset) at `position`, not yet inserted in the hash chains
+    #[inline(always)]
+    fn find_match(&self, data: &[u8], position: usize, params: &LevelParams) -> (usize, usize) {
+        let max_len = DEFLATE_MAX_MATCH_LEN.min(data.len() - position);
+        if max_len < MIN_MATCH_LEN {
+            return (0, 0);
+        }
+        let nice_len = params.nice_match_length.min(max_len);
+
+        let mut best = (0, 0);
+        let mut candidate = self.hash_heads[hash4(data, position)];
+
+        for _ in 0..params.max_search_depth {
+            if candidate == NO_POSITION || position - candidate as usize > DEFLATE_MAX_MATCH_OFFSET
+            {
+                break;
+            }
+            let candidate_pos = candidate as usize;
+
+            if best.0 == 0 || data[candidate_pos + best.0] == data[position + best.0] {
+                let length = match_length(data, candidate_pos, position, max_len);
+                if length >= MIN_MATCH_LEN && length > best.0 {
+                    best = (length, position - candidate_pos);
+                    if length >= nice_len {
+                        break;
+                    }
+                }
+            }
+            candidate = self.prev_positions[candidate_pos & WINDOW_MASK];
+        }
+        best
+    }
+
+    #[inline(always)]
+    fn add_literal(&mut self, literal: u8) {
+        self.litlen_freqs[literal as usize] += 1;
+        self.items.push(Item {
+            litlen: literal as u16,
+            offset: 0,
+        });
+    }
+
+    #[inline(always)]
+    fn add_match(&mut self, length: usize, offset: usize) {
+        self.litlen_freqs[DEFLATE_NUM_LITERALS + 1 + LENGTH_SLOTS[length] as usize] += 1;
+        self.offset_freqs[offset_slot(offset)] += 1;
+        self.items.push(Item {
+            litlen: length as u16,
+            offset: offset as u16,
+        });
+    }
+
+    fn write_items(
+        &self,
+        writer: &mut BitWriter,
+        litlen_code: &HuffmanCode<DEFLATE_NUM_LITLEN_SYMS>,
+        offset_code: &HuffmanCode<DEFLATE_NUM_OFFSET_SYMS>,
+    ) {
+        for item in self.items.iter() {
+            if item.offset == 0 {
+                let symbol = item.litlen as usize;
+                writer.put_bits(
+                    litlen_code.codewords[symbol] as u32,
+                    litlen_code.lens[symbol] as u32,
+                );
+            } else {
+                let length = item.litlen as usize;
+                let slot = LENGTH_SLOTS[length] as usize;
+                let symbol = DEFLATE_NUM_LITERALS + 1 + slot;
+                writer.put_bits(
+                    litlen_code.codewords[symbol] as u32,
+                    litlen_code.lens[symbol] as u32,
+                );
+                writer.put_bits(
+                    (length - LENGTH_BASE[slot] as usize) as u32,
+                    LENGTH_EXTRA_BITS[slot] as u32,
+                );
+
+                let offset = item.offset as usize;
+                let slot = offset_slot(offset);
+                writer.put_bits(
+                    offset_code.codewords[slot] as u32,
+                    offset_code.lens[slot] as u32,
+                );
+                writer.put_bits(
+                    (offset - OFFSET_BASE[slot] as usize) as u32,
+                    OFFSET_EXTRA_BITS[slot] as u32,
+                );
+            }
+        }
+        writer.put_bits(
+            litlen_code.codewords[DEFLATE_END_OF_BLOCK] as u32,
+            litlen_code.lens[DEFLATE_END_OF_BLOCK] as u32,
+        );
+    }
+
+    /// Writes the block made of the current items, encoding `block_data`, choosing its cheapest representation
+    fn flush_block(&mut self, writer: &mut BitWriter, block_data: &[u8], is_final: bool) {
+        self.litlen_freqs[DEFLATE_END_OF_BLOCK] += 1;
+
+        let extra_bits: usize =
+            (0..LENGTH_EXTRA_BITS.len())
+                .map(|slot| {
+                    self.litlen_freqs[DEFLATE_NUM_LITERALS + 1 + slot] as usize
+                        * LENGTH_EXTRA_BITS[slot] as usize
+                })
+                .chain((0..OFFSET_EXTRA_BITS.len()).map(|slot| {
+                    self.offset_freqs[slot] as usize * OFFSET_EXTRA_BITS[slot] as usize
+                }))
+                .sum();
+
+        let mut litlen_code = HuffmanCode::new();
+        let mut offset_code = HuffmanCode::new();
+        litlen_code.build(&self.litlen_freqs, DEFLATE_MAX_LITLEN_CODEWORD_LEN);
+        offset_code.build(&self.offset_freqs, DEFLATE_MAX_OFFSET_CODEWORD_LEN);
+        let header = DynamicHeader::new(&litlen_code, &offset_code);
+
+        let dynamic_cost = header.cost()
+            + litlen_code.cost(&self.litlen_freqs)
+            + offset_code.cost(&self.offset_freqs)
+            + extra_bits;
+        let static_cost = self.static_litlen_code.cost(&self.litlen_freqs)
+            + self.static_offset_code.cost(&self.offset_freqs)
+            + extra_bits;
+        let stored_cost = stored_blocks_cost(writer.bitcount, block_data.len());
+
+        if stored_cost <= dynamic_cost.min(static_cost) + 3 {
+            write_stored_blocks(writer, block_data, is_final);
+        } else if dynamic_cost < static_cost {
+            writer.put_bits(is_final as u32, 1);
+            writer.put_bits(DEFLATE_BLOCKTYPE_DYNAMIC_HUFFMAN, 2);
+            header.write(writer);
+            self.write_items(writer, &litlen_code, &offset_code);
+        } else {
+            writer.put_bits(is_final as u32, 1);
+            writer.put_bits(DEFLATE_BLOCKTYPE_STATIC_HUFFMAN, 2);
+            self.write_items(writer, &self.static_litlen_code, &self.static_offset_code);
+        }
+
+        self.items.clear();
+        self.litlen_freqs = [0; DEFLATE_NUM_LITLEN_SYMS];
+        self.offset_freqs = [0; DEFLATE_NUM_OFFSET_SYMS];
+    }
+}
+
+/// Size in bits of the uncompressed blocks needed to store `length` bytes, starting after `bitcount` bits
+fn stored_blocks_cost(bitcount: u32, length: usize) -> usize {
+    let blocks_count = ((length + MAX_STORED_BLOCK_LEN - 1) / MAX_STORED_BLOCK_LEN).max(1);
+    // The first block header is padded to a byte boundary, the following ones always take a byte
+    let first_header = 3 + (8 - (bitcount as usize + 3) % 8) % 8;
+    first_header + (blocks_count - 1) * 8 + blocks_count * 32 + length * 8
+}
+
+fn write_stored_blocks(writer: &mut BitWriter, data: &[u8], is_final: bool) {
+    let blocks_count = ((data.len() + MAX_STORED_BLOCK_LEN - 1) / MAX_STORED_BLOCK_LEN).max(1);
+    for (index, block) in data
+        .chunks(MAX_STORED_BLOCK_LEN)
+        .chain(data.is_empty().then_some(data))
+        .enumerate()
+    {
+        writer.put_bits((is_final && index + 1 == blocks_count) as u32, 1);
+        writer.put_bits(DEFLATE_BLOCKTYPE_UNCOMPRESSED, 2);
+        writer.flush();
+        writer
+            .output
+            .extend_from_slice(&(block.len() as u16).to_le_bytes());
+        writer
+            .output
+            .extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        writer.output.extend_from_slice(block);
+    }
+}
+
+/// Header of a dynamic Huffman block: the codewords lengths, run-length encoded with the precode
+struct DynamicHeader {
+    litlen_count: usize,
+    offset_count: usize,
+    /// Precode symbols with their extra bits
+    precode_items: Vec<(u8, u8)>,
+    precode_freqs: [u32; DEFLATE_NUM_PRECODE_SYMS],
+    precode: HuffmanCode<DEFLATE_NUM_PRECODE_SYMS>,
+    precode_lens_count: usize,
+}
+
+impl DynamicHeader {
+    fn new(
+        litlen_code: &HuffmanCode<DEFLATE_NUM_LITLEN_SYMS>,
+        offset_code: &HuffmanCode<DEFLATE_NUM_OFFSET_SYMS>,
+    ) -> Self {
+        let litlen_count = 257.max(
+            litlen_code
+                .lens
+                .iter()
+                .rposition(|&len| len > 0)
+                .unwrap_or(0)
+                + 1,
+        );
+        let offset_count = offset_code
+            .lens
+            .iter()
+            .rposition(|&len| len > 0)
+            .unwrap_or(0)
+            + 1;
+
+        let lens: Vec<u8> = litlen_code.lens[..litlen_count]
+            .iter()
+            .chain(offset_code.lens[..offset_count].iter())
+            .copied()
+            .collect();
+
+        let mut precode_items = Vec::new();
+        let mut precode_freqs = [0; DEFLATE_NUM_PRECODE_SYMS];
+        let mut push_item = |symbol: u8, extra: u8| {
+            precode_freqs[symbol as usize] += 1;
+            precode_items.push((symbol, extra));
+        };
+
+        let mut position = 0;
+        while position < lens.len() {
+            let len = lens[position];
+            let mut run = lens[position..]
+                .iter()
+                .take_while(|&&other| other == len)
+                .count();
+            position += run;
+
+            if len == 0 {
+                while run >= 11 {
+                    let count = run.min(138);
+                    push_item(18, (count - 11) as u8);
+                    run -= count;
+                }
+                if run >= 3 {
+                    push_item(17, (run - 3) as u8);
+                    run = 0;
+                }
+            } else if run >= 4 {
+                push_item(len, 0);
+                run -= 1;
+                while run >= 3 {
+                    let count = run.min(6);
+                    push_item(16, (count - 3) as u8);
+                    run -= count;
+                }
+            }
+            for _ in 0..run {
+                push_item(len, 0);
+            }
+        }
+
+        let mut precode = HuffmanCode::new();
+        precode.build(&precode_freqs, DEFLATE_MAX_PRE_CODEWORD_LEN);
+        let precode_lens_count = 4.max(
+            PRECODE_LENS_PERMUTATION
+                .iter()
+                .rposition(|&symbol| precode.lens[symbol] > 0)
+                .unwrap_or(0)
+                + 1,
+        );
+
+        Self {
+            litlen_count,
+            offset_count,
+            precode_items,
+            precode_freqs,
+            precode,
+            precode_lens_count,
+        }
+    }
+
+    fn cost(&self) -> usize {
+        5 + 5
+            + 4
+            + 3 * self.precode_lens_count
+            + self.precode.cost(&self.precode_freqs)
+            + self
+                .precode_items
+                .iter()
+                .map(|&(symbol, _)| PRECODE_EXTRA_BITS[symbol as usize] as usize)
+                .sum::<usize>()
+    }
+
+    fn write(&self, writer: &mut BitWriter) {
+        writer.put_bits((self.litlen_count - 257) as u32, 5);
+        writer.put_bits((self.offset_count - 1) as u32, 5);
+        writer.put_bits((self.precode_lens_count - 4) as u32, 4);
+        for &symbol in &PRECODE_LENS_PERMUTATION[..self.precode_lens_count] {
+            writer.put_bits(self.precode.lens[symbol] as u32, 3);
+        }
+        for &(symbol, extra) in self.precode_items.iter() {
+            let symbol = symbol as usize;
+            writer.put_bits(
+                self.precode.codewords[symbol] as u32,
+                self.precode.lens[symbol] as u32,
+            );
+            writer.put_bits(extra as u32, PRECODE_EXTRA_BITS[symbol] as u32);
+        }
+    }
+}
+
+/// Compresses `input` as a complete DEFLATE stream, appending it to `output`
+pub fn libdeflate_deflate_compress(
+    c: &mut LibdeflateCompressor,
+    input: &[u8],
+    output: &mut Vec<u8>,
+) {
+    assert!(input.len() < NO_POSITION as usize);
+
+    let mut writer = BitWriter {
+        output,
+        bitbuf: 0,
+        bitcount: 0,
+    };
+
+    if c.level == 0 {
+        write_stored_blocks(&mut writer, input, true);
+        writer.flush();
+        return;
+    }
+
+    let params = &LEVELS_PARAMS[c.level as usize - 1];
+    c.hash_heads.fill(NO_POSITION);
+
+    let mut position = 0;
+    let mut next_insert = 0;
+    let mut block_start = 0;
+
+    while position < input.len() {
+        let (mut length, mut offset) = c.find_match(input, position, params);
+        c.insert_positions(input, &mut next_insert, position + 1);
+
+        if length == 0 {
+            c.add_literal(input[position]);
+            position += 1;
+        } else {
+            // Lazy matching: emit a literal if the next position has a longer match
+            while params.lazy && length < params.nice_match_length {
+                let (next_length, next_offset) = c.find_match(input, position + 1, params);
+                c.insert_positions(input, &mut next_insert, position + 2);
+                if next_length <= length {
+                    break;
+                }
+                c.add_literal(input[position]);
+                position += 1;
+                length = next_length;
+                offset = next_offset;
+            }
+
+            c.add_match(length, offset);
+            position += length;
+            c.insert_positions(input, &mut next_insert, position);
+        }
+
+        if c.items.len() >= MAX_BLOCK_ITEMS - 1 {
+            c.flush_block(
+                &mut writer,
+                &input[block_start..position],
+                position == input.len(),
+            );
+            block_start = position;
+        }
+    }
+
+    if block_start < input.len() || input.is_empty() {
+        c.flush_block(&mut writer, &input[block_start..], true);
+    }
+    writer.flush();
+}
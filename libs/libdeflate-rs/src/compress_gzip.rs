@@ -0,0 +1,123 @@
+use crate::compress_deflate::{libdeflate_deflate_compress, LibdeflateCompressor};
+use crate::gzip_constants::*;
+use crc32fast::Hasher;
+
+/// Maximum uncompressed size of a BGZF block, as in htslib
+pub const BGZF_BLOCK_SIZE: usize = 0xff00;
+
+/// Maximum size of a BGZF block, including its gzip header and footer
+pub const BGZF_MAX_BLOCK_SIZE: usize = 65536;
+
+/// Empty BGZF block marking the end of the file
+pub const BGZF_EOF_BLOCK: [u8; 28] = [
+    0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43, 0x02, 0x00,
+    0x1b, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+fn write_gzip_member(
+    c: &mut LibdeflateCompressor,
+    input: &[u8],
+    output: &mut Vec<u8>,
+    extra: Option<&[u8]>,
+) {
+    let level = c.level();
+    output.extend_from_slice(&[
+        GZIP_ID1,
+        GZIP_ID2,
+        GZIP_CM_DEFLATE,
+        if extra.is_some() { GZIP_FEXTRA } else { 0 },
+    ]);
+    output.extend_from_slice(&(GZIP_MTIME_UNAVAILABLE as u32).to_le_bytes());
+    output.push(if level < 2 {
+        GZIP_XFL_FASTEST_COMPRESSION as u8
+    } else if level >= 8 {
+        GZIP_XFL_SLOWEST_COMPRESSION as u8
+    } else {
+        0
+    });
+    output.push(GZIP_OS_UNKNOWN as u8);
+
+    if let Some(extra) = extra {
+        output.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+        output.extend_from_slice(extra);
+    }
+
+    libdeflate_deflate_compress(c, input, output);
+
+    let mut crc32 = Hasher::new();
+    crc32.update(input);
+    output.extend_from_slice(&crc32.finalize().to_le_bytes());
+    output.extend_from_slice(&(input.len() as u32).to_le_bytes());
+}
+
+/// Compresses `input` as a single gzip member, appending it to `output`
+pub fn libdeflate_gzip_compress(c: &mut LibdeflateCompressor, input: &[u8], output: &mut Vec<u8>) {
+    write_gzip_member(c, input, output, None);
+}
+
+/// Compresses `input` as a BGZF block, a gzip member storing its own compressed size in the
+/// extra field. `input` must not be longer than `BGZF_BLOCK_SIZE`
+pub fn libdeflate_bgzf_compress(c: &mut LibdeflateCompressor, input: &[u8], output: &mut Vec<u8>) {
+    assert!(input.len() <= BGZF_BLOCK_SIZE);
+
+    let start = output.len();
+    write_gzip_member(c, input, output, Some(&[b'B', b'C', 2, 0, 0, 0]));
+
+    // Incompressible data is stored, so it always fits the maximum block size
+    let block_size = output.len() - start;
+    assert!(block_size <= BGZF_MAX_BLOCK_SIZE);
+    output[start + 16..start + 18].copy_from_slice(&((block_size - 1) as u16).to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compress_deflate::libdeflate_alloc_compressor;
+    use crate::compress_gzip::libdeflate_gzip_compress;
+    use crate::decompress_stream_buffered;
+
+    #[test]
+    fn gzip_roundtrip() {
+        let mut seed = 12345u64;
+        let mut random = move || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as u8
+        };
+
+        let mut dna = Vec::new();
+        for index in 0..200000 {
+            if index % 61 == 60 {
+                dna.push(b'\n');
+            } else {
+                dna.push(b"ACGT"[(random() % 4) as usize]);
+            }
+        }
+        let noise: Vec<u8> = (0..100000).map(|_| random()).collect();
+        let repeated = b"ACGTTGCA".repeat(50000);
+
+        for data in [&b""[..], b"A", &dna, &noise, &repeated] {
+            for level in 0..=9 {
+                let mut compressor = libdeflate_alloc_compressor(level);
+                let mut compressed = Vec::new();
+                libdeflate_gzip_compress(&mut compressor, data, &mut compressed);
+
+                let mut decompressed = Vec::new();
+                decompress_stream_buffered(
+                    &compressed[..],
+                    |chunk| {
+                        decompressed.extend_from_slice(chunk);
+                        Ok(())
+                    },
+                    1024 * 64,
+                )
+                .unwrap();
+                assert!(decompressed == data, "Level {} roundtrip failed", level);
+
+                if level > 0 {
+                    assert!(compressed.len() <= data.len() + data.len() / 1000 + 64);
+                }
+            }
+        }
+    }
+}
@@ -1,9 +1,12 @@
 #![deny(warnings)]
+pub mod compress_deflate;
+pub mod compress_gzip;
 pub mod decompress_deflate;
 pub mod decompress_gzip;
 mod decompress_utils;
 mod deflate_constants;
 mod gzip_constants;
+pub mod parallel;
 pub mod streams;
 mod utils;
 
@@ -18,12 +21,18 @@ use crate::decompress_deflate::{
 };
 use crate::decompress_gzip::libdeflate_gzip_decompress;
 use crate::deflate_constants::{DEFLATE_MAX_NUM_SYMS, DEFLATE_NUM_PRECODE_SYMS};
+use crate::parallel::bgzf::{bgzf_block_size, decompress_bgzf_parallel, BGZF_TASK_MAX_MEMORY};
+use crate::parallel::parse_gzip_header;
+use crate::parallel::speculative::{decompress_speculative, SPECULATIVE_CHUNK_MAX_MEMORY};
 use crate::streams::deflate_chunked_buffer_input::DeflateChunkedBufferInput;
 use crate::streams::deflate_chunked_buffer_output::DeflateChunkedBufferOutput;
+use filebuffer::FileBuffer;
+use std::cmp::min;
 use std::fs::File;
 use std::io::Read;
 use std::mem::{size_of, MaybeUninit};
 use std::path::Path;
+use std::sync::Arc;
 
 /*
  * The main DEFLATE decompressor structure.  Since this implementation only
@@ -128,10 +137,17 @@ pub fn decompress_file_buffered(
     func: impl FnMut(&[u8]) -> Result<(), ()>,
     buf_size: usize,
 ) -> Result<(), LibdeflateError> {
-    let mut read_file = File::open(file).unwrap();
+    decompress_stream_buffered(File::open(file).unwrap(), func, buf_size)
+}
 
+/// Decompresses a gzip stream made of one or more members, calling `func` with each chunk of decompressed data
+pub fn decompress_stream_buffered(
+    mut stream: impl Read,
+    func: impl FnMut(&[u8]) -> Result<(), ()>,
+    buf_size: usize,
+) -> Result<(), LibdeflateError> {
     let mut input_stream =
-        DeflateChunkedBufferInput::new(|buf| read_file.read(buf).unwrap_or(0), buf_size);
+        DeflateChunkedBufferInput::new(|buf| stream.read(buf).unwrap_or(0), buf_size);
 
     let mut output_stream = DeflateChunkedBufferOutput::new(func, buf_size);
 
@@ -143,6 +159,52 @@ pub fn decompress_file_buffered(
     Ok(())
 }
 
+/// Files smaller than this are always decompressed by a single thread
+const MIN_PARALLEL_FILE_SIZE: usize = 1024 * 1024 * 16;
+
+/// The speculative decoder is slower than the sequential one, so it pays off only with enough threads
+const MIN_SPECULATIVE_THREADS_COUNT: usize = 4;
+
+/// Decompresses a gzip file using the threads of the global rayon pool.
+/// BGZF files are split at their blocks boundaries, while ordinary gzip files are decoded speculatively
+/// from multiple points (see `parallel::speculative`). The decompressed data is passed to `func` in order.
+/// The chunks decompressed at the same time use at most `max_memory` bytes.
+/// Small files, calls from a rayon thread or a too small memory budget fall back to the sequential decompression
+pub fn decompress_file_parallel(
+    file: impl AsRef<Path>,
+    func: impl FnMut(&[u8]) -> Result<(), ()>,
+    buf_size: usize,
+    max_memory: usize,
+) -> Result<(), LibdeflateError> {
+    let data = Arc::new(FileBuffer::open(file).unwrap());
+    let threads_count = rayon::current_num_threads();
+
+    if data.len() < MIN_PARALLEL_FILE_SIZE
+        || threads_count < 2
+        || rayon::current_thread_index().is_some()
+    {
+        return decompress_stream_buffered(&data[..], func, buf_size);
+    }
+
+    let is_bgzf = bgzf_block_size(&data).is_some();
+
+    let (task_max_memory, min_threads_count) = if is_bgzf {
+        (BGZF_TASK_MAX_MEMORY, 2)
+    } else {
+        (SPECULATIVE_CHUNK_MAX_MEMORY, MIN_SPECULATIVE_THREADS_COUNT)
+    };
+    let max_in_flight = min(threads_count * 2, max_memory / task_max_memory);
+
+    if threads_count < min_threads_count || max_in_flight < 2 {
+        decompress_stream_buffered(&data[..], func, buf_size)
+    } else if is_bgzf {
+        decompress_bgzf_parallel(data, func, buf_size, max_in_flight)
+    } else {
+        let (header_length, _) = parse_gzip_header(&data).ok_or(LibdeflateError::BadData)?;
+        decompress_speculative(data, header_length, func, buf_size, max_in_flight)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::decompress_file_buffered;
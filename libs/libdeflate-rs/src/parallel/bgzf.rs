@@ -0,0 +1,173 @@
+use crate::parallel::{parse_gzip_header, process_ordered_parallel};
+use crate::{decompress_stream_buffered, LibdeflateError};
+use filebuffer::FileBuffer;
+use std::sync::Arc;
+
+/// Maximum decompressed size of the groups of blocks decompressed by each task
+const BGZF_TASK_DECODED_SIZE: usize = 1024 * 1024 * 4;
+/// Maximum decompressed size of a BGZF block
+const BGZF_MAX_BLOCK_DECODED_SIZE: usize = 1024 * 64;
+const BGZF_BUFFER_SIZE: usize = 1024 * 128;
+
+/// Maximum memory used by each task decompressing a group of blocks
+pub const BGZF_TASK_MAX_MEMORY: usize = BGZF_TASK_DECODED_SIZE + BGZF_MAX_BLOCK_DECODED_SIZE;
+
+/// Returns the total size of the BGZF block starting at `data`, or None if it is not a BGZF block
+pub fn bgzf_block_size(data: &[u8]) -> Option<usize> {
+    let (_, mut extra) = parse_gzip_header(data)?;
+
+    while extra.len() >= 4 {
+        let field_length = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        let field = extra.get(4..4 + field_length)?;
+        if extra[0] == b'B' && extra[1] == b'C' && field_length == 2 {
+            return Some(u16::from_le_bytes([field[0], field[1]]) as usize + 1);
+        }
+        extra = &extra[4 + field_length..];
+    }
+    None
+}
+
+/// Returns the total and the decompressed sizes of the complete BGZF block starting at `data`
+fn bgzf_block_sizes(data: &[u8]) -> Option<(usize, usize)> {
+    let size = bgzf_block_size(data)?;
+    let trailer = data.get(size.checked_sub(4)?..size)?;
+    let decoded_size = u32::from_le_bytes(trailer.try_into().unwrap()) as usize;
+    (decoded_size <= BGZF_MAX_BLOCK_DECODED_SIZE).then(|| (size, decoded_size))
+}
+
+/// Decompresses a BGZF file, splitting it at the blocks boundaries and decompressing
+/// at most `max_in_flight` groups of blocks at the same time (see `BGZF_TASK_MAX_MEMORY`).
+/// If a block without the BGZF header is found, the rest of the file is decompressed sequentially
+pub fn decompress_bgzf_parallel(
+    data: Arc<FileBuffer>,
+    mut func: impl FnMut(&[u8]) -> Result<(), ()>,
+    buf_size: usize,
+    max_in_flight: usize,
+) -> Result<(), LibdeflateError> {
+    let mut position = 0;
+    let mut sequential_start = None;
+    let tasks_ranges = std::iter::from_fn(|| {
+        if sequential_start.is_some() {
+            return None;
+        }
+
+        let start = position;
+        let mut decoded_size = 0;
+        while position < data.len() && decoded_size < BGZF_TASK_DECODED_SIZE {
+            match bgzf_block_sizes(&data[position..]) {
+                Some((size, block_decoded_size)) => {
+                    position += size;
+                    decoded_size += block_decoded_size;
+                }
+                None => {
+                    sequential_start = Some(position);
+                    break;
+                }
+            }
+        }
+
+        if position > start {
+            Some((start..position, decoded_size))
+        } else {
+            None
+        }
+    });
+
+    let tasks_data = data.clone();
+    let tasks = tasks_ranges.map(move |(range, decoded_size)| {
+        let data = tasks_data.clone();
+        move || {
+            let mut output = Vec::with_capacity(decoded_size);
+            decompress_stream_buffered(
+                &data[range],
+                |chunk| {
+                    // The blocks sizes are checked only at their end
+                    if output.len() + chunk.len() > decoded_size {
+                        return Err(());
+                    }
+                    output.extend_from_slice(chunk);
+                    Ok(())
+                },
+                BGZF_BUFFER_SIZE,
+            )
+            .map(|_| output)
+        }
+    });
+
+    process_ordered_parallel(tasks, max_in_flight, |output| {
+        for chunk in output?.chunks(buf_size) {
+            func(chunk).map_err(|_| LibdeflateError::InsufficientSpace)?;
+        }
+        Ok(true)
+    })?;
+
+    match sequential_start {
+        Some(start) => decompress_stream_buffered(&data[start..], func, buf_size),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parallel::bgzf::{bgzf_block_size, decompress_bgzf_parallel};
+    use crate::parallel::bgzf_writer::BgzfWriter;
+    use crate::parallel::parse_gzip_header;
+    use filebuffer::FileBuffer;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use std::sync::Arc;
+
+    #[test]
+    fn bgzf_detection() {
+        const BGZF_EOF_BLOCK: [u8; 28] = [
+            0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43,
+            0x02, 0x00, 0x1b, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(parse_gzip_header(&BGZF_EOF_BLOCK).map(|h| h.0), Some(18));
+        assert_eq!(bgzf_block_size(&BGZF_EOF_BLOCK), Some(BGZF_EOF_BLOCK.len()));
+
+        let gzip_header = [
+            0x1f, 0x8b, 0x08, 0x08, 0, 0, 0, 0, 0, 0x03, b'a', 0, 0x03, 0x00,
+        ];
+        assert_eq!(parse_gzip_header(&gzip_header).map(|h| h.0), Some(12));
+        assert_eq!(bgzf_block_size(&gzip_header), None);
+        assert_eq!(bgzf_block_size(b"@read"), None);
+    }
+
+    #[test]
+    fn bgzf_parallel_decompression() {
+        let data: Vec<u8> = (0..12_000_000u32)
+            .map(|i| b"ACGT\n"[(i.wrapping_mul(2654435761) >> 29) as usize % 5])
+            .collect();
+        let (bgzf_data, gzip_data) = data.split_at(10_000_000);
+
+        // BGZF blocks followed by an ordinary gzip member, that is decompressed sequentially
+        let mut writer = BgzfWriter::with_threads(Vec::new(), 6, 4);
+        writer.write_all(bgzf_data).unwrap();
+        let mut encoder = GzEncoder::new(writer.finish().unwrap(), Compression::new(6));
+        encoder.write_all(gzip_data).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let path = std::env::temp_dir().join(format!(
+            "ggcat_bgzf_parallel_decompression_{}.gz",
+            std::process::id()
+        ));
+        std::fs::write(&path, &compressed).unwrap();
+
+        let mut output = Vec::new();
+        decompress_bgzf_parallel(
+            Arc::new(FileBuffer::open(&path).unwrap()),
+            |chunk| {
+                output.extend_from_slice(chunk);
+                Ok(())
+            },
+            1024 * 64,
+            4,
+        )
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(output == data);
+    }
+}
@@ -0,0 +1,236 @@
+use crate::compress_deflate::{libdeflate_alloc_compressor, LibdeflateCompressor};
+use crate::compress_gzip::{libdeflate_bgzf_compress, BGZF_BLOCK_SIZE, BGZF_EOF_BLOCK};
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// Number of BGZF blocks compressed by each task
+const BGZF_TASK_BLOCKS: usize = 16;
+const BGZF_TASK_SIZE: usize = BGZF_BLOCK_SIZE * BGZF_TASK_BLOCKS;
+
+fn compress_task(compressor: &mut LibdeflateCompressor, data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(data.len() / 2);
+    for block in data.chunks(BGZF_BLOCK_SIZE) {
+        libdeflate_bgzf_compress(compressor, block, &mut output);
+    }
+    output
+}
+
+/// Dedicated compression threads, so that the writer can be used from the threads of the rayon pool
+/// without waiting for tasks queued behind them
+struct CompressionThreads {
+    tasks_sender: Option<Sender<(usize, Vec<u8>)>>,
+    results_receiver: Receiver<(usize, Vec<u8>)>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl CompressionThreads {
+    fn new(level: u32, threads_count: usize) -> Self {
+        let (tasks_sender, tasks_receiver) = channel::<(usize, Vec<u8>)>();
+        let (results_sender, results_receiver) = channel();
+        let tasks_receiver = Arc::new(Mutex::new(tasks_receiver));
+
+        let threads = (0..threads_count)
+            .map(|_| {
+                let tasks_receiver = tasks_receiver.clone();
+                let results_sender = results_sender.clone();
+                std::thread::spawn(move || {
+                    let mut compressor = libdeflate_alloc_compressor(level);
+                    loop {
+                        let task = tasks_receiver.lock().unwrap().recv();
+                        let (index, data) = match task {
+                            Ok(task) => task,
+                            Err(_) => break,
+                        };
+                        let output = compress_task(&mut compressor, &data);
+                        if results_sender.send((index, output)).is_err() {
+                            break;
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        Self {
+            tasks_sender: Some(tasks_sender),
+            results_receiver,
+            threads,
+        }
+    }
+}
+
+impl Drop for CompressionThreads {
+    fn drop(&mut self) {
+        self.tasks_sender.take();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Writes a BGZF file (a sequence of independent gzip members of at most 64KB, readable by any
+/// gzip decompressor and randomly accessible by the BGZF aware tools), compressing the blocks
+/// with multiple threads. The end of file marker is written by `finish` or when the writer is dropped
+pub struct BgzfWriter<W: Write> {
+    inner: Option<W>,
+    buffer: Vec<u8>,
+    compressor: LibdeflateCompressor,
+    threads: Option<CompressionThreads>,
+    max_in_flight: usize,
+    next_task: usize,
+    next_output: usize,
+    completed: HashMap<usize, Vec<u8>>,
+}
+
+impl<W: Write> BgzfWriter<W> {
+    /// Creates a writer using as many compression threads as the global rayon pool
+    pub fn new(inner: W, level: u32) -> Self {
+        Self::with_threads(inner, level, rayon::current_num_threads())
+    }
+
+    /// Creates a writer with the given number of compression threads.
+    /// With a single thread the blocks are compressed by the calling thread
+    pub fn with_threads(inner: W, level: u32, threads_count: usize) -> Self {
+        Self {
+            inner: Some(inner),
+            buffer: Vec::with_capacity(BGZF_TASK_SIZE),
+            compressor: libdeflate_alloc_compressor(level),
+            threads: (threads_count > 1).then(|| CompressionThreads::new(level, threads_count)),
+            max_in_flight: threads_count * 2,
+            next_task: 0,
+            next_output: 0,
+            completed: HashMap::new(),
+        }
+    }
+
+    /// Writes the compressed tasks ready in order, waiting for them until at most `max_pending` are left
+    fn write_completed(&mut self, max_pending: usize) -> std::io::Result<()> {
+        let threads = match &self.threads {
+            Some(threads) => threads,
+            None => return Ok(()),
+        };
+
+        while self.next_task - self.next_output > max_pending {
+            let output = loop {
+                if let Some(output) = self.completed.remove(&self.next_output) {
+                    break output;
+                }
+                let (index, output) = threads.results_receiver.recv().unwrap();
+                self.completed.insert(index, output);
+            };
+            self.inner.as_mut().unwrap().write_all(&output)?;
+            self.next_output += 1;
+        }
+        Ok(())
+    }
+
+    fn submit_buffer(&mut self) -> std::io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        match &self.threads {
+            None => {
+                let output = compress_task(&mut self.compressor, &self.buffer);
+                self.buffer.clear();
+                self.inner.as_mut().unwrap().write_all(&output)
+            }
+            Some(threads) => {
+                let data = std::mem::replace(&mut self.buffer, Vec::with_capacity(BGZF_TASK_SIZE));
+                threads
+                    .tasks_sender
+                    .as_ref()
+                    .unwrap()
+                    .send((self.next_task, data))
+                    .unwrap();
+                self.next_task += 1;
+                self.write_completed(self.max_in_flight)
+            }
+        }
+    }
+
+    /// Compresses the remaining data and writes the end of file marker, returning the inner writer
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.try_finish()?;
+        Ok(self.inner.take().unwrap())
+    }
+
+    fn try_finish(&mut self) -> std::io::Result<()> {
+        self.submit_buffer()?;
+        self.write_completed(0)?;
+        let inner = self.inner.as_mut().unwrap();
+        inner.write_all(&BGZF_EOF_BLOCK)?;
+        inner.flush()
+    }
+}
+
+impl<W: Write> Write for BgzfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let length = buf.len().min(BGZF_TASK_SIZE - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..length]);
+        if self.buffer.len() == BGZF_TASK_SIZE {
+            self.submit_buffer()?;
+        }
+        Ok(length)
+    }
+
+    /// Compresses and writes all the buffered data, ending the current block
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.submit_buffer()?;
+        self.write_completed(0)?;
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write> Drop for BgzfWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.try_finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compress_gzip::BGZF_EOF_BLOCK;
+    use crate::decompress_stream_buffered;
+    use crate::parallel::bgzf::bgzf_block_size;
+    use crate::parallel::bgzf_writer::BgzfWriter;
+    use std::io::Write;
+
+    #[test]
+    fn bgzf_writer_roundtrip() {
+        let data: Vec<u8> = (0..3_000_000u32)
+            .map(|i| b"ACGT"[(i.wrapping_mul(2654435761) >> 30) as usize])
+            .collect();
+
+        for threads_count in [1, 4] {
+            let mut writer = BgzfWriter::with_threads(Vec::new(), 6, threads_count);
+            for chunk in data.chunks(100_000) {
+                writer.write_all(chunk).unwrap();
+            }
+            let compressed = writer.finish().unwrap();
+            assert!(compressed.ends_with(&BGZF_EOF_BLOCK));
+
+            let mut position = 0;
+            while position < compressed.len() {
+                position += bgzf_block_size(&compressed[position..]).unwrap();
+            }
+            assert_eq!(position, compressed.len());
+
+            let mut decompressed = Vec::new();
+            decompress_stream_buffered(
+                &compressed[..],
+                |chunk| {
+                    decompressed.extend_from_slice(chunk);
+                    Ok(())
+                },
+                1024 * 64,
+            )
+            .unwrap();
+            assert!(decompressed == data);
+        }
+    }
+}
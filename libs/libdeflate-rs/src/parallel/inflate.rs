@@ -0,0 +1,444 @@
+/*
+ * Simple DEFLATE decoder used by the parallel decompression.
+ * Unlike the main decoder it can start from any bit position of a stream, representing the bytes
+ * copied from the (unknown) window that precedes the starting point with placeholder symbols.
+ */
+
+use crate::deflate_constants::*;
+
+pub const WINDOW_SIZE: usize = DEFLATE_MAX_WINDOW_SIZE;
+
+/// Symbols >= PLACEHOLDER_BASE stand for the byte at index `symbol - PLACEHOLDER_BASE`
+/// of the unknown window preceding the start of the decoding
+pub const PLACEHOLDER_BASE: u16 = 256;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const OFFSET_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const OFFSET_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+const PRECODE_ORDER: [usize; DEFLATE_NUM_PRECODE_SYMS] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(data: &'a [u8], bit_position: usize) -> Self {
+        Self {
+            data,
+            position: bit_position,
+        }
+    }
+
+    #[inline(always)]
+    pub fn bit_position(&self) -> usize {
+        self.position
+    }
+
+    #[inline(always)]
+    pub fn set_bit_position(&mut self, bit_position: usize) {
+        self.position = bit_position;
+    }
+
+    /// Returns at least 57 bits starting from the current position, zero padded after the end of the data
+    #[inline(always)]
+    pub fn peek(&self) -> u64 {
+        let byte = self.position / 8;
+        let word = if byte + 8 <= self.data.len() {
+            u64::from_le_bytes(self.data[byte..byte + 8].try_into().unwrap())
+        } else {
+            let mut bytes = [0; 8];
+            if byte < self.data.len() {
+                bytes[..self.data.len() - byte].copy_from_slice(&self.data[byte..]);
+            }
+            u64::from_le_bytes(bytes)
+        };
+        word >> (self.position % 8)
+    }
+
+    #[inline(always)]
+    fn bits(&mut self, count: usize) -> usize {
+        let value = (self.peek() & ((1 << count) - 1)) as usize;
+        self.position += count;
+        value
+    }
+
+    #[inline(always)]
+    fn align_to_byte(&mut self) {
+        self.position = (self.position + 7) / 8 * 8;
+    }
+
+    #[inline(always)]
+    fn is_overrun(&self) -> bool {
+        self.position > self.data.len() * 8
+    }
+}
+
+const TABLE_BITS: usize = 10;
+
+struct HuffmanCode {
+    /// Direct lookup of the codewords up to TABLE_BITS long, as `(symbol << 4) | length`.
+    /// Zero entries are decoded with the (slower) canonical codes walk
+    table: Box<[u16; 1 << TABLE_BITS]>,
+    counts: [u16; DEFLATE_MAX_CODEWORD_LEN + 1],
+    symbols: [u16; DEFLATE_MAX_NUM_SYMS],
+}
+
+impl HuffmanCode {
+    fn new() -> Self {
+        Self {
+            table: Box::new([0; 1 << TABLE_BITS]),
+            counts: [0; DEFLATE_MAX_CODEWORD_LEN + 1],
+            symbols: [0; DEFLATE_MAX_NUM_SYMS],
+        }
+    }
+
+    /// Builds the canonical code from the codeword lengths, returning false if they are not valid.
+    /// As in zlib, incomplete codes are accepted only if made of a single codeword
+    fn build(&mut self, lengths: &[u8], allow_incomplete: bool) -> bool {
+        self.counts = [0; DEFLATE_MAX_CODEWORD_LEN + 1];
+        for &length in lengths {
+            self.counts[length as usize] += 1;
+        }
+        self.counts[0] = 0;
+
+        let mut left = 1i32;
+        for length in 1..=DEFLATE_MAX_CODEWORD_LEN {
+            left = (left << 1) - self.counts[length] as i32;
+            if left < 0 {
+                return false;
+            }
+        }
+
+        let max_length = (1..=DEFLATE_MAX_CODEWORD_LEN)
+            .rev()
+            .find(|l| self.counts[*l] != 0)
+            .unwrap_or(0);
+
+        if left > 0 && (!allow_incomplete || max_length > 1) {
+            return false;
+        }
+
+        let mut offsets = [0u16; DEFLATE_MAX_CODEWORD_LEN + 2];
+        for length in 1..=DEFLATE_MAX_CODEWORD_LEN {
+            offsets[length + 1] = offsets[length] + self.counts[length];
+        }
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                self.symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+
+        self.table.fill(0);
+        let mut code = 0u32;
+        let mut index = 0;
+        for length in 1..=TABLE_BITS {
+            for _ in 0..self.counts[length] {
+                let symbol = self.symbols[index];
+                let entry = (symbol << 4) | length as u16;
+                let mut position = (code.reverse_bits() >> (32 - length)) as usize;
+                while position < (1 << TABLE_BITS) {
+                    self.table[position] = entry;
+                    position += 1 << length;
+                }
+                index += 1;
+                code += 1;
+            }
+            code <<= 1;
+        }
+        true
+    }
+
+    #[inline(always)]
+    fn decode(&self, reader: &mut BitReader) -> Option<usize> {
+        let bits = reader.peek();
+        let entry = self.table[(bits & ((1 << TABLE_BITS) - 1)) as usize];
+        if entry != 0 {
+            reader.position += (entry & 0xF) as usize;
+            return Some((entry >> 4) as usize);
+        }
+        self.decode_slow(reader, bits)
+    }
+
+    #[cold]
+    fn decode_slow(&self, reader: &mut BitReader, bits: u64) -> Option<usize> {
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+        for length in 1..=DEFLATE_MAX_CODEWORD_LEN {
+            code |= ((bits >> (length - 1)) & 1) as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                reader.position += length;
+                return Some(self.symbols[(index + code - first) as usize] as usize);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        None
+    }
+}
+
+pub trait InflateOutput {
+    fn position(&self) -> usize;
+    fn push_literal(&mut self, byte: u8);
+    /// Returns false if the match refers to data before the start of the available window
+    fn copy_match(&mut self, distance: usize, length: usize) -> bool;
+}
+
+/// Output with a fully known window, stored before the decoded data
+impl InflateOutput for Vec<u8> {
+    #[inline(always)]
+    fn position(&self) -> usize {
+        self.len()
+    }
+
+    #[inline(always)]
+    fn push_literal(&mut self, byte: u8) {
+        self.push(byte);
+    }
+
+    #[inline(always)]
+    fn copy_match(&mut self, distance: usize, length: usize) -> bool {
+        if distance > self.len() {
+            return false;
+        }
+        let start = self.len() - distance;
+        if distance >= length {
+            self.extend_from_within(start..start + length);
+        } else {
+            for i in start..start + length {
+                self.push(self[i]);
+            }
+        }
+        true
+    }
+}
+
+/// Output decoded without knowing the preceding window
+impl InflateOutput for Vec<u16> {
+    #[inline(always)]
+    fn position(&self) -> usize {
+        self.len()
+    }
+
+    #[inline(always)]
+    fn push_literal(&mut self, byte: u8) {
+        self.push(byte as u16);
+    }
+
+    #[inline(always)]
+    fn copy_match(&mut self, distance: usize, length: usize) -> bool {
+        if distance > self.len() + WINDOW_SIZE {
+            return false;
+        }
+        if distance <= self.len() {
+            let start = self.len() - distance;
+            if distance >= length {
+                self.extend_from_within(start..start + length);
+            } else {
+                for i in start..start + length {
+                    self.push(self[i]);
+                }
+            }
+        } else {
+            for _ in 0..length {
+                let position = self.len();
+                let symbol = if distance <= position {
+                    self[position - distance]
+                } else {
+                    PLACEHOLDER_BASE + (WINDOW_SIZE + position - distance) as u16
+                };
+                self.push(symbol);
+            }
+        }
+        true
+    }
+}
+
+#[inline(always)]
+fn is_text(byte: u8) -> bool {
+    matches!(byte, b'\t' | b'\n' | b'\r' | b' '..=b'~')
+}
+
+pub struct InflateState {
+    precode: HuffmanCode,
+    litlen: HuffmanCode,
+    offset: HuffmanCode,
+    lengths: [u8; DEFLATE_NUM_LITLEN_SYMS + DEFLATE_NUM_OFFSET_SYMS],
+    output_limit: usize,
+}
+
+impl InflateState {
+    /// Decoding fails if the output grows beyond `output_limit` symbols
+    pub fn new(output_limit: usize) -> Self {
+        Self {
+            precode: HuffmanCode::new(),
+            litlen: HuffmanCode::new(),
+            offset: HuffmanCode::new(),
+            lengths: [0; DEFLATE_NUM_LITLEN_SYMS + DEFLATE_NUM_OFFSET_SYMS],
+            output_limit,
+        }
+    }
+
+    /// Decodes a single block, returning whether it is the final one of the stream.
+    /// With `text_only` set the decoding fails on literals that are not printable characters,
+    /// to reject the false block starts found while searching for a synchronization point
+    pub fn decode_block<O: InflateOutput>(
+        &mut self,
+        reader: &mut BitReader,
+        output: &mut O,
+        text_only: bool,
+    ) -> Result<bool, ()> {
+        let is_final = reader.bits(1) != 0;
+
+        match reader.bits(2) as u32 {
+            DEFLATE_BLOCKTYPE_UNCOMPRESSED => {
+                reader.align_to_byte();
+                let length = reader.bits(16);
+                if length != !reader.bits(16) & 0xFFFF {
+                    return Err(());
+                }
+                for _ in 0..length {
+                    let byte = reader.bits(8) as u8;
+                    if text_only && !is_text(byte) {
+                        return Err(());
+                    }
+                    output.push_literal(byte);
+                }
+            }
+            DEFLATE_BLOCKTYPE_STATIC_HUFFMAN => {
+                self.load_static_codes();
+                self.decode_huffman_data(reader, output, text_only)?;
+            }
+            DEFLATE_BLOCKTYPE_DYNAMIC_HUFFMAN => {
+                self.read_dynamic_codes(reader)?;
+                self.decode_huffman_data(reader, output, text_only)?;
+            }
+            _ => return Err(()),
+        }
+
+        if reader.is_overrun() {
+            return Err(());
+        }
+        Ok(is_final)
+    }
+
+    fn load_static_codes(&mut self) {
+        let (litlen, offset) = self.lengths.split_at_mut(DEFLATE_NUM_LITLEN_SYMS);
+        litlen[0..144].fill(8);
+        litlen[144..256].fill(9);
+        litlen[256..280].fill(7);
+        litlen[280..288].fill(8);
+        offset.fill(5);
+        self.litlen.build(litlen, false);
+        self.offset.build(offset, false);
+    }
+
+    fn read_dynamic_codes(&mut self, reader: &mut BitReader) -> Result<(), ()> {
+        let litlen_count = reader.bits(5) + 257;
+        let offset_count = reader.bits(5) + 1;
+        let precode_count = reader.bits(4) + 4;
+
+        if litlen_count > 286 || offset_count > 30 {
+            return Err(());
+        }
+
+        let mut precode_lengths = [0; DEFLATE_NUM_PRECODE_SYMS];
+        for &symbol in &PRECODE_ORDER[..precode_count] {
+            precode_lengths[symbol] = reader.bits(3) as u8;
+        }
+        if !self.precode.build(&precode_lengths, false) {
+            return Err(());
+        }
+
+        let total_count = litlen_count + offset_count;
+        let mut index = 0;
+        while index < total_count {
+            let (value, repeat) = match self.precode.decode(reader).ok_or(())? {
+                length @ 0..=15 => (length as u8, 1),
+                16 => {
+                    if index == 0 {
+                        return Err(());
+                    }
+                    (self.lengths[index - 1], 3 + reader.bits(2))
+                }
+                17 => (0, 3 + reader.bits(3)),
+                _ => (0, 11 + reader.bits(7)),
+            };
+            if index + repeat > total_count {
+                return Err(());
+            }
+            self.lengths[index..index + repeat].fill(value);
+            index += repeat;
+        }
+
+        if reader.is_overrun() || self.lengths[DEFLATE_END_OF_BLOCK] == 0 {
+            return Err(());
+        }
+
+        let (litlen, offset) = self.lengths[..total_count].split_at(litlen_count);
+        if !self.litlen.build(litlen, true) || !self.offset.build(offset, true) {
+            return Err(());
+        }
+        Ok(())
+    }
+
+    fn decode_huffman_data<O: InflateOutput>(
+        &mut self,
+        reader: &mut BitReader,
+        output: &mut O,
+        text_only: bool,
+    ) -> Result<(), ()> {
+        loop {
+            let symbol = self.litlen.decode(reader).ok_or(())?;
+
+            if symbol < DEFLATE_NUM_LITERALS {
+                if text_only && !is_text(symbol as u8) {
+                    return Err(());
+                }
+                output.push_literal(symbol as u8);
+            } else if symbol == DEFLATE_END_OF_BLOCK {
+                return Ok(());
+            } else {
+                let length_index = symbol - DEFLATE_END_OF_BLOCK - 1;
+                if length_index >= LENGTH_BASE.len() {
+                    return Err(());
+                }
+                let length = LENGTH_BASE[length_index] as usize
+                    + reader.bits(LENGTH_EXTRA_BITS[length_index] as usize);
+
+                let offset_index = self.offset.decode(reader).ok_or(())?;
+                if offset_index >= OFFSET_BASE.len() {
+                    return Err(());
+                }
+                let distance = OFFSET_BASE[offset_index] as usize
+                    + reader.bits(OFFSET_EXTRA_BITS[offset_index] as usize);
+
+                if !output.copy_match(distance, length) || output.position() > self.output_limit {
+                    return Err(());
+                }
+            }
+
+            if reader.is_overrun() {
+                return Err(());
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+pub mod bgzf;
+pub mod bgzf_writer;
+mod inflate;
+pub mod speculative;
+
+use crate::gzip_constants::*;
+use crate::LibdeflateError;
+use std::collections::HashMap;
+use std::sync::mpsc::channel;
+
+/// Parses the header of the gzip member at the start of `data`,
+/// returning its length and the content of its extra field
+pub fn parse_gzip_header(data: &[u8]) -> Option<(usize, &[u8])> {
+    if data.len() < GZIP_MIN_HEADER_SIZE
+        || data[0] != GZIP_ID1
+        || data[1] != GZIP_ID2
+        || data[2] != GZIP_CM_DEFLATE
+    {
+        return None;
+    }
+
+    let flg = data[3];
+    if (flg & GZIP_FRESERVED) != 0 {
+        return None;
+    }
+
+    let mut position = GZIP_MIN_HEADER_SIZE;
+    let mut extra: &[u8] = &[];
+
+    if (flg & GZIP_FEXTRA) != 0 {
+        let xlen = u16::from_le_bytes(data.get(position..position + 2)?.try_into().unwrap());
+        extra = data.get(position + 2..position + 2 + xlen as usize)?;
+        position += 2 + xlen as usize;
+    }
+
+    for field in [GZIP_FNAME, GZIP_FCOMMENT] {
+        if (flg & field) != 0 {
+            position += data.get(position..)?.iter().position(|b| *b == 0)? + 1;
+        }
+    }
+
+    if (flg & GZIP_FHCRC) != 0 {
+        position += 2;
+    }
+
+    if position > data.len() {
+        return None;
+    }
+    Some((position, extra))
+}
+
+/// Runs the tasks in the global rayon thread pool, with at most `max_in_flight` of them
+/// running or waiting to be processed, and processes their results in order on the current thread.
+/// Stops early if `process` returns false
+fn process_ordered_parallel<T: Send + 'static, F: FnOnce() -> T + Send + 'static>(
+    tasks: impl Iterator<Item = F>,
+    max_in_flight: usize,
+    mut process: impl FnMut(T) -> Result<bool, LibdeflateError>,
+) -> Result<(), LibdeflateError> {
+    let (sender, receiver) = channel();
+    let mut tasks = tasks.enumerate();
+    let mut completed = HashMap::new();
+    let mut in_flight = 0;
+    let mut next_index = 0;
+
+    loop {
+        while in_flight < max_in_flight {
+            let (index, task) = match tasks.next() {
+                Some(task) => task,
+                None => break,
+            };
+            let sender = sender.clone();
+            rayon::spawn(move || {
+                // The receiver is dropped if the processing stopped early
+                let _ = sender.send((index, task()));
+            });
+            in_flight += 1;
+        }
+
+        if in_flight == 0 {
+            return Ok(());
+        }
+
+        let result = loop {
+            if let Some(result) = completed.remove(&next_index) {
+                break result;
+            }
+            let (index, result) = receiver.recv().unwrap();
+            completed.insert(index, result);
+        };
+        next_index += 1;
+        in_flight -= 1;
+
+        if !process(result)? {
+            return Ok(());
+        }
+    }
+}
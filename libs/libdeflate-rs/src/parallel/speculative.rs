@@ -0,0 +1,380 @@
+/*
+ * Speculative parallel decompression of ordinary gzip files, following the approach of pugz
+ * (Kerbiriou and Chikhi, "Parallel decompression of gzip-compressed files and random access to
+ * DNA sequences", 2019).
+ *
+ * The compressed stream is split into chunks, and each chunk except the first one searches for
+ * the start of a dynamic Huffman block by trying to decode text from every bit position.
+ * From there the chunk is decoded without knowing the preceding 32KB window, representing the
+ * bytes copied from it with placeholders, until reaching the first block boundary past the start
+ * of the next chunk.
+ * The chunks are then processed in order: a chunk is used only if it starts exactly where the
+ * previous decoded data ended, and its placeholders are resolved with the previous window.
+ * Otherwise the gap is decoded sequentially, so a wrong guess slows down the decompression
+ * but never changes its result.
+ */
+
+use crate::gzip_constants::GZIP_FOOTER_SIZE;
+use crate::parallel::inflate::{BitReader, InflateState, PLACEHOLDER_BASE, WINDOW_SIZE};
+use crate::parallel::process_ordered_parallel;
+use crate::{decompress_stream_buffered, LibdeflateError};
+use crc32fast::Hasher;
+use filebuffer::FileBuffer;
+use std::sync::Arc;
+
+/// Compressed size of the chunks decoded independently
+const CHUNK_SIZE: usize = 1024 * 1024 * 2;
+
+/// Maximum decompressed size of a chunk, longer chunks are decoded sequentially
+const MAX_CHUNK_DECODED_SIZE: usize = CHUNK_SIZE * 16;
+
+/// Maximum memory used by each chunk decoded in parallel, as its symbols are 16 bits wide
+pub const SPECULATIVE_CHUNK_MAX_MEMORY: usize = MAX_CHUNK_DECODED_SIZE * 2;
+
+struct DecodedChunk {
+    /// Bit position of the first decoded block
+    start: usize,
+    /// Bit position after the last decoded block
+    end: usize,
+    is_final: bool,
+    symbols: Vec<u16>,
+}
+
+/// Decodes the blocks starting in the range `start..stop` (in bits). If `known_start` is false,
+/// the first block is searched in the range
+fn decode_chunk(data: &[u8], start: usize, stop: usize, known_start: bool) -> Option<DecodedChunk> {
+    let mut state = InflateState::new(MAX_CHUNK_DECODED_SIZE);
+    let mut reader = BitReader::new(data, start);
+    let mut symbols = Vec::with_capacity(CHUNK_SIZE * 4);
+    let mut is_final = false;
+
+    let start = if known_start {
+        start
+    } else {
+        (start..stop).find(|&position| {
+            reader.set_bit_position(position);
+            // Non final dynamic Huffman block
+            if reader.peek() & 0x7 != 0x4 {
+                return false;
+            }
+            symbols.clear();
+            state.decode_block(&mut reader, &mut symbols, true) == Ok(false)
+        })?
+    };
+
+    while reader.bit_position() < stop && !is_final {
+        is_final = state.decode_block(&mut reader, &mut symbols, false).ok()?;
+    }
+
+    Some(DecodedChunk {
+        start,
+        end: reader.bit_position(),
+        is_final,
+        symbols,
+    })
+}
+
+struct OrderedDecoder<'a, F: FnMut(&[u8]) -> Result<(), ()>> {
+    data: &'a [u8],
+    /// Bit position of the first block not yet decoded
+    position: usize,
+    is_final: bool,
+    window: Vec<u8>,
+    state: InflateState,
+    crc32: Hasher,
+    written: usize,
+    buf_size: usize,
+    func: F,
+}
+
+impl<'a, F: FnMut(&[u8]) -> Result<(), ()>> OrderedDecoder<'a, F> {
+    fn new(data: &'a [u8], header_length: usize, buf_size: usize, func: F) -> Self {
+        Self {
+            data,
+            position: header_length * 8,
+            is_final: false,
+            window: Vec::with_capacity(WINDOW_SIZE * 2),
+            state: InflateState::new(usize::MAX),
+            crc32: Hasher::new(),
+            written: 0,
+            buf_size,
+            func,
+        }
+    }
+
+    fn emit(&mut self, bytes: &[u8]) -> Result<(), LibdeflateError> {
+        self.crc32.update(bytes);
+        self.written += bytes.len();
+        for chunk in bytes.chunks(self.buf_size) {
+            (self.func)(chunk).map_err(|_| LibdeflateError::InsufficientSpace)?;
+        }
+
+        if bytes.len() >= WINDOW_SIZE {
+            self.window.clear();
+            self.window
+                .extend_from_slice(&bytes[bytes.len() - WINDOW_SIZE..]);
+        } else {
+            self.window.extend_from_slice(bytes);
+            if self.window.len() > WINDOW_SIZE {
+                self.window.drain(..self.window.len() - WINDOW_SIZE);
+            }
+        }
+        Ok(())
+    }
+
+    /// Decodes the blocks until reaching the `target` bit position or the end of the stream
+    fn decode_sequential(&mut self, target: usize) -> Result<(), LibdeflateError> {
+        let mut reader = BitReader::new(self.data, self.position);
+        let mut output = self.window.clone();
+
+        while self.position < target && !self.is_final {
+            let window_size = output.len();
+            self.is_final = self
+                .state
+                .decode_block(&mut reader, &mut output, false)
+                .map_err(|_| LibdeflateError::BadData)?;
+            self.position = reader.bit_position();
+
+            self.emit(&output[window_size..])?;
+            output.drain(..output.len() - self.window.len());
+        }
+        Ok(())
+    }
+
+    fn process_chunk(&mut self, chunk: Option<DecodedChunk>) -> Result<bool, LibdeflateError> {
+        let chunk = match chunk {
+            Some(chunk) => chunk,
+            // The chunk start was not found or it was wrong
+            None => return Ok(true),
+        };
+
+        if chunk.start > self.position {
+            self.decode_sequential(chunk.start)?;
+        }
+
+        if !self.is_final && chunk.start == self.position {
+            let mut bytes = Vec::with_capacity(chunk.symbols.len());
+            for symbol in chunk.symbols {
+                if symbol < PLACEHOLDER_BASE {
+                    bytes.push(symbol as u8);
+                } else {
+                    let distance = WINDOW_SIZE - (symbol - PLACEHOLDER_BASE) as usize;
+                    if distance > self.window.len() {
+                        return Err(LibdeflateError::BadData);
+                    }
+                    bytes.push(self.window[self.window.len() - distance]);
+                }
+            }
+            self.emit(&bytes)?;
+            self.position = chunk.end;
+            self.is_final = chunk.is_final;
+        }
+
+        Ok(!self.is_final)
+    }
+}
+
+/// Decompresses a gzip file whose first member deflate stream starts after `header_length` bytes,
+/// decoding at most `max_in_flight` chunks at the same time (see `SPECULATIVE_CHUNK_MAX_MEMORY`).
+/// The following members, if any, are decompressed sequentially
+pub fn decompress_speculative(
+    data: Arc<FileBuffer>,
+    header_length: usize,
+    func: impl FnMut(&[u8]) -> Result<(), ()>,
+    buf_size: usize,
+    max_in_flight: usize,
+) -> Result<(), LibdeflateError> {
+    let chunks_count = (data.len() - header_length + CHUNK_SIZE - 1) / CHUNK_SIZE;
+    let data_bits = data.len() * 8;
+
+    let tasks_data = data.clone();
+    let tasks = (0..chunks_count).map(move |index| {
+        let data = tasks_data.clone();
+        move || {
+            let start = (header_length + index * CHUNK_SIZE) * 8;
+            let stop = (start + CHUNK_SIZE * 8).min(data_bits);
+            decode_chunk(&data, start, stop, index == 0)
+        }
+    });
+
+    let mut decoder = OrderedDecoder::new(&data, header_length, buf_size, func);
+
+    process_ordered_parallel(tasks, max_in_flight, |chunk| decoder.process_chunk(chunk))?;
+    decoder.decode_sequential(usize::MAX)?;
+
+    let trailer_start = (decoder.position + 7) / 8;
+    let trailer = data
+        .get(trailer_start..trailer_start + GZIP_FOOTER_SIZE)
+        .ok_or(LibdeflateError::BadData)?;
+
+    if decoder.crc32.finalize() != u32::from_le_bytes(trailer[0..4].try_into().unwrap())
+        || decoder.written as u32 != u32::from_le_bytes(trailer[4..8].try_into().unwrap())
+    {
+        return Err(LibdeflateError::BadData);
+    }
+
+    let next_member = trailer_start + GZIP_FOOTER_SIZE;
+    if next_member < data.len() {
+        decompress_stream_buffered(&data[next_member..], decoder.func, buf_size)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decompress_stream_buffered;
+    use crate::parallel::parse_gzip_header;
+    use crate::parallel::speculative::{
+        decode_chunk, decompress_speculative, DecodedChunk, OrderedDecoder, CHUNK_SIZE,
+    };
+    use filebuffer::FileBuffer;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use std::sync::Arc;
+
+    /// Pseudo random FASTQ like records, with repeated sequences to have long distance matches
+    fn fastq_text(size: usize) -> Vec<u8> {
+        let mut seed = 12345u64;
+        let mut random = move || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as usize
+        };
+
+        let mut sequences: Vec<Vec<u8>> = vec![];
+        let mut text = Vec::with_capacity(size + 256);
+        while text.len() < size {
+            let sequence = if sequences.len() > 16 && random() % 2 == 0 {
+                sequences[random() % sequences.len()].clone()
+            } else {
+                (0..100).map(|_| b"ACGT"[random() % 4]).collect()
+            };
+            writeln!(text, "@read{}", random() % 1000000).unwrap();
+            text.extend_from_slice(&sequence);
+            text.extend_from_slice(b"\n+\n");
+            text.extend((0..sequence.len()).map(|_| b'!' + (random() % 40) as u8));
+            text.push(b'\n');
+            sequences.push(sequence);
+        }
+        text
+    }
+
+    fn gzip(data: &[u8], level: u32) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::new(level));
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn decompress_sequential(compressed: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        decompress_stream_buffered(
+            compressed,
+            |chunk| {
+                output.extend_from_slice(chunk);
+                Ok(())
+            },
+            1024 * 64,
+        )
+        .unwrap();
+        output
+    }
+
+    fn decompress_file(name: &str, compressed: &[u8]) -> Vec<u8> {
+        let path = std::env::temp_dir().join(format!(
+            "ggcat_speculative_{}_{}.gz",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, compressed).unwrap();
+
+        let data = Arc::new(FileBuffer::open(&path).unwrap());
+        let (header_length, _) = parse_gzip_header(&data).unwrap();
+        let mut output = Vec::new();
+        decompress_speculative(
+            data,
+            header_length,
+            |chunk| {
+                output.extend_from_slice(chunk);
+                Ok(())
+            },
+            1024 * 64,
+            4,
+        )
+        .unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+        output
+    }
+
+    #[test]
+    fn speculative_decompression() {
+        let text = fastq_text(1024 * 1024 * 24);
+        let compressed = gzip(&text, 6);
+        assert!(compressed.len() > CHUNK_SIZE * 3);
+
+        // The chunks after the first one are decoded without knowing their start
+        let (header_length, _) = parse_gzip_header(&compressed).unwrap();
+        let start = (header_length + CHUNK_SIZE) * 8;
+        assert!(decode_chunk(&compressed, start, start + CHUNK_SIZE * 8, false).is_some());
+
+        let output = decompress_file("dynamic_blocks", &compressed);
+        assert!(output == text);
+        assert!(output == decompress_sequential(&compressed));
+    }
+
+    #[test]
+    fn speculative_multi_member_decompression() {
+        let texts = [fastq_text(1024 * 1024 * 12), fastq_text(1000), vec![]];
+        let compressed: Vec<u8> = texts.iter().flat_map(|text| gzip(text, 6)).collect();
+
+        let output = decompress_file("multi_member", &compressed);
+        assert!(output == texts.concat());
+        assert!(output == decompress_sequential(&compressed));
+    }
+
+    #[test]
+    fn speculative_stored_blocks_decompression() {
+        // Stored blocks are never guessed as chunks starts, so they are decoded sequentially
+        let text = fastq_text(1024 * 1024 * 8);
+        let compressed = gzip(&text, 0);
+        assert!(compressed.len() > CHUNK_SIZE * 3);
+
+        assert!(decompress_file("stored_blocks", &compressed) == text);
+    }
+
+    #[test]
+    fn speculative_wrong_start_guess() {
+        let text = fastq_text(1024 * 1024 * 8);
+        let compressed = gzip(&text, 6);
+        let (header_length, _) = parse_gzip_header(&compressed).unwrap();
+
+        let mut output = Vec::new();
+        let mut decoder = OrderedDecoder::new(&compressed, header_length, 1024 * 64, |chunk| {
+            output.extend_from_slice(chunk);
+            Ok(())
+        });
+
+        let start = header_length * 8;
+        let first_chunk = decode_chunk(&compressed, start, start + CHUNK_SIZE * 8, true);
+        assert!(decoder.process_chunk(first_chunk).unwrap());
+
+        // Chunks starting past the current position, or before it, must be discarded
+        let position = decoder.position;
+        for wrong_start in [position + 1, position - 1] {
+            let wrong_chunk = DecodedChunk {
+                start: wrong_start,
+                end: compressed.len() * 8,
+                is_final: true,
+                symbols: vec![b'X' as u16; 1000],
+            };
+            assert!(decoder.process_chunk(Some(wrong_chunk)).unwrap());
+        }
+        assert!(decoder.position > position);
+
+        decoder.decode_sequential(usize::MAX).unwrap();
+        drop(decoder);
+        assert!(output == text);
+    }
+}
@@ -104,13 +104,15 @@ impl<'a> DeflateInput for DeflateChunkedBufferInput<'a> {
         while length > 0 {
             let buffer = out_stream.get_available_buffer();
             let copyable = min(buffer.len(), length);
-            if self.read(&mut buffer[0..copyable]) != copyable {
+            // Uncompressed blocks can be longer than the input buffer, so they are read in multiple steps
+            let copied = self.read(&mut buffer[0..copyable]);
+            if copied == 0 {
                 return false;
             }
             unsafe {
-                out_stream.advance_available_buffer_position(copyable);
+                out_stream.advance_available_buffer_position(copied);
             }
-            length -= copyable;
+            length -= copied;
         }
         true
     }
@@ -2,24 +2,154 @@ use crate::buckets::readers::generic_binary_reader::{ChunkDecoder, GenericChunke
 use crate::buckets::writers::compressed_binary_writer::COMPRESSED_BUCKET_MAGIC;
 use crate::memory_fs::file::reader::FileReader;
 use crate::utils::vec_reader::VecReader;
+use std::io::{BufReader, Read, Seek, SeekFrom};
 
 pub struct CompressedStreamDecoder;
 
 const DEFAULT_BUFFER_SIZE: usize = 1024 * 1024;
 
+const ZSTD_FRAME_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Decoder of a compressed chunk, lz4 or zstd depending on the magic of its frame
+pub enum CompressedChunkReader {
+    Lz4(lz4::Decoder<FileReader>),
+    Zstd(zstd::stream::read::Decoder<'static, BufReader<FileReader>>),
+}
+
+impl CompressedChunkReader {
+    pub fn new(mut reader: FileReader) -> Self {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic).unwrap();
+        let position = reader.stream_position().unwrap();
+        reader.seek(SeekFrom::Start(position - 4)).unwrap();
+
+        if magic == ZSTD_FRAME_MAGIC {
+            Self::Zstd(
+                zstd::stream::read::Decoder::with_buffer(BufReader::new(reader))
+                    .unwrap()
+                    .single_frame(),
+            )
+        } else {
+            Self::Lz4(lz4::Decoder::new(reader).unwrap())
+        }
+    }
+
+    pub fn finish(self) -> FileReader {
+        match self {
+            Self::Lz4(decoder) => {
+                let (file, result) = decoder.finish();
+                result.unwrap();
+                file
+            }
+            Self::Zstd(decoder) => {
+                // Move back the reader to the end of the frame, as it could have been read past it
+                let mut reader = decoder.finish();
+                let position = reader.stream_position().unwrap();
+                let mut file = reader.into_inner();
+                file.seek(SeekFrom::Start(position)).unwrap();
+                file
+            }
+        }
+    }
+}
+
+impl Read for CompressedChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Lz4(decoder) => decoder.read(buf),
+            Self::Zstd(decoder) => decoder.read(buf),
+        }
+    }
+}
+
 impl ChunkDecoder for CompressedStreamDecoder {
     const MAGIC_HEADER: &'static [u8; 16] = COMPRESSED_BUCKET_MAGIC;
-    type ReaderType = VecReader<lz4::Decoder<FileReader>>;
+    type ReaderType = VecReader<CompressedChunkReader>;
 
     fn decode_stream(reader: FileReader, _size: u64) -> Self::ReaderType {
-        VecReader::new(DEFAULT_BUFFER_SIZE, lz4::Decoder::new(reader).unwrap())
+        VecReader::new(DEFAULT_BUFFER_SIZE, CompressedChunkReader::new(reader))
     }
 
     fn dispose_stream(stream: Self::ReaderType) -> FileReader {
-        let (file, result) = stream.into_inner().finish();
-        result.unwrap();
-        file
+        stream.into_inner().finish()
     }
 }
 
 pub type CompressedBinaryReader = GenericChunkedBinaryReader<CompressedStreamDecoder>;
+
+#[cfg(test)]
+mod tests {
+    use crate::buckets::readers::compressed_binary_reader::CompressedChunkReader;
+    use crate::memory_fs::file::reader::FileReader;
+    use std::io::{Read, Seek, Write};
+
+    fn chunk_data(index: usize) -> Vec<u8> {
+        (0..(100000 + index * 1000))
+            .map(|x| ((x * (index + 1)) % 251) as u8)
+            .collect()
+    }
+
+    fn write_zstd_chunk(file_data: Vec<u8>, index: usize) -> Vec<u8> {
+        let mut encoder = zstd::stream::write::Encoder::new(file_data, 1).unwrap();
+        encoder.write_all(&chunk_data(index)).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn mixed_codecs_chunks_roundtrip() {
+        let mut file_data = Vec::new();
+        let mut checkpoints = vec![];
+
+        // Some zstd chunks followed by an lz4 one, as the codec can change between the chunks of a file
+        for index in 0..3 {
+            checkpoints.push(file_data.len() as u64);
+            file_data = write_zstd_chunk(file_data, index);
+        }
+
+        checkpoints.push(file_data.len() as u64);
+        let mut encoder = lz4::EncoderBuilder::new()
+            .level(0)
+            .checksum(lz4::ContentChecksum::NoChecksum)
+            .block_mode(lz4::BlockMode::Linked)
+            .block_size(lz4::BlockSize::Max64KB)
+            .build(file_data)
+            .unwrap();
+        encoder.write_all(&chunk_data(3)).unwrap();
+        let (data, result) = encoder.finish();
+        result.unwrap();
+
+        // The last zstd chunk is read up to the end of the file
+        checkpoints.push(data.len() as u64);
+        file_data = write_zstd_chunk(data, 4);
+        checkpoints.push(file_data.len() as u64);
+
+        let path = std::env::temp_dir().join(format!(
+            "ggcat_mixed_codecs_chunks_roundtrip_{}.tmp",
+            std::process::id()
+        ));
+        std::fs::write(&path, &file_data).unwrap();
+
+        let mut file = FileReader::open(&path, None).unwrap();
+        for index in 0..5 {
+            assert_eq!(file.stream_position().unwrap(), checkpoints[index]);
+
+            let mut reader = CompressedChunkReader::new(file);
+            match (&reader, index) {
+                (CompressedChunkReader::Lz4(_), 3) => {}
+                (CompressedChunkReader::Zstd(_), index) if index != 3 => {}
+                _ => panic!("Wrong codec detected for chunk {}", index),
+            }
+
+            let mut decoded = Vec::new();
+            reader.read_to_end(&mut decoded).unwrap();
+            assert_eq!(decoded, chunk_data(index));
+
+            // The next chunk must start exactly where the current one ends
+            file = reader.finish();
+        }
+        assert_eq!(file.stream_position().unwrap(), checkpoints[5]);
+
+        file.close_and_remove(true);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::buckets::readers::compressed_binary_reader::CompressedChunkReader;
 use crate::buckets::readers::generic_binary_reader::{ChunkDecoder, GenericChunkedBinaryReader};
 use crate::buckets::writers::compressed_binary_writer::COMPRESSED_BUCKET_MAGIC;
 use crate::memory_fs::file::reader::FileReader;
@@ -6,16 +7,14 @@ pub struct UnbufferedCompressedStreamDecoder;
 
 impl ChunkDecoder for UnbufferedCompressedStreamDecoder {
     const MAGIC_HEADER: &'static [u8; 16] = COMPRESSED_BUCKET_MAGIC;
-    type ReaderType = lz4::Decoder<FileReader>;
+    type ReaderType = CompressedChunkReader;
 
     fn decode_stream(reader: FileReader, _size: u64) -> Self::ReaderType {
-        lz4::Decoder::new(reader).unwrap()
+        CompressedChunkReader::new(reader)
     }
 
     fn dispose_stream(stream: Self::ReaderType) -> FileReader {
-        let (file, result) = stream.finish();
-        result.unwrap();
-        file
+        stream.finish()
     }
 }
 
@@ -24,26 +24,60 @@ impl CompressedCheckpointSize {
     }
 }
 
-fn create_lz4_stream<W: Write>(writer: W, level: CompressionLevelInfo) -> lz4::Encoder<W> {
-    let (queue_occupation, queue_size) = GlobalFlush::global_queue_occupation();
-
-    let level = if queue_size < 2 * queue_occupation {
-        level.slow_disk
-    } else {
-        level.fast_disk
-    };
-
-    lz4::EncoderBuilder::new()
-        .level(level)
-        .checksum(ContentChecksum::NoChecksum)
-        .block_mode(BlockMode::Linked)
-        .block_size(BlockSize::Max64KB)
-        .build(writer)
-        .unwrap()
+/// Compressed stream of a chunk, the codec of each chunk is recognized by the reader from its frame magic
+enum CompressedStream<W: Write> {
+    Lz4(lz4::Encoder<W>),
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+}
+
+impl<W: Write> CompressedStream<W> {
+    fn new(writer: W, level: CompressionLevelInfo) -> Self {
+        let (queue_occupation, queue_size) = GlobalFlush::global_queue_occupation();
+
+        let compression_level = if queue_size < 2 * queue_occupation {
+            level.slow_disk
+        } else {
+            level.fast_disk
+        };
+
+        match level.codec {
+            CompressionCodec::Lz4 => Self::Lz4(
+                lz4::EncoderBuilder::new()
+                    .level(compression_level)
+                    .checksum(ContentChecksum::NoChecksum)
+                    .block_mode(BlockMode::Linked)
+                    .block_size(BlockSize::Max64KB)
+                    .build(writer)
+                    .unwrap(),
+            ),
+            // Level 0 selects the zstd default level, use the fastest one instead as in lz4
+            CompressionCodec::Zstd => Self::Zstd(
+                zstd::stream::write::Encoder::new(writer, compression_level.max(1) as i32).unwrap(),
+            ),
+        }
+    }
+
+    fn write_all(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        match self {
+            Self::Lz4(encoder) => encoder.write_all(bytes),
+            Self::Zstd(encoder) => encoder.write_all(bytes),
+        }
+    }
+
+    fn finish(self) -> W {
+        match self {
+            Self::Lz4(encoder) => {
+                let (writer, res) = encoder.finish();
+                res.unwrap();
+                writer
+            }
+            Self::Zstd(encoder) => encoder.finish().unwrap(),
+        }
+    }
 }
 
 struct CompressedBinaryWriterInternal {
-    writer: lz4::Encoder<FileWriter>,
+    writer: CompressedStream<FileWriter>,
     checkpoint_max_size: u64,
     checkpoints: Vec<u64>,
     current_chunk_size: u64,
@@ -59,13 +93,12 @@ unsafe impl Send for CompressedBinaryWriter {}
 impl CompressedBinaryWriterInternal {
     fn create_new_block(&mut self) {
         replace_with_or_abort(&mut self.writer, |writer| {
-            let (file_buf, res) = writer.finish();
-            res.unwrap();
+            let file_buf = writer.finish();
 
             let checkpoint_pos = file_buf.len();
             self.checkpoints.push(checkpoint_pos as u64);
 
-            create_lz4_stream(file_buf, self.level)
+            CompressedStream::new(file_buf, self.level)
         });
         self.current_chunk_size = 0;
     }
@@ -76,10 +109,18 @@ impl CompressedBinaryWriter {
         CompressedCheckpointSize::new_from_log2(62);
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CompressionCodec {
+    Lz4,
+    /// Slower than lz4 but with a better compression ratio, for disks with a limited bandwidth
+    Zstd,
+}
+
 #[derive(Copy, Clone)]
 pub struct CompressionLevelInfo {
     pub fast_disk: u32,
     pub slow_disk: u32,
+    pub codec: CompressionCodec,
 }
 
 impl LockFreeBucket for CompressedBinaryWriter {
@@ -108,7 +149,7 @@ impl LockFreeBucket for CompressedBinaryWriter {
 
         let first_checkpoint = initialize_bucket_file(&mut file);
 
-        let writer = create_lz4_stream(file, *compression_level);
+        let writer = CompressedStream::new(file, *compression_level);
 
         Self {
             inner: Mutex::new(CompressedBinaryWriterInternal {
@@ -142,9 +183,7 @@ impl LockFreeBucket for CompressedBinaryWriter {
     fn finalize(self) {
         let mut inner = self.inner.into_inner();
 
-        inner.writer.flush().unwrap();
-        let (file, res) = inner.writer.finish();
-        res.unwrap();
+        let file = inner.writer.finish();
 
         finalize_bucket_file(file, COMPRESSED_BUCKET_MAGIC, inner.checkpoints);
     }
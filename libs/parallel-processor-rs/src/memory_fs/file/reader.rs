@@ -155,7 +155,10 @@ impl Seek for FileReader {
                 let file = self.file.read();
                 while chunk_idx < self.chunks_count {
                     let len = file.get_chunk(chunk_idx).read().get_length();
-                    if offset < (len as u64) {
+                    // Seeking to the end of the file leaves the reader at the end of the last chunk
+                    if offset < (len as u64)
+                        || (offset == len as u64 && chunk_idx + 1 == self.chunks_count)
+                    {
                         break;
                     }
                     chunk_idx += 1;
@@ -190,15 +193,18 @@ impl Seek for FileReader {
 
         let file_read = self.file.read();
 
-        for i in 0..self.current_chunk_index {
+        // The chunk index goes past the last chunk after reading the whole file
+        for i in 0..min(self.current_chunk_index, self.chunks_count) {
             position += file_read.get_chunk(i).read().get_length();
         }
 
-        position += file_read
-            .get_chunk(self.current_chunk_index)
-            .read()
-            .get_length()
-            - self.current_len;
+        if self.current_chunk_index < self.chunks_count {
+            position += file_read
+                .get_chunk(self.current_chunk_index)
+                .read()
+                .get_length()
+                - self.current_len;
+        }
 
         Ok(position as u64)
     }
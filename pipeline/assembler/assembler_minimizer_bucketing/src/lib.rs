@@ -1,8 +1,9 @@
 use ::static_dispatch::static_dispatch;
 use colors::colors_manager::color_types::MinimizerBucketingSeqColorDataType;
 use colors::colors_manager::{ColorsManager, MinimizerBucketingSeqColorData};
+use colors::parsers::records::RecordColorsTable;
 use colors::parsers::SingleSequenceInfo;
-use config::BucketIndexType;
+use config::{BucketIndexType, ColorIndexType};
 use config::{READ_FLAG_INCL_BEGIN, READ_FLAG_INCL_END};
 use hashes::rolling::minqueue::RollingMinQueue;
 use hashes::ExtendableHashTraitType;
@@ -25,7 +26,7 @@ use std::sync::Arc;
 
 pub struct AssemblerMinimizerBucketingExecutor<H: MinimizerHashFunctionFactory, CX: ColorsManager> {
     minimizer_queue: RollingMinQueue<H>,
-    global_data: Arc<MinimizerBucketingCommonData<()>>,
+    global_data: Arc<MinimizerBucketingCommonData<Option<RecordColorsTable>>>,
     _phantom: PhantomData<CX>,
 }
 
@@ -52,7 +53,7 @@ impl<CX: ColorsManager> Default for AssemblerPreprocessInfo<CX> {
 
 #[derive(Clone, Default)]
 pub struct InputFileInfo {
-    file_index: usize,
+    file_color: ColorIndexType,
 }
 
 pub struct AssemblerMinimizerBucketingExecutorFactory<
@@ -63,7 +64,7 @@ pub struct AssemblerMinimizerBucketingExecutorFactory<
 impl<H: MinimizerHashFunctionFactory, CX: ColorsManager> MinimizerBucketingExecutorFactory
     for AssemblerMinimizerBucketingExecutorFactory<H, CX>
 {
-    type GlobalData = ();
+    type GlobalData = Option<RecordColorsTable>;
     type ExtraData = MinimizerBucketingSeqColorDataType<CX>;
     type PreprocessInfo = AssemblerPreprocessInfo<CX>;
     type FileInfo = InputFileInfo;
@@ -101,7 +102,8 @@ impl<H: MinimizerHashFunctionFactory, CX: ColorsManager>
 
         preprocess_info.color_info = MinimizerBucketingSeqColorDataType::<CX>::create(
             SingleSequenceInfo {
-                file_index: file_info.file_index,
+                file_color: file_info.file_color,
+                record_colors: self.global_data.global_data.as_ref(),
                 sequence_ident: sequence.ident,
             },
             &mut preprocess_info.color_info_buffer,
@@ -208,12 +210,13 @@ impl<H: MinimizerHashFunctionFactory, CX: ColorsManager>
     colors::non_colored::NonColoredManager,
 ])]
 pub fn minimizer_bucketing<H: MinimizerHashFunctionFactory, CX: ColorsManager>(
-    input_files: Vec<PathBuf>,
+    input_files: Vec<(PathBuf, ColorIndexType)>,
     output_path: &Path,
     buckets_count: usize,
     threads_count: usize,
     k: usize,
     m: usize,
+    record_colors: Option<RecordColorsTable>,
 ) -> (Vec<PathBuf>, PathBuf) {
     H::initialize(k);
 
@@ -223,10 +226,12 @@ pub fn minimizer_bucketing<H: MinimizerHashFunctionFactory, CX: ColorsManager>(
 
     let input_files: Vec<_> = input_files
         .into_iter()
-        .enumerate()
-        .map(|(i, f)| (f, InputFileInfo { file_index: i }))
+        .map(|(f, color)| (f, InputFileInfo { file_color: color }))
         .collect();
 
+    // The sequence headers are needed only to assign colors per record
+    let copy_ident = record_colors.is_some();
+
     GenericMinimizerBucketing::do_bucketing::<AssemblerMinimizerBucketingExecutorFactory<H, CX>>(
         input_files,
         output_path,
@@ -234,8 +239,8 @@ pub fn minimizer_bucketing<H: MinimizerHashFunctionFactory, CX: ColorsManager>(
         threads_count,
         k,
         m,
-        (),
+        record_colors,
         Some(k - 1),
-        false,
+        copy_ident,
     )
 }
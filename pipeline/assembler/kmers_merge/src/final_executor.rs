@@ -1,5 +1,8 @@
+use crate::histogram::KmersHistogram;
+use crate::kmers_dump::{KmersDump, KmersDumpBuffer};
 use crate::map_processor::ParallelKmersMergeMapPacket;
 use crate::{GlobalMergeData, ParallelKmersMergeFactory, ResultsBucket};
+use colors::abundance::UnitigAbundance;
 use colors::colors_manager::ColorsMergeManager;
 use colors::colors_manager::{color_types, ColorsManager};
 use config::DEFAULT_PER_CPU_BUFFER_SIZE;
@@ -39,6 +42,8 @@ pub struct ParallelKmersMergeFinalExecutor<
         <color_types::PartialUnitigsColorStructure<H, MH, CX> as SequenceExtraData>::TempBuffer,
     bucket_counter: usize,
     bucket_change_threshold: usize,
+    local_histogram: Vec<u64>,
+    local_kmers_dump: Option<KmersDumpBuffer>,
     _phantom: PhantomData<H>,
 }
 
@@ -60,6 +65,11 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                 color_types::PartialUnitigsColorStructure::<H, MH, CX>::new_temp_buffer(),
             bucket_counter: 0,
             bucket_change_threshold: 16, // TODO: Parametrize
+            local_histogram: KmersHistogram::alloc_local_counts(),
+            local_kmers_dump: global_data
+                .kmers_dump
+                .as_ref()
+                .map(|_| KmersDump::alloc_local_buffer()),
             _phantom: PhantomData,
         }
     }
@@ -176,8 +186,16 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
         global_data: &<ParallelKmersMergeFactory<H, MH, CX> as KmersTransformExecutorFactory>::GlobalExtraData,
         mut map_struct_packet: Packet<Self::MapStruct>,
     ) -> Packet<Self::MapStruct> {
-        if self.current_bucket.is_none() {
-            self.current_bucket = Some(global_data.output_results_buckets.pop().unwrap());
+        // The counters must be read before processing the colors, as they are replaced by the colors indexes
+        for entry in map_struct_packet.rhash_map.values() {
+            KmersHistogram::add_to_local_counts(
+                &mut self.local_histogram,
+                entry.get_kmer_multiplicity(),
+            );
+        }
+
+        if global_data.histogram_only && global_data.kmers_dump.is_none() {
+            return map_struct_packet;
         }
 
         let map_struct = map_struct_packet.deref_mut();
@@ -186,9 +204,6 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
         let buckets_count = global_data.buckets_count;
         let buckets_count_bits = buckets_count.ilog2() as usize;
 
-        let current_bucket = self.current_bucket.as_mut().unwrap();
-        let bucket_index = current_bucket.get_bucket_index();
-
         if CX::COLORS_ENABLED {
             CX::ColorsMergeManagerType::<H, MH>::process_colors(
                 &global_data.colors_global_table,
@@ -204,6 +219,35 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
             return map_struct_packet;
         }
 
+        if let Some(kmers_dump) = &global_data.kmers_dump {
+            let local_kmers_dump = self.local_kmers_dump.as_mut().unwrap();
+            Self::get_kmers(global_data, map_struct, |_, cread, rhentry| {
+                kmers_dump.add_kmer(
+                    local_kmers_dump,
+                    cread,
+                    CX::ColorsMergeManagerType::<H, MH>::get_kmer_multiplicity(rhentry),
+                    CX::ColorsMergeManagerType::<H, MH>::get_kmer_colors_subset(rhentry),
+                );
+                // The same kmer can be found in more than one saved read
+                rhentry.set_used();
+            });
+
+            for entry in map_struct.rhash_map.values() {
+                entry.clear_used();
+            }
+
+            if global_data.histogram_only {
+                return map_struct_packet;
+            }
+        }
+
+        if self.current_bucket.is_none() {
+            self.current_bucket = Some(global_data.output_results_buckets.pop().unwrap());
+        }
+
+        let current_bucket = self.current_bucket.as_mut().unwrap();
+        let bucket_index = current_bucket.get_bucket_index();
+
         Self::get_kmers(global_data, map_struct, |hash, cread, rhentry| {
             let ignored_status = rhentry.get_flags();
 
@@ -313,7 +357,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                             break (temp_data.0, false);
                         }
 
-                        if CX::COLORS_ENABLED {
+                        if CX::COLORS_ENABLED || UnitigAbundance::is_enabled() {
                             colors_function(&mut self.unitigs_temp_colors, entryref);
                         }
 
@@ -488,8 +532,18 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
 
     fn finalize(
         self,
-        _global_data: &<ParallelKmersMergeFactory<H, MH, CX> as KmersTransformExecutorFactory>::GlobalExtraData,
+        global_data: &<ParallelKmersMergeFactory<H, MH, CX> as KmersTransformExecutorFactory>::GlobalExtraData,
     ) {
+        global_data
+            .kmers_histogram
+            .add_local_counts(&self.local_histogram);
+        if let Some(mut local_kmers_dump) = self.local_kmers_dump {
+            global_data
+                .kmers_dump
+                .as_ref()
+                .unwrap()
+                .flush_local_buffer(&mut local_kmers_dump);
+        }
         self.hashes_tmp.finalize();
     }
 }
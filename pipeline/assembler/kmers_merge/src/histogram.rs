@@ -0,0 +1,107 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Kmers with a higher multiplicity are counted in the last bin, as done by jellyfish
+pub const HISTOGRAM_MAX_MULTIPLICITY: usize = 10000;
+
+/// Kmers frequency histogram, holding the number of distinct kmers for each multiplicity
+pub struct KmersHistogram {
+    counts: Vec<AtomicU64>,
+}
+
+impl Default for KmersHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KmersHistogram {
+    pub fn new() -> Self {
+        Self {
+            counts: (0..=HISTOGRAM_MAX_MULTIPLICITY)
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+        }
+    }
+
+    /// Allocates a thread local histogram, to be merged with `add_local_counts`
+    pub fn alloc_local_counts() -> Vec<u64> {
+        vec![0; HISTOGRAM_MAX_MULTIPLICITY + 1]
+    }
+
+    #[inline(always)]
+    pub fn add_to_local_counts(local_counts: &mut [u64], multiplicity: usize) {
+        local_counts[multiplicity.min(HISTOGRAM_MAX_MULTIPLICITY)] += 1;
+    }
+
+    pub fn add_local_counts(&self, local_counts: &[u64]) {
+        for (count, local) in self.counts.iter().zip(local_counts.iter()) {
+            if *local > 0 {
+                count.fetch_add(*local, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Writes the histogram in the jellyfish histo format (one '<multiplicity> <count>' line for each nonzero bin),
+    /// accepted by GenomeScope
+    pub fn write_histo(&self, path: impl AsRef<Path>) {
+        let mut file = BufWriter::new(File::create(path).unwrap());
+
+        for (multiplicity, count) in self.counts.iter().enumerate().skip(1) {
+            let count = count.load(Ordering::Relaxed);
+            if count > 0 {
+                writeln!(file, "{} {}", multiplicity, count).unwrap();
+            }
+        }
+    }
+
+    /// Finds the first local minimum of the histogram, that separates the erroneous kmers from the solid ones.
+    /// Returns None if the histogram does not start with a decreasing error peak, or if it never increases again
+    pub fn find_first_valley(&self) -> Option<usize> {
+        let counts: Vec<_> = self
+            .counts
+            .iter()
+            .map(|c| c.load(Ordering::Relaxed))
+            .collect();
+
+        if counts[1] <= counts[2] {
+            return None;
+        }
+
+        (2..HISTOGRAM_MAX_MULTIPLICITY)
+            .find(|&multiplicity| counts[multiplicity] < counts[multiplicity + 1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::histogram::KmersHistogram;
+
+    fn histogram_from_counts(counts: &[u64]) -> KmersHistogram {
+        let histogram = KmersHistogram::new();
+        let mut local_counts = KmersHistogram::alloc_local_counts();
+        local_counts[1..=counts.len()].copy_from_slice(counts);
+        histogram.add_local_counts(&local_counts);
+        histogram
+    }
+
+    #[test]
+    fn histogram_valley() {
+        // Error peak, valley at 4 and coverage peak at 8
+        let histogram =
+            histogram_from_counts(&[9000, 800, 90, 40, 60, 150, 300, 420, 310, 120, 30]);
+        assert_eq!(histogram.find_first_valley(), Some(4));
+
+        // No error peak
+        let histogram = histogram_from_counts(&[50, 400, 900, 300, 20, 35, 10]);
+        assert_eq!(histogram.find_first_valley(), None);
+
+        // Monotonically decreasing
+        let histogram = histogram_from_counts(&[5000, 700, 80, 9, 1]);
+        assert_eq!(histogram.find_first_valley(), None);
+
+        assert_eq!(KmersHistogram::new().find_first_valley(), None);
+    }
+}
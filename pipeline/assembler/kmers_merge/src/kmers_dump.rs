@@ -0,0 +1,266 @@
+use config::{ColorIndexType, DEFAULT_OUTPUT_BUFFER_SIZE};
+use io::compressed_read::CompressedRead;
+use parking_lot::Mutex;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Magic bytes at the start of a binary kmers dump
+pub const KMERS_DUMP_MAGIC: &[u8; 8] = b"GGCATKMR";
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum KmersDumpFormat {
+    /// One 'kmer<TAB>count[<TAB>colors subset]' line for each kmer, as in the jellyfish/KMC text dumps
+    Text,
+    /// A header (magic, k as u32, colors flag as u8) followed by one record for each kmer:
+    /// the 2-bit packed kmer (A=0, C=1, G=2, T=3, first base in the high bits), the count as u32
+    /// and, with colors, the colors subset as u32, all little endian
+    Binary,
+}
+
+#[derive(Clone, Debug)]
+pub struct KmersDumpConfig {
+    pub path: PathBuf,
+    pub format: KmersDumpFormat,
+    /// Write each kmer in its lexicographically smallest orientation
+    pub canonical: bool,
+    /// Stop after dumping the kmers, without building the unitigs
+    pub skip_unitigs: bool,
+}
+
+/// Thread local buffer of a kmers dump
+pub struct KmersDumpBuffer {
+    data: Vec<u8>,
+    kmer_bases: Vec<u8>,
+}
+
+/// Shared writer of the solid kmers found while merging the buckets, each thread fills a local buffer
+/// that is flushed to the output file when full
+pub struct KmersDump {
+    path: PathBuf,
+    writer: Mutex<BufWriter<File>>,
+    format: KmersDumpFormat,
+    canonical: bool,
+    with_colors: bool,
+    kmers_count: AtomicU64,
+}
+
+impl KmersDump {
+    pub fn new(config: &KmersDumpConfig, k: usize, with_colors: bool) -> Self {
+        let mut writer = BufWriter::new(File::create(&config.path).unwrap_or_else(|err| {
+            println!(
+                "ERROR: Cannot create kmers dump file {}: {}",
+                config.path.display(),
+                err
+            );
+            std::process::exit(1);
+        }));
+
+        if config.format == KmersDumpFormat::Binary {
+            writer.write_all(KMERS_DUMP_MAGIC).unwrap();
+            writer.write_all(&(k as u32).to_le_bytes()).unwrap();
+            writer.write_all(&[with_colors as u8]).unwrap();
+        }
+
+        Self {
+            path: config.path.clone(),
+            writer: Mutex::new(writer),
+            format: config.format,
+            canonical: config.canonical,
+            with_colors,
+            kmers_count: AtomicU64::new(0),
+        }
+    }
+
+    /// Allocates a thread local buffer, to be flushed with `flush_local_buffer`
+    pub fn alloc_local_buffer() -> KmersDumpBuffer {
+        KmersDumpBuffer {
+            data: Vec::with_capacity(DEFAULT_OUTPUT_BUFFER_SIZE),
+            kmer_bases: Vec::new(),
+        }
+    }
+
+    pub fn add_kmer(
+        &self,
+        local_buffer: &mut KmersDumpBuffer,
+        kmer: CompressedRead,
+        count: u64,
+        colors_subset: Option<ColorIndexType>,
+    ) {
+        let KmersDumpBuffer {
+            data: buffer,
+            kmer_bases,
+        } = local_buffer;
+        let k = kmer.get_length();
+
+        kmer_bases.clear();
+        kmer_bases.resize(k, 0);
+        kmer.write_unpacked_to_slice(&mut kmer_bases[..]);
+
+        if self.canonical {
+            let is_reverse_smaller = kmer_bases
+                .iter()
+                .zip(kmer_bases.iter().rev())
+                .map(|(fw, bw)| (*fw, complement_base(*bw)))
+                .find(|(fw, rc)| fw != rc)
+                .map(|(fw, rc)| rc < fw)
+                .unwrap_or(false);
+
+            if is_reverse_smaller {
+                kmer_bases.reverse();
+                kmer_bases
+                    .iter_mut()
+                    .for_each(|base| *base = complement_base(*base));
+            }
+        }
+
+        match self.format {
+            KmersDumpFormat::Text => {
+                buffer.extend_from_slice(kmer_bases);
+                write!(buffer, "\t{}", count).unwrap();
+                if self.with_colors {
+                    write!(buffer, "\t{}", colors_subset.unwrap_or(0)).unwrap();
+                }
+                buffer.push(b'\n');
+            }
+            KmersDumpFormat::Binary => {
+                for bases in kmer_bases.chunks(4) {
+                    let mut packed = 0;
+                    for (index, base) in bases.iter().enumerate() {
+                        packed |= encode_base(*base) << (6 - index * 2);
+                    }
+                    buffer.push(packed);
+                }
+                buffer.extend_from_slice(&(count.min(u32::MAX as u64) as u32).to_le_bytes());
+                if self.with_colors {
+                    buffer.extend_from_slice(&colors_subset.unwrap_or(0).to_le_bytes());
+                }
+            }
+        }
+
+        self.kmers_count.fetch_add(1, Ordering::Relaxed);
+
+        if buffer.len() >= DEFAULT_OUTPUT_BUFFER_SIZE {
+            self.flush_local_buffer(local_buffer);
+        }
+    }
+
+    pub fn flush_local_buffer(&self, local_buffer: &mut KmersDumpBuffer) {
+        if !local_buffer.data.is_empty() {
+            self.writer.lock().write_all(&local_buffer.data).unwrap();
+            local_buffer.data.clear();
+        }
+    }
+
+    pub fn finalize(&self) {
+        self.writer.lock().flush().unwrap();
+        println!(
+            "Dumped {} kmers to {}",
+            self.kmers_count.load(Ordering::Relaxed),
+            self.path.display()
+        );
+    }
+}
+
+#[inline(always)]
+fn complement_base(base: u8) -> u8 {
+    match base {
+        b'A' => b'T',
+        b'C' => b'G',
+        b'G' => b'C',
+        _ => b'A',
+    }
+}
+
+#[inline(always)]
+fn encode_base(base: u8) -> u8 {
+    match base {
+        b'A' => 0,
+        b'C' => 1,
+        b'G' => 2,
+        _ => 3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kmers_dump::{KmersDump, KmersDumpConfig, KmersDumpFormat, KMERS_DUMP_MAGIC};
+    use io::compressed_read::CompressedReadIndipendent;
+
+    /// Dumps the kmers with their counts and colors subsets, returning the contents of the dump file
+    fn dump_kmers(
+        name: &str,
+        format: KmersDumpFormat,
+        canonical: bool,
+        with_colors: bool,
+        k: usize,
+        kmers: &[(&str, u64, u32)],
+    ) -> Vec<u8> {
+        let path = std::env::temp_dir().join(format!(
+            "ggcat_kmers_dump_{}_{}.txt",
+            name,
+            std::process::id()
+        ));
+        let config = KmersDumpConfig {
+            path: path.clone(),
+            format,
+            canonical,
+            skip_unitigs: false,
+        };
+
+        let dump = KmersDump::new(&config, k, with_colors);
+        let mut local_buffer = KmersDump::alloc_local_buffer();
+        let mut storage = Vec::new();
+        for (kmer, count, colors_subset) in kmers {
+            storage.clear();
+            let kmer = CompressedReadIndipendent::from_plain(kmer.as_bytes(), &mut storage);
+            dump.add_kmer(
+                &mut local_buffer,
+                kmer.as_reference(&storage),
+                *count,
+                Some(*colors_subset),
+            );
+        }
+        dump.flush_local_buffer(&mut local_buffer);
+        dump.finalize();
+        drop(dump);
+
+        let contents = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        contents
+    }
+
+    #[test]
+    fn text_dump() {
+        // ACGCGT is palindromic, the reverse complement of TTTACG is CGTAAA, while GGTACA is already the smallest
+        let kmers = [("ACGCGT", 3, 0), ("TTTACG", 5, 2), ("GGTACA", 7, 1)];
+
+        let dump = dump_kmers("text", KmersDumpFormat::Text, true, false, 6, &kmers);
+        assert_eq!(
+            String::from_utf8(dump).unwrap(),
+            "ACGCGT\t3\nCGTAAA\t5\nGGTACA\t7\n"
+        );
+
+        let dump = dump_kmers("text_colors", KmersDumpFormat::Text, false, true, 6, &kmers);
+        assert_eq!(
+            String::from_utf8(dump).unwrap(),
+            "ACGCGT\t3\t0\nTTTACG\t5\t2\nGGTACA\t7\t1\n"
+        );
+    }
+
+    #[test]
+    fn binary_dump() {
+        let kmers = [("ACGCGT", 3, 0), ("TTTACG", 5_000_000_000, 2)];
+
+        let dump = dump_kmers("binary", KmersDumpFormat::Binary, true, true, 6, &kmers);
+
+        let mut expected = KMERS_DUMP_MAGIC.to_vec();
+        expected.extend_from_slice(&[6, 0, 0, 0, 1]);
+        // ACGC GT, with the last byte padded
+        expected.extend_from_slice(&[0b00011001, 0b10110000, 3, 0, 0, 0, 0, 0, 0, 0]);
+        // CGTA AA, with the count clamped to u32
+        expected.extend_from_slice(&[0b01101100, 0, 255, 255, 255, 255, 2, 0, 0, 0]);
+        assert_eq!(dump, expected);
+    }
+}
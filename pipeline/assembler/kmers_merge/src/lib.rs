@@ -1,6 +1,8 @@
 #![feature(int_log)]
 
 use crate::final_executor::ParallelKmersMergeFinalExecutor;
+use crate::histogram::KmersHistogram;
+use crate::kmers_dump::KmersDump;
 use crate::map_processor::{ParallelKmersMergeMapProcessor, KMERGE_TEMP_DIR};
 use crate::preprocessor::ParallelKmersMergePreprocessor;
 use crate::structs::{ResultsBucket, RetType};
@@ -9,6 +11,7 @@ use colors::colors_manager::color_types::{
     GlobalColorsTableWriter, MinimizerBucketingSeqColorDataType,
 };
 use colors::colors_manager::{color_types, ColorsManager};
+use colors::parsers::records::RecordColorsTable;
 use config::{
     get_compression_level_info, get_memory_mode, BucketIndexType, SwapPriority,
     MINIMUM_SUBBUCKET_KMERS_COUNT, RESPLITTING_MAX_K_M_DIFFERENCE,
@@ -37,6 +40,8 @@ use std::sync::Arc;
 use utils::owned_drop::OwnedDrop;
 
 mod final_executor;
+pub mod histogram;
+pub mod kmers_dump;
 mod map_processor;
 mod preprocessor;
 pub mod structs;
@@ -54,7 +59,10 @@ pub struct GlobalMergeData<
     output_results_buckets:
         ArrayQueue<ResultsBucket<color_types::PartialUnitigsColorStructure<H, MH, CX>>>,
     hashes_buckets: Arc<MultiThreadBuckets<LockFreeBinaryWriter>>,
-    global_resplit_data: Arc<MinimizerBucketingCommonData<()>>,
+    global_resplit_data: Arc<MinimizerBucketingCommonData<Option<RecordColorsTable>>>,
+    kmers_histogram: Arc<KmersHistogram>,
+    histogram_only: bool,
+    kmers_dump: Option<Arc<KmersDump>>,
     sequences_size_total: AtomicU64,
     hasnmap_kmers_total: AtomicU64,
     kmer_batches_count: AtomicU64,
@@ -141,6 +149,9 @@ pub fn kmers_merge<
     k: usize,
     m: usize,
     threads_count: usize,
+    kmers_histogram: Arc<KmersHistogram>,
+    histogram_only: bool,
+    kmers_dump: Option<Arc<KmersDump>>,
 ) -> RetType {
     PHASES_TIMES_MONITOR
         .write()
@@ -167,7 +178,7 @@ pub fn kmers_merge<
         &(
             get_memory_mode(SwapPriority::ResultBuckets),
             CompressedBinaryWriter::CHECKPOINT_SIZE_UNLIMITED,
-            get_compression_level_info(),
+            get_compression_level_info(SwapPriority::ResultBuckets),
         ),
     );
 
@@ -202,8 +213,12 @@ pub fn kmers_merge<
             }, // m
             buckets_count,
             1,
-            (),
+            // Resplit sequences already carry their colors, no need to recompute them from headers
+            None,
         )),
+        kmers_histogram,
+        histogram_only,
+        kmers_dump,
         sequences_size_total: AtomicU64::new(0),
         hasnmap_kmers_total: AtomicU64::new(0),
         kmer_batches_count: AtomicU64::new(0),
@@ -327,6 +342,9 @@ mod tests {
             k,
             m,
             threads_count,
+            Arc::new(KmersHistogram::new()),
+            false,
+            None,
         );
     }
 }
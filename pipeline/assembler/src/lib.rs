@@ -2,6 +2,8 @@
 
 use crate::pipeline::build_unitigs::build_unitigs;
 use crate::pipeline::compute_matchtigs::{compute_matchtigs_thread, MatchtigsStorageBackend};
+use crate::pipeline::graph_cleaning::clean_graph;
+use crate::pipeline::graph_index::write_unitigs_with_index;
 use crate::pipeline::hashes_sorting::hashes_sorting;
 use crate::pipeline::links_compaction::links_compaction;
 use crate::pipeline::maximal_unitig_links::build_maximal_unitigs_links;
@@ -9,17 +11,22 @@ use crate::pipeline::reorganize_reads::reorganize_reads;
 use ::static_dispatch::static_dispatch;
 use colors::colors_manager::ColorsManager;
 use colors::colors_manager::ColorsMergeManager;
+use colors::parsers::records::{RecordColorsMode, RecordColorsTable};
 use config::{
-    get_compression_level_info, get_memory_mode, SwapPriority, DEFAULT_PER_CPU_BUFFER_SIZE,
+    get_compression_level_info, get_memory_mode, ColorIndexType, SwapPriority,
+    DEFAULT_MIN_MULTIPLICITY, DEFAULT_PER_CPU_BUFFER_SIZE,
     INTERMEDIATE_COMPRESSION_LEVEL_FAST, INTERMEDIATE_COMPRESSION_LEVEL_SLOW, KEEP_FILES,
-    MAXIMUM_SECOND_BUCKETS_LOG, MINIMUM_LOG_DELTA_TIME,
+    MAXIMUM_SECOND_BUCKETS_LOG, MINIMUM_LOG_DELTA_TIME, TRACK_UNITIGS_ABUNDANCE,
 };
 use hashes::{HashFunctionFactory, MinimizerHashFunctionFactory};
 use io::concurrent::structured_sequences::binary::StructSeqBinaryWriter;
 use io::concurrent::structured_sequences::fasta::FastaWriter;
 use io::concurrent::structured_sequences::StructuredSequenceWriter;
 use io::{compute_stats_from_input_files, generate_bucket_names};
+use kmers_merge::histogram::KmersHistogram;
+use kmers_merge::kmers_dump::KmersDump;
 use kmers_merge::structs::RetType;
+use minimizer_bucketing::inputs_stats::print_inputs_stats;
 use parallel_processor::buckets::concurrent::BucketsThreadBuffer;
 use parallel_processor::buckets::writers::compressed_binary_writer::CompressedCheckpointSize;
 use parallel_processor::buckets::writers::lock_free_binary_writer::LockFreeBinaryWriter;
@@ -38,6 +45,8 @@ mod pipeline;
 mod structs;
 
 pub use pipeline::compute_matchtigs::MatchtigMode;
+pub use kmers_merge::kmers_dump::{KmersDumpConfig, KmersDumpFormat};
+pub use pipeline::graph_cleaning::GraphCleaningConfig;
 
 #[derive(PartialEq, PartialOrd)]
 pub enum AssemblerStartingStep {
@@ -81,21 +90,38 @@ pub fn run_assembler<
     m: usize,
     step: AssemblerStartingStep,
     last_step: AssemblerStartingStep,
-    input: Vec<PathBuf>,
+    input: Vec<(PathBuf, ColorIndexType)>,
+    color_names: Vec<String>,
+    record_colors_mode: Option<RecordColorsMode>,
     output_file: PathBuf,
     temp_dir: PathBuf,
     threads_count: usize,
-    min_multiplicity: usize,
+    min_multiplicity: Option<usize>,
     buckets_count_log: Option<usize>,
     loopit_number: Option<usize>,
     default_compression_level: Option<u32>,
     generate_maximal_unitigs_links: bool,
     compute_tigs_mode: Option<MatchtigMode>,
+    graph_cleaning: Option<GraphCleaningConfig>,
+    graph_index: bool,
+    kmers_dump: Option<KmersDumpConfig>,
+    write_histogram: bool,
     only_bstats: bool,
 ) {
     PHASES_TIMES_MONITOR.write().init();
 
-    let file_stats = compute_stats_from_input_files(&input);
+    // Graph cleaning needs the unitigs links and abundances, the graph index needs the links
+    let generate_maximal_unitigs_links = generate_maximal_unitigs_links
+        || ((graph_cleaning.is_some() || graph_index) && compute_tigs_mode.is_none());
+    if graph_cleaning.is_some() {
+        TRACK_UNITIGS_ABUNDANCE.store(true, Ordering::Relaxed);
+    }
+
+    // The histogram used to choose the min multiplicity is always written
+    let write_histogram = write_histogram || min_multiplicity.is_none();
+
+    let input_paths: Vec<_> = input.iter().map(|(path, _)| path.clone()).collect();
+    let file_stats = compute_stats_from_input_files(&input_paths);
 
     let buckets_count_log = buckets_count_log.unwrap_or_else(|| file_stats.best_buckets_count_log);
 
@@ -106,10 +132,13 @@ pub fn run_assembler<
 
     let buckets_count = 1 << buckets_count_log;
 
-    let color_names: Vec<_> = input
-        .iter()
-        .map(|f| f.file_name().unwrap().to_string_lossy().to_string())
-        .collect();
+    let (record_colors, color_names) = match record_colors_mode {
+        Some(mode) if AssemblerColorsManager::COLORS_ENABLED => {
+            let (record_colors, color_names) = RecordColorsTable::build(&input_paths, mode);
+            (Some(record_colors), color_names)
+        }
+        _ => (None, color_names),
+    };
 
     let global_colors_table = Arc::new(
         AssemblerColorsManager::ColorsMergeManagerType::create_colors_table(
@@ -129,6 +158,7 @@ pub fn run_assembler<
             threads_count,
             k,
             m,
+            record_colors,
         )
     } else {
         (
@@ -171,7 +201,67 @@ pub fn run_assembler<
     }
 
     let RetType { sequences, hashes } = if step <= AssemblerStartingStep::KmersMerge {
-        kmers_merge::kmers_merge::<BucketingHash, MergingHash, AssemblerColorsManager, _>(
+        let min_multiplicity = match min_multiplicity {
+            Some(min_multiplicity) => min_multiplicity,
+            None => {
+                // Run a first counting pass to find the histogram valley, keeping the buckets for the real merge
+                let keep_files = KEEP_FILES.swap(true, Ordering::Relaxed);
+                let histogram = Arc::new(KmersHistogram::new());
+                let RetType { sequences, hashes } = kmers_merge::kmers_merge::<
+                    BucketingHash,
+                    MergingHash,
+                    AssemblerColorsManager,
+                    _,
+                >(
+                    buckets.clone(),
+                    counters.clone(),
+                    global_colors_table.clone(),
+                    buckets_count,
+                    1,
+                    temp_dir.as_path(),
+                    k,
+                    m,
+                    threads_count,
+                    histogram.clone(),
+                    true,
+                    None,
+                );
+                KEEP_FILES.store(keep_files, Ordering::Relaxed);
+
+                for file in sequences.into_iter().chain(hashes.into_iter()) {
+                    let _ = MemoryFs::remove_file(file, RemoveFileMode::Remove { remove_fs: true });
+                }
+
+                match histogram.find_first_valley() {
+                    Some(valley) => {
+                        println!("Automatic min multiplicity: {}", valley);
+                        valley
+                    }
+                    None => {
+                        println!(
+                            "Warning: cannot find a valley in the kmers histogram, using the default min multiplicity ({})",
+                            DEFAULT_MIN_MULTIPLICITY
+                        );
+                        DEFAULT_MIN_MULTIPLICITY
+                    }
+                }
+            }
+        };
+
+        let skip_unitigs = kmers_dump
+            .as_ref()
+            .map(|config| config.skip_unitigs)
+            .unwrap_or(false);
+        let kmers_dump = kmers_dump.map(|config| {
+            Arc::new(KmersDump::new(
+                &config,
+                k,
+                AssemblerColorsManager::COLORS_ENABLED,
+            ))
+        });
+
+        let histogram = Arc::new(KmersHistogram::new());
+        let result = kmers_merge::kmers_merge::<BucketingHash, MergingHash, AssemblerColorsManager, _>(
             buckets,
             counters,
             global_colors_table.clone(),
@@ -181,7 +271,32 @@ pub fn run_assembler<
             k,
             m,
             threads_count,
-        )
+            histogram.clone(),
+            skip_unitigs,
+            kmers_dump.clone(),
+        );
+        if write_histogram {
+            histogram.write_histo(output_file.with_extension("histo"));
+        }
+
+        if let Some(kmers_dump) = kmers_dump {
+            kmers_dump.finalize();
+        }
+
+        if skip_unitigs {
+            for file in result.sequences.into_iter().chain(result.hashes.into_iter()) {
+                let _ = MemoryFs::remove_file(file, RemoveFileMode::Remove { remove_fs: true });
+            }
+            AssemblerColorsManager::ColorsMergeManagerType::print_color_stats(&global_colors_table);
+            drop(global_colors_table);
+            let _ = std::fs::remove_dir(temp_dir.as_path());
+            PHASES_TIMES_MONITOR
+                .write()
+                .print_stats("Kmers dump completed.".to_string());
+            return;
+        }
+
+        result
     } else {
         RetType {
             sequences: generate_bucket_names(temp_dir.join("result"), buckets_count, None),
@@ -342,6 +457,7 @@ pub fn run_assembler<
         Some(ext) => match ext.to_string_lossy().to_string().as_str() {
             "lz4" => FastaWriter::new_compressed_lz4(&output_file, 2),
             "gz" => FastaWriter::new_compressed_gzip(&output_file, 2),
+            "zst" => FastaWriter::new_compressed_zstd(&output_file, 3),
             _ => FastaWriter::new_plain(&output_file),
         },
         None => FastaWriter::new_plain(&output_file),
@@ -355,7 +471,7 @@ pub fn run_assembler<
                 &(
                     get_memory_mode(SwapPriority::FinalMaps as usize),
                     CompressedCheckpointSize::new_from_size(MemoryDataSize::from_mebioctets(4)),
-                    get_compression_level_info(),
+                    get_compression_level_info(SwapPriority::FinalMaps as usize),
                 ),
             )))
         } else {
@@ -463,17 +579,46 @@ pub fn run_assembler<
                     })
                     .unwrap();
 
-                build_maximal_unitigs_links::<
-                    BucketingHash,
-                    MergingHash,
-                    AssemblerColorsManager,
-                    MatchtigsStorageBackend<_>,
-                >(
-                    temp_path,
-                    temp_dir.as_path(),
-                    &StructuredSequenceWriter::new(matchtigs_backend),
-                    k,
-                );
+                if let Some(graph_cleaning) = graph_cleaning {
+                    let cleaning_backend = MatchtigsStorageBackend::new();
+                    let cleaning_receiver = cleaning_backend.get_receiver();
+
+                    build_maximal_unitigs_links::<
+                        BucketingHash,
+                        MergingHash,
+                        AssemblerColorsManager,
+                        MatchtigsStorageBackend<_>,
+                    >(
+                        temp_path,
+                        temp_dir.as_path(),
+                        &StructuredSequenceWriter::new(cleaning_backend),
+                        k,
+                    );
+
+                    clean_graph::<
+                        BucketingHash,
+                        MergingHash,
+                        AssemblerColorsManager,
+                        MatchtigsStorageBackend<_>,
+                    >(
+                        k,
+                        cleaning_receiver,
+                        &StructuredSequenceWriter::new(matchtigs_backend),
+                        graph_cleaning,
+                    );
+                } else {
+                    build_maximal_unitigs_links::<
+                        BucketingHash,
+                        MergingHash,
+                        AssemblerColorsManager,
+                        MatchtigsStorageBackend<_>,
+                    >(
+                        temp_path,
+                        temp_dir.as_path(),
+                        &StructuredSequenceWriter::new(matchtigs_backend),
+                        k,
+                    );
+                }
 
                 handle.join().unwrap();
             } else if generate_maximal_unitigs_links {
@@ -484,17 +629,98 @@ pub fn run_assembler<
                         Some(ext) => match ext.to_string_lossy().to_string().as_str() {
                             "lz4" => FastaWriter::new_compressed_lz4(&output_file, 2),
                             "gz" => FastaWriter::new_compressed_gzip(&output_file, 2),
+                            "zst" => FastaWriter::new_compressed_zstd(&output_file, 3),
                             _ => FastaWriter::new_plain(&output_file),
                         },
                         None => FastaWriter::new_plain(&output_file),
                     });
 
-                build_maximal_unitigs_links::<
-                    BucketingHash,
-                    MergingHash,
-                    AssemblerColorsManager,
-                    FastaWriter<_, _>,
-                >(temp_path, temp_dir.as_path(), &final_unitigs_file, k);
+                if let Some(graph_cleaning) = graph_cleaning {
+                    let cleaning_backend = MatchtigsStorageBackend::new();
+                    let cleaning_receiver = cleaning_backend.get_receiver();
+
+                    build_maximal_unitigs_links::<
+                        BucketingHash,
+                        MergingHash,
+                        AssemblerColorsManager,
+                        MatchtigsStorageBackend<_>,
+                    >(
+                        temp_path,
+                        temp_dir.as_path(),
+                        &StructuredSequenceWriter::new(cleaning_backend),
+                        k,
+                    );
+
+                    if graph_index {
+                        let index_backend = MatchtigsStorageBackend::new();
+                        let index_receiver = index_backend.get_receiver();
+
+                        clean_graph::<
+                            BucketingHash,
+                            MergingHash,
+                            AssemblerColorsManager,
+                            MatchtigsStorageBackend<_>,
+                        >(
+                            k,
+                            cleaning_receiver,
+                            &StructuredSequenceWriter::new(index_backend),
+                            graph_cleaning,
+                        );
+
+                        write_unitigs_with_index::<
+                            BucketingHash,
+                            MergingHash,
+                            AssemblerColorsManager,
+                            FastaWriter<_, _>,
+                        >(
+                            k,
+                            index_receiver,
+                            &final_unitigs_file,
+                            output_file.with_extension("idx"),
+                        );
+                    } else {
+                        clean_graph::<
+                            BucketingHash,
+                            MergingHash,
+                            AssemblerColorsManager,
+                            FastaWriter<_, _>,
+                        >(k, cleaning_receiver, &final_unitigs_file, graph_cleaning);
+                    }
+                } else if graph_index {
+                    let index_backend = MatchtigsStorageBackend::new();
+                    let index_receiver = index_backend.get_receiver();
+
+                    build_maximal_unitigs_links::<
+                        BucketingHash,
+                        MergingHash,
+                        AssemblerColorsManager,
+                        MatchtigsStorageBackend<_>,
+                    >(
+                        temp_path,
+                        temp_dir.as_path(),
+                        &StructuredSequenceWriter::new(index_backend),
+                        k,
+                    );
+
+                    write_unitigs_with_index::<
+                        BucketingHash,
+                        MergingHash,
+                        AssemblerColorsManager,
+                        FastaWriter<_, _>,
+                    >(
+                        k,
+                        index_receiver,
+                        &final_unitigs_file,
+                        output_file.with_extension("idx"),
+                    );
+                } else {
+                    build_maximal_unitigs_links::<
+                        BucketingHash,
+                        MergingHash,
+                        AssemblerColorsManager,
+                        FastaWriter<_, _>,
+                    >(temp_path, temp_dir.as_path(), &final_unitigs_file, k);
+                }
                 final_unitigs_file.finalize();
             }
         } else {
@@ -506,6 +732,8 @@ pub fn run_assembler<
 
     let _ = std::fs::remove_dir(temp_dir.as_path());
 
+    print_inputs_stats();
+
     PHASES_TIMES_MONITOR
         .write()
         .print_stats("Compacted De Bruijn graph construction completed.".to_string());
@@ -2,7 +2,9 @@ use crate::pipeline::reorganize_reads::ReorganizedReadsExtraData;
 use colors::colors_manager::color_types::PartialUnitigsColorStructure;
 use colors::colors_manager::ColorsMergeManager;
 use colors::colors_manager::{color_types, ColorsManager};
-use config::{DEFAULT_OUTPUT_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT, KEEP_FILES};
+use config::{
+    DEFAULT_OUTPUT_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT, KEEP_FILES, SPLIT_UNITIGS_BY_COLORS,
+};
 use hashbrown::HashMap;
 use hashes::{HashFunctionFactory, HashableSequence, MinimizerHashFunctionFactory};
 use io::compressed_read::CompressedReadIndipendent;
@@ -242,6 +244,11 @@ pub fn build_unitigs<
 
                 let mut final_unitig_color =
                     CX::ColorsMergeManagerType::<H, MH>::alloc_unitig_color_structure();
+                let mut run_unitig_color =
+                    CX::ColorsMergeManagerType::<H, MH>::alloc_unitig_color_structure();
+
+                let split_by_colors =
+                    CX::COLORS_ENABLED && SPLIT_UNITIGS_BY_COLORS.load(Ordering::Relaxed);
 
                 'uloop: for sequence in
                     final_sequences.group_by(|_a, b| !b.as_ref().unwrap().1.is_start)
@@ -323,6 +330,40 @@ pub fn build_unitigs<
                         CX::ColorsMergeManagerType::<H, MH>::pop_base(&mut final_unitig_color);
                     }
 
+                    if split_by_colors {
+                        // Write a separate unitig for each colors subset, consecutive parts overlap by k - 1 bases
+                        let mut offset = 0;
+                        loop {
+                            let run_kmers =
+                                CX::ColorsMergeManagerType::<H, MH>::split_first_colors_run(
+                                    &mut final_unitig_color,
+                                    &mut run_unitig_color,
+                                ) as usize;
+
+                            if run_kmers == 0 {
+                                break;
+                            }
+
+                            let writable_color =
+                                CX::ColorsMergeManagerType::<H, MH>::encode_part_unitigs_colors(
+                                    &mut run_unitig_color,
+                                    &mut final_color_extra_buffer,
+                                );
+
+                            let run_end = (offset + run_kmers + k - 1).min(temp_sequence.len());
+                            tmp_final_unitigs_buffer.add_read(
+                                &temp_sequence[offset..run_end],
+                                None,
+                                writable_color,
+                                &final_color_extra_buffer,
+                                (),
+                                &(),
+                            );
+                            offset += run_kmers;
+                        }
+                        continue;
+                    }
+
                     let writable_color =
                         CX::ColorsMergeManagerType::<H, MH>::encode_part_unitigs_colors(
                             &mut final_unitig_color,
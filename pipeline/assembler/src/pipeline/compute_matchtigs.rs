@@ -1,7 +1,7 @@
 use crate::pipeline::maximal_unitig_links::maximal_unitig_index::DoubleMaximalUnitigLinks;
 use colors::colors_manager::color_types::PartialUnitigsColorStructure;
 use colors::colors_manager::{color_types, ColorsManager, ColorsMergeManager};
-use config::DEFAULT_OUTPUT_BUFFER_SIZE;
+use config::{ColorIndexType, DEFAULT_OUTPUT_BUFFER_SIZE, SPLIT_UNITIGS_BY_COLORS};
 use crossbeam::channel::{Receiver, Sender};
 use genome_graph::bigraph::implementation::node_bigraph_wrapper::NodeBigraphWrapper;
 use genome_graph::bigraph::interface::BidirectedData;
@@ -25,8 +25,10 @@ use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use std::convert::identity;
 use std::ops::Deref;
 use std::path::PathBuf;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use traitgraph_algo::dijkstra::DijkstraWeightedEdgeData;
+use utils::vec_slice::VecSlice;
 
 const DUMMY_EDGE_VALUE: usize = usize::MAX;
 
@@ -133,17 +135,17 @@ impl<ColorInfo: IdentSequenceWriter> DijkstraWeightedEdgeData<usize> for UnitigE
 }
 
 pub struct StructuredUnitigsStorage<ColorInfo: IdentSequenceWriter> {
-    first_sequence_index: usize,
-    sequences: Vec<(
+    pub(crate) first_sequence_index: usize,
+    pub(crate) sequences: Vec<(
         CompressedReadIndipendent,
         ColorInfo,
         DoubleMaximalUnitigLinks,
         bool,
     )>,
 
-    sequences_buffer: Vec<u8>,
-    links_buffer: <DoubleMaximalUnitigLinks as SequenceExtraData>::TempBuffer,
-    color_buffer: ColorInfo::TempBuffer,
+    pub(crate) sequences_buffer: Vec<u8>,
+    pub(crate) links_buffer: <DoubleMaximalUnitigLinks as SequenceExtraData>::TempBuffer,
+    pub(crate) color_buffer: ColorInfo::TempBuffer,
 }
 
 impl<ColorInfo: IdentSequenceWriter> StructuredUnitigsStorage<ColorInfo> {
@@ -291,6 +293,50 @@ impl<ColorInfo: IdentSequenceWriter> GenericNode for UnitigEdgeData<ColorInfo> {
     }
 }
 
+/// Removes the links between unitigs with different colors subsets, so that each tig has a single colors subset
+fn filter_links_by_colors<
+    H: MinimizerHashFunctionFactory,
+    MH: HashFunctionFactory,
+    CX: ColorsManager,
+>(
+    storages: &mut [Arc<StructuredUnitigsStorage<PartialUnitigsColorStructure<H, MH, CX>>>],
+) {
+    let unitigs_count = storages.iter().map(|s| s.sequences.len()).sum();
+    let mut colors_subsets: Vec<Option<ColorIndexType>> = vec![None; unitigs_count];
+
+    for storage in storages.iter() {
+        for (index, (_, color, _, _)) in storage.sequences.iter().enumerate() {
+            colors_subsets[storage.first_sequence_index + index] =
+                CX::ColorsMergeManagerType::<H, MH>::get_single_colors_subset(
+                    color,
+                    &storage.color_buffer,
+                );
+        }
+    }
+
+    for storage in storages.iter_mut() {
+        let storage = Arc::get_mut(storage).unwrap();
+        let mut filtered_links = Vec::with_capacity(storage.links_buffer.len());
+
+        for (index, (_, _, links, _)) in storage.sequences.iter_mut().enumerate() {
+            let colors_subset = colors_subsets[storage.first_sequence_index + index];
+
+            for link in links.0.iter_mut() {
+                link.entries = VecSlice::new_extend_iter(
+                    &mut filtered_links,
+                    link.entries
+                        .get_slice(&storage.links_buffer)
+                        .iter()
+                        .filter(|entry| colors_subsets[entry.index() as usize] == colors_subset)
+                        .copied(),
+                );
+            }
+        }
+
+        storage.links_buffer = filtered_links;
+    }
+}
+
 pub enum MatchtigMode {
     EulerTigs,
     GreedyTigs,
@@ -310,7 +356,14 @@ pub fn compute_matchtigs_thread<
     out_file: &StructuredSequenceWriter<PartialUnitigsColorStructure<H, MH, CX>, (), BK>,
     mode: MatchtigMode,
 ) {
-    let iterator = input_data
+    let mut storages: Vec<_> = input_data.into_iter().collect();
+
+    // With unitigs split by colors, walks are extended only between unitigs sharing the same colors subset
+    if CX::COLORS_ENABLED && SPLIT_UNITIGS_BY_COLORS.load(Ordering::Relaxed) {
+        filter_links_by_colors::<H, MH, CX>(&mut storages);
+    }
+
+    let iterator = storages
         .into_iter()
         .map(|storage| {
             (0..storage.sequences.len())
@@ -0,0 +1,651 @@
+use crate::pipeline::compute_matchtigs::StructuredUnitigsStorage;
+use crate::pipeline::maximal_unitig_links::maximal_unitig_index::{
+    DoubleMaximalUnitigLinks, MaximalUnitigFlags, MaximalUnitigIndex, MaximalUnitigLink,
+};
+use colors::colors_manager::color_types::PartialUnitigsColorStructure;
+use colors::colors_manager::{color_types, ColorsManager, ColorsMergeManager};
+use config::DEFAULT_OUTPUT_BUFFER_SIZE;
+use crossbeam::channel::Receiver;
+use hashes::{HashFunctionFactory, MinimizerHashFunctionFactory};
+use io::concurrent::structured_sequences::concurrent::FastaWriterConcurrentBuffer;
+use io::concurrent::structured_sequences::{StructuredSequenceBackend, StructuredSequenceWriter};
+use io::concurrent::temp_reads::extra_data::SequenceExtraDataTempBufferManagement;
+use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
+use std::sync::Arc;
+use utils::vec_slice::VecSlice;
+
+/// Parameters of the graph cleaning step, done on the maximal unitigs graph
+#[derive(Copy, Clone, Debug)]
+pub struct GraphCleaningConfig {
+    /// Maximum length (in kmers) of a dead-end unitig to be clipped as a tip, 0 disables tip clipping
+    pub tips_max_kmers: usize,
+    /// A tip is clipped only if its mean abundance is lower than this fraction of the best alternative branch
+    pub tips_max_ratio: f64,
+    /// A bubble branch is popped if its mean abundance is lower than this fraction of the best parallel branch,
+    /// 0 disables bubble popping
+    pub bubbles_max_ratio: f64,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+enum UnitigSide {
+    Start = 0,
+    End = 1,
+}
+
+impl UnitigSide {
+    #[inline(always)]
+    fn opposite(self) -> Self {
+        match self {
+            UnitigSide::Start => UnitigSide::End,
+            UnitigSide::End => UnitigSide::Start,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+struct SideRef {
+    unitig: usize,
+    side: UnitigSide,
+}
+
+impl SideRef {
+    #[inline(always)]
+    fn opposite(self) -> Self {
+        Self {
+            unitig: self.unitig,
+            side: self.side.opposite(),
+        }
+    }
+}
+
+struct CleaningUnitig {
+    storage_index: usize,
+    sequence_index: usize,
+    kmers: usize,
+    abundance: f64,
+    // Sides of the other unitigs linked to the start and to the end of this unitig
+    links: [Vec<SideRef>; 2],
+    removed: bool,
+}
+
+struct CleaningGraph {
+    unitigs: Vec<CleaningUnitig>,
+}
+
+impl CleaningGraph {
+    fn active_links(&self, side: SideRef) -> impl Iterator<Item = SideRef> + '_ {
+        self.unitigs[side.unitig].links[side.side as usize]
+            .iter()
+            .copied()
+            .filter(move |link| !self.unitigs[link.unitig].removed)
+    }
+
+    fn degree(&self, side: SideRef) -> usize {
+        self.active_links(side).count()
+    }
+
+    /// Removes the short dead-end unitigs attached to a branching node, with a low abundance compared to the other branches
+    fn clip_tips(&mut self, config: &GraphCleaningConfig) -> usize {
+        let mut tips = vec![];
+
+        for index in 0..self.unitigs.len() {
+            if self.unitigs[index].kmers > config.tips_max_kmers {
+                continue;
+            }
+
+            let start = SideRef {
+                unitig: index,
+                side: UnitigSide::Start,
+            };
+
+            let connected_side = match (self.degree(start), self.degree(start.opposite())) {
+                (0, degree) if degree > 0 => start.opposite(),
+                (degree, 0) if degree > 0 => start,
+                _ => continue,
+            };
+
+            let mut is_branching = true;
+            let mut best_alternative = 0.0f64;
+
+            for neighbor in self.active_links(connected_side) {
+                let mut has_alternatives = false;
+                for alternative in self.active_links(neighbor) {
+                    if alternative.unitig != index {
+                        has_alternatives = true;
+                        best_alternative =
+                            best_alternative.max(self.unitigs[alternative.unitig].abundance);
+                    }
+                }
+                is_branching &= has_alternatives;
+            }
+
+            if is_branching
+                && self.unitigs[index].abundance < best_alternative * config.tips_max_ratio
+            {
+                tips.push(index);
+            }
+        }
+
+        for tip in tips.iter() {
+            self.unitigs[*tip].removed = true;
+        }
+
+        tips.len()
+    }
+
+    /// Pops the low abundance branches of simple bubbles, made of single unitigs sharing both their neighbors
+    fn pop_bubbles(&mut self, config: &GraphCleaningConfig) -> usize {
+        let mut popped_count = 0;
+        let mut branches = vec![];
+
+        for index in 0..self.unitigs.len() {
+            for side in [UnitigSide::Start, UnitigSide::End] {
+                let source = SideRef {
+                    unitig: index,
+                    side,
+                };
+
+                if self.unitigs[index].removed || self.degree(source) < 2 {
+                    continue;
+                }
+
+                branches.clear();
+                for branch in self.active_links(source) {
+                    if branch.unitig == index || self.degree(branch) != 1 {
+                        continue;
+                    }
+
+                    let mut targets = self.active_links(branch.opposite());
+                    if let (Some(target), None) = (targets.next(), targets.next()) {
+                        if target != source {
+                            branches.push((target, branch.unitig));
+                        }
+                    }
+                }
+
+                branches.sort_unstable();
+                branches.dedup();
+
+                for group in branches.group_by(|a, b| a.0 == b.0) {
+                    if group.len() < 2 {
+                        continue;
+                    }
+
+                    let best_abundance = group
+                        .iter()
+                        .map(|(_, branch)| self.unitigs[*branch].abundance)
+                        .fold(0.0, f64::max);
+
+                    for (_, branch) in group {
+                        if self.unitigs[*branch].abundance
+                            < best_abundance * config.bubbles_max_ratio
+                        {
+                            self.unitigs[*branch].removed = true;
+                            popped_count += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        popped_count
+    }
+
+    /// Returns the entry side of the unitig that can be merged after the given exit side, if any
+    fn next_in_chain(&self, exit: SideRef) -> Option<SideRef> {
+        let mut links = self.active_links(exit);
+        let next = links.next()?;
+
+        if links.next().is_some() || next.unitig == exit.unitig || self.degree(next) != 1 {
+            return None;
+        }
+
+        Some(next)
+    }
+
+    /// Builds the graph of the given chains, each one becoming a single unitig with the total kmers
+    /// and the kmers weighted mean abundance of its unitigs
+    fn chains_graph(&self, chains: &[Vec<SideRef>], chain_ids: &[usize]) -> CleaningGraph {
+        let unitigs = chains
+            .iter()
+            .map(|chain| {
+                let kmers = chain
+                    .iter()
+                    .map(|entry| self.unitigs[entry.unitig].kmers)
+                    .sum();
+                let abundance_sum: f64 = chain
+                    .iter()
+                    .map(|entry| {
+                        let unitig = &self.unitigs[entry.unitig];
+                        unitig.abundance * unitig.kmers as f64
+                    })
+                    .sum();
+
+                let outer_sides = [*chain.first().unwrap(), chain.last().unwrap().opposite()];
+
+                CleaningUnitig {
+                    storage_index: 0,
+                    sequence_index: 0,
+                    kmers,
+                    abundance: abundance_sum / kmers.max(1) as f64,
+                    links: outer_sides.map(|outer_side| {
+                        self.active_links(outer_side)
+                            .map(|neighbor| {
+                                let neighbor_chain = chain_ids[neighbor.unitig];
+                                SideRef {
+                                    unitig: neighbor_chain,
+                                    side: if chains[neighbor_chain][0] == neighbor {
+                                        UnitigSide::Start
+                                    } else {
+                                        UnitigSide::End
+                                    },
+                                }
+                            })
+                            .collect()
+                    }),
+                    removed: false,
+                }
+            })
+            .collect();
+
+        CleaningGraph { unitigs }
+    }
+
+    /// Clips the tips and pops the bubbles on the recompacted graph, until no more can be removed.
+    /// Returns the number of removed tips and bubble branches
+    fn clean(&mut self, config: &GraphCleaningConfig) -> (usize, usize) {
+        let mut clipped_tips = 0;
+        let mut popped_bubbles = 0;
+
+        loop {
+            let (chains, chain_ids) = self.build_chains();
+            let mut chains_graph = self.chains_graph(&chains, &chain_ids);
+
+            let tips = chains_graph.clip_tips(config);
+            let bubbles = chains_graph.pop_bubbles(config);
+
+            if tips + bubbles == 0 {
+                break;
+            }
+            clipped_tips += tips;
+            popped_bubbles += bubbles;
+
+            for (chain, chain_unitig) in chains.iter().zip(chains_graph.unitigs.iter()) {
+                if chain_unitig.removed {
+                    for entry in chain {
+                        self.unitigs[entry.unitig].removed = true;
+                    }
+                }
+            }
+        }
+
+        (clipped_tips, popped_bubbles)
+    }
+
+    /// Groups the remaining unitigs in maximal non-branching chains,
+    /// each chain element is the side from which the unitig is entered
+    fn build_chains(&self) -> (Vec<Vec<SideRef>>, Vec<usize>) {
+        let mut chains = vec![];
+        let mut chain_ids = vec![usize::MAX; self.unitigs.len()];
+
+        for index in 0..self.unitigs.len() {
+            if self.unitigs[index].removed || chain_ids[index] != usize::MAX {
+                continue;
+            }
+
+            // Walk backwards to find the first unitig of the chain, stopping if the chain is circular
+            let mut first = SideRef {
+                unitig: index,
+                side: UnitigSide::Start,
+            };
+            while let Some(previous_exit) = self.next_in_chain(first) {
+                if previous_exit.unitig == index {
+                    break;
+                }
+                first = previous_exit.opposite();
+            }
+
+            let chain_id = chains.len();
+            let mut chain = vec![];
+            let mut current = first;
+
+            loop {
+                chain_ids[current.unitig] = chain_id;
+                chain.push(current);
+
+                match self.next_in_chain(current.opposite()) {
+                    Some(next) if chain_ids[next.unitig] == usize::MAX => current = next,
+                    _ => break,
+                }
+            }
+
+            chains.push(chain);
+        }
+
+        (chains, chain_ids)
+    }
+}
+
+/// Clips tips and pops bubbles from the maximal unitigs graph, then writes the recompacted unitigs with updated links
+pub fn clean_graph<
+    H: MinimizerHashFunctionFactory,
+    MH: HashFunctionFactory,
+    CX: ColorsManager,
+    BK: StructuredSequenceBackend<PartialUnitigsColorStructure<H, MH, CX>, DoubleMaximalUnitigLinks>,
+>(
+    k: usize,
+    input_data: Receiver<Arc<StructuredUnitigsStorage<PartialUnitigsColorStructure<H, MH, CX>>>>,
+    out_file: &StructuredSequenceWriter<
+        PartialUnitigsColorStructure<H, MH, CX>,
+        DoubleMaximalUnitigLinks,
+        BK,
+    >,
+    config: GraphCleaningConfig,
+) {
+    PHASES_TIMES_MONITOR
+        .write()
+        .start_phase("phase: graph cleaning".to_string());
+
+    let storages: Vec<_> = input_data.into_iter().collect();
+
+    let unitigs_count = storages.iter().map(|s| s.sequences.len()).sum();
+    let mut unitigs: Vec<Option<CleaningUnitig>> = (0..unitigs_count).map(|_| None).collect();
+
+    for (storage_index, storage) in storages.iter().enumerate() {
+        for (sequence_index, (sequence, color, links, _)) in storage.sequences.iter().enumerate() {
+            let mut unitig_links = [vec![], vec![]];
+
+            for link in links.0.iter() {
+                for entry in link.entries.get_slice(&storage.links_buffer) {
+                    let from_side = if entry.flags.flip_current() {
+                        UnitigSide::Start
+                    } else {
+                        UnitigSide::End
+                    };
+                    unitig_links[from_side as usize].push(SideRef {
+                        unitig: entry.index() as usize,
+                        side: if entry.flags.flip_other() {
+                            UnitigSide::End
+                        } else {
+                            UnitigSide::Start
+                        },
+                    });
+                }
+            }
+
+            unitigs[storage.first_sequence_index + sequence_index] = Some(CleaningUnitig {
+                storage_index,
+                sequence_index,
+                kmers: sequence.bases_count() + 1 - k,
+                abundance: CX::ColorsMergeManagerType::<H, MH>::get_unitig_abundance(color)
+                    .mean(),
+                links: unitig_links,
+                removed: false,
+            });
+        }
+    }
+
+    let mut graph = CleaningGraph {
+        unitigs: unitigs.into_iter().map(|u| u.unwrap()).collect(),
+    };
+
+    let (clipped_tips, popped_bubbles) = graph.clean(&config);
+
+    let (chains, chain_ids) = graph.build_chains();
+
+    println!(
+        "Graph cleaning: clipped {} tips and popped {} bubble branches, {} unitigs recompacted into {}",
+        clipped_tips,
+        popped_bubbles,
+        unitigs_count,
+        chains.len()
+    );
+
+    let mut output_buffer =
+        FastaWriterConcurrentBuffer::new(&out_file, DEFAULT_OUTPUT_BUFFER_SIZE, true);
+
+    let mut read_buffer = Vec::new();
+    let mut links_buffer = Vec::new();
+
+    let mut final_unitig_color =
+        color_types::ColorsMergeManagerType::<H, MH, CX>::alloc_unitig_color_structure();
+    let mut final_color_extra_buffer =
+        color_types::PartialUnitigsColorStructure::<H, MH, CX>::new_temp_buffer();
+
+    for (chain_id, chain) in chains.iter().enumerate() {
+        color_types::ColorsMergeManagerType::<H, MH, CX>::reset_unitig_color_structure(
+            &mut final_unitig_color,
+        );
+        color_types::PartialUnitigsColorStructure::<H, MH, CX>::clear_temp_buffer(
+            &mut final_color_extra_buffer,
+        );
+        read_buffer.clear();
+        links_buffer.clear();
+
+        for (position, entry) in chain.iter().enumerate() {
+            let unitig = &graph.unitigs[entry.unitig];
+            let storage = &storages[unitig.storage_index];
+            let (sequence, color, _, _) = &storage.sequences[unitig.sequence_index];
+            let sequence = sequence.as_reference(&storage.sequences_buffer);
+
+            // Consecutive unitigs overlap by k - 1 bases
+            let offset = if position == 0 { 0 } else { k - 1 };
+
+            if entry.side == UnitigSide::Start {
+                read_buffer.extend(sequence.as_bases_iter().skip(offset));
+                CX::ColorsMergeManagerType::<H, MH>::join_structures::<false>(
+                    &mut final_unitig_color,
+                    color,
+                    &storage.color_buffer,
+                    0,
+                );
+            } else {
+                read_buffer.extend(sequence.as_reverse_complement_bases_iter().skip(offset));
+                CX::ColorsMergeManagerType::<H, MH>::join_structures::<true>(
+                    &mut final_unitig_color,
+                    color,
+                    &storage.color_buffer,
+                    0,
+                );
+            }
+        }
+
+        let chain_start = *chain.first().unwrap();
+        let chain_end = chain.last().unwrap().opposite();
+
+        for (new_side, outer_side) in [
+            (UnitigSide::Start, chain_start),
+            (UnitigSide::End, chain_end),
+        ] {
+            for neighbor in graph.active_links(outer_side) {
+                let neighbor_chain = chain_ids[neighbor.unitig];
+                let neighbor_side = if chains[neighbor_chain][0] == neighbor {
+                    UnitigSide::Start
+                } else {
+                    UnitigSide::End
+                };
+
+                links_buffer.push(MaximalUnitigIndex::new(
+                    neighbor_chain as u64,
+                    MaximalUnitigFlags::new_direction(
+                        new_side == UnitigSide::Start,
+                        neighbor_side == UnitigSide::End,
+                    ),
+                ));
+            }
+        }
+
+        let links = DoubleMaximalUnitigLinks([
+            MaximalUnitigLink::new(chain_id as u64, VecSlice::new(0, links_buffer.len())),
+            MaximalUnitigLink::new(chain_id as u64, VecSlice::new(0, 0)),
+        ]);
+
+        let writable_color =
+            color_types::ColorsMergeManagerType::<H, MH, CX>::encode_part_unitigs_colors(
+                &mut final_unitig_color,
+                &mut final_color_extra_buffer,
+            );
+
+        output_buffer.add_read(
+            &read_buffer,
+            None,
+            writable_color,
+            &final_color_extra_buffer,
+            links,
+            &links_buffer,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::graph_cleaning::{
+        CleaningGraph, CleaningUnitig, GraphCleaningConfig, SideRef, UnitigSide,
+    };
+
+    const START: UnitigSide = UnitigSide::Start;
+    const END: UnitigSide = UnitigSide::End;
+
+    const CONFIG: GraphCleaningConfig = GraphCleaningConfig {
+        tips_max_kmers: 10,
+        tips_max_ratio: 0.5,
+        bubbles_max_ratio: 0.2,
+    };
+
+    type Link = ((usize, UnitigSide), (usize, UnitigSide));
+
+    /// Builds a graph from the (kmers, abundance) of each unitig and the links between their sides
+    fn build_graph(unitigs: &[(usize, f64)], links: &[Link]) -> CleaningGraph {
+        let mut graph = CleaningGraph {
+            unitigs: unitigs
+                .iter()
+                .map(|&(kmers, abundance)| CleaningUnitig {
+                    storage_index: 0,
+                    sequence_index: 0,
+                    kmers,
+                    abundance,
+                    links: [vec![], vec![]],
+                    removed: false,
+                })
+                .collect(),
+        };
+
+        for &((unitig, side), (other, other_side)) in links {
+            graph.unitigs[unitig].links[side as usize].push(SideRef {
+                unitig: other,
+                side: other_side,
+            });
+            if (unitig, side) != (other, other_side) {
+                graph.unitigs[other].links[other_side as usize].push(SideRef { unitig, side });
+            }
+        }
+
+        graph
+    }
+
+    fn removed(graph: &CleaningGraph) -> Vec<usize> {
+        (0..graph.unitigs.len())
+            .filter(|index| graph.unitigs[*index].removed)
+            .collect()
+    }
+
+    #[test]
+    fn tips_clipping() {
+        // 0 -> 1 main path, with the tips 2 (short, low abundance), 3 (long) and 4 (abundant) on the end of 0
+        let mut graph = build_graph(
+            &[(100, 20.0), (100, 20.0), (5, 2.0), (50, 2.0), (5, 15.0)],
+            &[
+                ((0, END), (1, START)),
+                ((0, END), (2, START)),
+                ((0, END), (3, START)),
+                ((4, END), (0, END)),
+            ],
+        );
+
+        assert_eq!(graph.clip_tips(&CONFIG), 1);
+        assert_eq!(removed(&graph), [2]);
+    }
+
+    #[test]
+    fn bubbles_popping() {
+        // 0 -> (1 | 2 | 3) -> 4, with 2 reverse complemented and 3 with a low abundance
+        let mut graph = build_graph(
+            &[(100, 20.0), (31, 18.0), (31, 15.0), (31, 3.0), (100, 20.0)],
+            &[
+                ((0, END), (1, START)),
+                ((0, END), (2, END)),
+                ((0, END), (3, START)),
+                ((1, END), (4, START)),
+                ((2, START), (4, START)),
+                ((3, END), (4, START)),
+            ],
+        );
+
+        assert_eq!(graph.pop_bubbles(&CONFIG), 1);
+        assert_eq!(removed(&graph), [3]);
+    }
+
+    #[test]
+    fn palindromic_links() {
+        // 1 is linked to its own reverse complement, 2 is a short tip looping on itself
+        let mut graph = build_graph(
+            &[(100, 20.0), (5, 1.0), (5, 1.0), (100, 20.0)],
+            &[
+                ((0, END), (1, START)),
+                ((1, END), (1, END)),
+                ((0, END), (3, START)),
+                ((0, START), (2, START)),
+                ((2, END), (2, END)),
+            ],
+        );
+
+        assert_eq!(graph.clip_tips(&CONFIG), 0);
+        assert_eq!(graph.pop_bubbles(&CONFIG), 0);
+
+        // 2 is joined only to 0, that has two links on the other side
+        let (chains, _) = graph.build_chains();
+        assert_eq!(chains.len(), 3);
+    }
+
+    #[test]
+    fn iterative_cleaning() {
+        // 0 -> 1 main path, with the dead end 2 -> (3 | 4) -> 5 attached to the end of 0:
+        // popping the bubble branch 4 recompacts the dead end in a single tip
+        let mut graph = build_graph(
+            &[
+                (100, 20.0),
+                (100, 20.0),
+                (2, 2.0),
+                (3, 2.0),
+                (3, 0.2),
+                (2, 2.0),
+            ],
+            &[
+                ((0, END), (1, START)),
+                ((0, END), (2, START)),
+                ((2, END), (3, START)),
+                ((2, END), (4, START)),
+                ((3, END), (5, START)),
+                ((4, END), (5, START)),
+            ],
+        );
+
+        assert_eq!(graph.clean(&CONFIG), (1, 1));
+        assert_eq!(removed(&graph), [2, 3, 4, 5]);
+
+        let (chains, _) = graph.build_chains();
+        assert_eq!(
+            chains,
+            [vec![
+                SideRef {
+                    unitig: 0,
+                    side: START
+                },
+                SideRef {
+                    unitig: 1,
+                    side: START
+                }
+            ]]
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use crate::pipeline::compute_matchtigs::StructuredUnitigsStorage;
+use crate::pipeline::maximal_unitig_links::maximal_unitig_index::DoubleMaximalUnitigLinks;
+use colors::colors_manager::color_types::PartialUnitigsColorStructure;
+use colors::colors_manager::{ColorsManager, ColorsMergeManager};
+use config::DEFAULT_OUTPUT_BUFFER_SIZE;
+use crossbeam::channel::Receiver;
+use hashes::{HashFunctionFactory, MinimizerHashFunctionFactory};
+use io::concurrent::structured_sequences::concurrent::FastaWriterConcurrentBuffer;
+use io::concurrent::structured_sequences::{StructuredSequenceBackend, StructuredSequenceWriter};
+use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
+use std::path::Path;
+use std::sync::Arc;
+use unitig_graph::builder::UnitigGraphBuilder;
+use unitig_graph::{ColorsRun, Strand, UnitigLink};
+
+/// Writes the maximal unitigs to the output file in id order, along with a binary adjacency index
+/// of the graph that can be loaded with the unitig_graph crate
+pub fn write_unitigs_with_index<
+    H: MinimizerHashFunctionFactory,
+    MH: HashFunctionFactory,
+    CX: ColorsManager,
+    BK: StructuredSequenceBackend<PartialUnitigsColorStructure<H, MH, CX>, DoubleMaximalUnitigLinks>,
+>(
+    k: usize,
+    input_data: Receiver<Arc<StructuredUnitigsStorage<PartialUnitigsColorStructure<H, MH, CX>>>>,
+    out_file: &StructuredSequenceWriter<
+        PartialUnitigsColorStructure<H, MH, CX>,
+        DoubleMaximalUnitigLinks,
+        BK,
+    >,
+    index_path: impl AsRef<Path>,
+) {
+    PHASES_TIMES_MONITOR
+        .write()
+        .start_phase("phase: graph index building".to_string());
+
+    // The storages arrive in any order once the links of all the unitigs are built,
+    // so the whole graph is held in memory until it is written sorted by id
+    let mut storages: Vec<_> = input_data.into_iter().collect();
+    storages.sort_unstable_by_key(|s| s.first_sequence_index);
+
+    let mut output_buffer =
+        FastaWriterConcurrentBuffer::new(&out_file, DEFAULT_OUTPUT_BUFFER_SIZE, true);
+    let mut index_builder = UnitigGraphBuilder::new(k);
+
+    let mut read_buffer = Vec::new();
+    let mut colors_runs = Vec::new();
+
+    for storage in storages.iter() {
+        for (sequence, color, links, _) in storage.sequences.iter() {
+            read_buffer.clear();
+            read_buffer.extend(
+                sequence
+                    .as_reference(&storage.sequences_buffer)
+                    .as_bases_iter(),
+            );
+
+            colors_runs.clear();
+            CX::ColorsMergeManagerType::<H, MH>::get_colors_runs(
+                color,
+                &storage.color_buffer,
+                &mut colors_runs,
+            );
+
+            index_builder.add_unitig(
+                &read_buffer,
+                links
+                    .0
+                    .iter()
+                    .flat_map(|link| link.entries.get_slice(&storage.links_buffer))
+                    .map(|entry| UnitigLink {
+                        from: if entry.flags.flip_current() {
+                            Strand::Reverse
+                        } else {
+                            Strand::Forward
+                        },
+                        to: entry.index(),
+                        to_strand: if entry.flags.flip_other() {
+                            Strand::Reverse
+                        } else {
+                            Strand::Forward
+                        },
+                    }),
+                colors_runs.iter().map(|(subset, kmers)| ColorsRun {
+                    subset: *subset as u64,
+                    kmers: *kmers,
+                }),
+            );
+
+            output_buffer.add_read(
+                &read_buffer,
+                None,
+                color.clone(),
+                &storage.color_buffer,
+                links.clone(),
+                &storage.links_buffer,
+            );
+        }
+    }
+
+    drop(output_buffer);
+
+    index_builder.write(&index_path).unwrap_or_else(|err| {
+        println!(
+            "ERROR: Cannot write the graph index {}: {}",
+            index_path.as_ref().display(),
+            err
+        );
+        std::process::exit(1);
+    });
+
+    println!(
+        "Graph index with {} unitigs saved to: {}",
+        storages.iter().map(|s| s.sequences.len()).sum::<usize>(),
+        index_path.as_ref().display()
+    );
+}
@@ -81,7 +81,7 @@ pub fn build_maximal_unitigs_links<
                     &(
                         get_memory_mode(SwapPriority::HashBuckets),
                         CompressedBinaryWriter::CHECKPOINT_SIZE_UNLIMITED,
-                        get_compression_level_info(),
+                        get_compression_level_info(SwapPriority::HashBuckets),
                     ),
                 ));
 
@@ -221,7 +221,7 @@ pub fn build_maximal_unitigs_links<
             &(
                 get_memory_mode(SwapPriority::LinksBuckets),
                 CompressedBinaryWriter::CHECKPOINT_SIZE_UNLIMITED,
-                get_compression_level_info(),
+                get_compression_level_info(SwapPriority::LinksBuckets),
             ),
         ));
 
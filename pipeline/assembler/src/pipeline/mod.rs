@@ -1,5 +1,7 @@
 pub mod build_unitigs;
 pub mod compute_matchtigs;
+pub mod graph_cleaning;
+pub mod graph_index;
 pub mod hashes_sorting;
 pub mod links_compaction;
 pub mod maximal_unitig_links;
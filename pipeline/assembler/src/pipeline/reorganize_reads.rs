@@ -128,7 +128,7 @@ pub fn reorganize_reads<
         &(
             get_memory_mode(SwapPriority::ReorganizeReads),
             CompressedBinaryWriter::CHECKPOINT_SIZE_UNLIMITED,
-            get_compression_level_info(),
+            get_compression_level_info(SwapPriority::ReorganizeReads),
         ),
     ));
 
@@ -0,0 +1,336 @@
+use byteorder::ReadBytesExt;
+use config::{TRACK_UNITIGS_ABUNDANCE, UNITIGS_ABUNDANCE_FULL_TAGS, UNITIGS_ABUNDANCE_TAGS};
+use io::concurrent::structured_sequences::IdentSequenceWriter;
+use io::concurrent::temp_reads::extra_data::SequenceExtraData;
+use io::varint::{decode_varint, encode_varint, VARINT_MAX_SIZE};
+use std::cmp::{max, min};
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::sync::atomic::Ordering;
+
+/// Quantizes the count of a kmer in a color using logarithmic bins, bin b holds the counts in [2^(b-1), 2^b)
+#[inline(always)]
+pub fn quantize_color_abundance(count: u64) -> u8 {
+    (u64::BITS - count.leading_zeros()) as u8
+}
+
+/// Returns the midpoint of the counts range of a quantized abundance bin
+#[inline(always)]
+pub fn dequantize_color_abundance(bin: u8) -> f64 {
+    if bin == 0 {
+        0.0
+    } else {
+        let low = 1u64 << (bin - 1);
+        (low + (2 * low - 1)) as f64 / 2.0
+    }
+}
+
+/// Kmer abundances of a (partial) unitig, used to write BCALM2 like km:f: / KC:i: tags.
+/// The abundances are kept exactly, as (abundance, kmers count) runs, so that the tags do not change
+/// when the unitigs are joined or split
+#[derive(Clone, Debug, Default)]
+pub struct UnitigAbundance {
+    runs: VecDeque<(u64, u64)>,
+}
+
+impl UnitigAbundance {
+    /// True if the abundances are tracked, either to write the tags or for internal use
+    #[inline(always)]
+    pub fn is_enabled() -> bool {
+        UNITIGS_ABUNDANCE_TAGS.load(Ordering::Relaxed)
+            || TRACK_UNITIGS_ABUNDANCE.load(Ordering::Relaxed)
+    }
+
+    #[inline(always)]
+    pub fn clear(&mut self) {
+        self.runs.clear();
+    }
+
+    #[inline(always)]
+    pub fn extend_forward(&mut self, abundance: u64) {
+        match self.runs.back_mut() {
+            Some(run) if run.0 == abundance => run.1 += 1,
+            _ => self.runs.push_back((abundance, 1)),
+        }
+    }
+
+    #[inline(always)]
+    pub fn extend_backward(&mut self, abundance: u64) {
+        match self.runs.front_mut() {
+            Some(run) if run.0 == abundance => run.1 += 1,
+            _ => self.runs.push_front((abundance, 1)),
+        }
+    }
+
+    /// Appends the kmers of another unitig, skipping its first `skip` kmers (after the optional reversal)
+    pub fn join<const REVERSE: bool>(&mut self, other: &Self, mut skip: u64) {
+        let mut append_run = |&(abundance, kmers): &(u64, u64)| {
+            if kmers <= skip {
+                skip -= kmers;
+                return;
+            }
+
+            match self.runs.back_mut() {
+                Some(run) if run.0 == abundance => run.1 += kmers - skip,
+                _ => self.runs.push_back((abundance, kmers - skip)),
+            }
+            skip = 0;
+        };
+
+        if REVERSE {
+            other.runs.iter().rev().for_each(&mut append_run);
+        } else {
+            other.runs.iter().for_each(&mut append_run);
+        }
+    }
+
+    /// Returns the kmers count, the total abundance and the minimum and maximum kmer abundances
+    fn stats(&self) -> (u64, u64, u64, u64) {
+        self.runs.iter().fold(
+            (0, 0, u64::MAX, 0),
+            |(kmers, sum, min_abundance, max_abundance), &(abundance, count)| {
+                (
+                    kmers + count,
+                    sum + abundance * count,
+                    min(min_abundance, abundance),
+                    max(max_abundance, abundance),
+                )
+            },
+        )
+    }
+
+    /// Mean kmer abundance, 0 if the unitig has no kmers
+    #[inline(always)]
+    pub fn mean(&self) -> f64 {
+        let (kmers, sum, _, _) = self.stats();
+        if kmers > 0 {
+            sum as f64 / kmers as f64
+        } else {
+            0.0
+        }
+    }
+
+    /// Splits the first `kmers` kmers from the unitig, returning their abundances
+    pub fn split_front(&mut self, mut kmers: u64) -> Self {
+        let mut front = Self::default();
+
+        while let Some(run) = self.runs.front_mut() {
+            if kmers == 0 {
+                break;
+            }
+
+            if run.1 <= kmers {
+                kmers -= run.1;
+                front.runs.push_back(*run);
+                self.runs.pop_front();
+            } else {
+                run.1 -= kmers;
+                front.runs.push_back((run.0, kmers));
+                kmers = 0;
+            }
+        }
+
+        front
+    }
+
+    /// Removes the last kmer
+    #[inline(always)]
+    pub fn pop_kmer(&mut self) {
+        if let Some(run) = self.runs.back_mut() {
+            run.1 -= 1;
+            if run.1 == 0 {
+                self.runs.pop_back();
+            }
+        }
+    }
+
+    pub fn decode(reader: &mut impl Read) -> Option<Self> {
+        if !Self::is_enabled() {
+            return Some(Self::default());
+        }
+
+        let runs_count = decode_varint(|| reader.read_u8().ok())?;
+        let mut runs = VecDeque::with_capacity(runs_count as usize);
+        for _ in 0..runs_count {
+            runs.push_back((
+                decode_varint(|| reader.read_u8().ok())?,
+                decode_varint(|| reader.read_u8().ok())?,
+            ));
+        }
+
+        Some(Self { runs })
+    }
+
+    pub fn encode(&self, writer: &mut impl Write) {
+        if !Self::is_enabled() {
+            return;
+        }
+
+        encode_varint(|b| writer.write_all(b), self.runs.len() as u64).unwrap();
+        for &(abundance, kmers) in &self.runs {
+            encode_varint(|b| writer.write_all(b), abundance).unwrap();
+            encode_varint(|b| writer.write_all(b), kmers).unwrap();
+        }
+    }
+
+    #[inline(always)]
+    pub fn encoded_max_size(&self) -> usize {
+        if Self::is_enabled() {
+            (2 * self.runs.len() + 1) * VARINT_MAX_SIZE
+        } else {
+            0
+        }
+    }
+
+    /// Writes the abundance tags, km:f: is the mean kmer abundance and KC:i: the total kmers count
+    pub fn write_tags(&self, stream: &mut impl Write) {
+        if !UNITIGS_ABUNDANCE_TAGS.load(Ordering::Relaxed) {
+            return;
+        }
+
+        self.write_tags_values(stream, UNITIGS_ABUNDANCE_FULL_TAGS.load(Ordering::Relaxed));
+    }
+
+    fn write_tags_values(&self, stream: &mut impl Write, full_tags: bool) {
+        let (kmers, sum, min_abundance, max_abundance) = self.stats();
+
+        write!(stream, " km:f:{:.1}", self.mean()).unwrap();
+
+        if full_tags {
+            if kmers == 0 {
+                write!(stream, " KC:i:0 mn:i:0 mx:i:0").unwrap();
+            } else {
+                write!(
+                    stream,
+                    " KC:i:{} mn:i:{} mx:i:{}",
+                    sum, min_abundance, max_abundance
+                )
+                .unwrap();
+            }
+        }
+    }
+}
+
+impl SequenceExtraData for UnitigAbundance {
+    type TempBuffer = ();
+
+    #[inline(always)]
+    fn decode_extended(_: &mut (), reader: &mut impl Read) -> Option<Self> {
+        Self::decode(reader)
+    }
+
+    #[inline(always)]
+    fn encode_extended(&self, _: &(), writer: &mut impl Write) {
+        self.encode(writer)
+    }
+
+    #[inline(always)]
+    fn max_size(&self) -> usize {
+        self.encoded_max_size()
+    }
+}
+
+impl IdentSequenceWriter for UnitigAbundance {
+    fn write_as_ident(&self, stream: &mut impl Write, _extra_buffer: &Self::TempBuffer) {
+        self.write_tags(stream)
+    }
+
+    fn write_as_gfa(&self, _stream: &mut impl Write, _extra_buffer: &Self::TempBuffer) {}
+
+    fn parse_as_ident<'a>(_ident: &[u8], _extra_buffer: &mut Self::TempBuffer) -> Option<Self> {
+        Some(Self::default())
+    }
+
+    fn parse_as_gfa<'a>(_ident: &[u8], _extra_buffer: &mut Self::TempBuffer) -> Option<Self> {
+        Some(Self::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::abundance::UnitigAbundance;
+
+    fn expected_tags(abundances: &[u64]) -> String {
+        format!(
+            " km:f:{:.1} KC:i:{} mn:i:{} mx:i:{}",
+            abundances.iter().sum::<u64>() as f64 / abundances.len() as f64,
+            abundances.iter().sum::<u64>(),
+            abundances.iter().min().unwrap(),
+            abundances.iter().max().unwrap()
+        )
+    }
+
+    fn tags(abundance: &UnitigAbundance) -> String {
+        let mut tags = vec![];
+        abundance.write_tags_values(&mut tags, true);
+        String::from_utf8(tags).unwrap()
+    }
+
+    #[test]
+    fn unitig_abundance_tags_are_exact() {
+        let mut seed = 12345u64;
+        let abundances: Vec<u64> = (0..200)
+            .map(|_| {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                1 + (seed >> 60) % 5
+            })
+            .collect();
+
+        // Partial unitigs sharing their boundary kmer, the odd ones stored reverse complemented
+        let parts: Vec<_> = [0..37, 36..90, 89..91, 90..200]
+            .into_iter()
+            .enumerate()
+            .map(|(index, range)| {
+                let mut part = UnitigAbundance::default();
+                if index % 2 == 0 {
+                    abundances[range.clone()]
+                        .iter()
+                        .for_each(|a| part.extend_forward(*a));
+                } else {
+                    abundances[range.clone()]
+                        .iter()
+                        .for_each(|a| part.extend_backward(*a));
+                }
+                (range, part)
+            })
+            .collect();
+
+        let mut unitig = UnitigAbundance::default();
+        for (index, (_, part)) in parts.iter().enumerate() {
+            let skip = if index == 0 { 0 } else { 1 };
+            if index % 2 == 0 {
+                unitig.join::<false>(part, skip);
+            } else {
+                unitig.join::<true>(part, skip);
+            }
+        }
+        assert_eq!(tags(&unitig), expected_tags(&abundances));
+
+        // Joining skipping more than one kmer
+        let mut joined = UnitigAbundance::default();
+        joined.join::<false>(&parts[0].1, 0);
+        joined.join::<false>(&parts[2].1, 0);
+        joined.join::<true>(&parts[3].1, 25);
+        let mut expected = abundances[0..37].to_vec();
+        expected.extend_from_slice(&abundances[89..91]);
+        expected.extend_from_slice(&abundances[115..200]);
+        assert_eq!(tags(&joined), expected_tags(&expected));
+
+        // Removing the last kmer
+        let mut popped = unitig.clone();
+        popped.pop_kmer();
+        assert_eq!(tags(&popped), expected_tags(&abundances[..199]));
+
+        // Splitting in parts
+        let mut start = 0;
+        for length in [1, 13, 50, 7, 129] {
+            let front = unitig.split_front(length);
+            assert_eq!(
+                tags(&front),
+                expected_tags(&abundances[start..start + length as usize])
+            );
+            start += length as usize;
+        }
+        assert_eq!(tags(&unitig), " km:f:0.0 KC:i:0 mn:i:0 mx:i:0");
+    }
+}
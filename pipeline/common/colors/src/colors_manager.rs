@@ -1,3 +1,4 @@
+use crate::abundance::UnitigAbundance;
 use crate::parsers::SingleSequenceInfo;
 use config::{BucketIndexType, ColorIndexType};
 use hashbrown::HashMap;
@@ -68,6 +69,7 @@ pub trait ColorMapReader {
     fn get_color_name(&self, index: ColorIndexType, json_escaped: bool) -> &str;
     fn colors_count(&self) -> usize;
     fn colors_subsets_count(&self) -> u64;
+    fn has_abundances(&self) -> bool;
 }
 
 impl ColorMapReader for () {
@@ -82,6 +84,10 @@ impl ColorMapReader for () {
     fn colors_subsets_count(&self) -> u64 {
         0
     }
+
+    fn has_abundances(&self) -> bool {
+        false
+    }
 }
 
 /// Helper trait to manage colors parsing from different sources (actually 2, color from file or color from annotated dbg graph)
@@ -181,6 +187,14 @@ pub trait ColorsMergeManager<H: MinimizerHashFunctionFactory, MH: HashFunctionFa
         entry: &MapEntry<Self::HashMapTempColorIndex>,
     );
 
+    /// Returns the multiplicity of a kmer, also after its colors have been processed
+    fn get_kmer_multiplicity(entry: &MapEntry<Self::HashMapTempColorIndex>) -> u64;
+
+    /// Returns the colors subset of a kmer after its colors have been processed, None if colors are not enabled
+    fn get_kmer_colors_subset(
+        entry: &MapEntry<Self::HashMapTempColorIndex>,
+    ) -> Option<ColorIndexType>;
+
     fn join_structures<const REVERSE: bool>(
         dest: &mut Self::TempUnitigColorStructure,
         src: &Self::PartialUnitigsColorStructure,
@@ -190,6 +204,29 @@ pub trait ColorsMergeManager<H: MinimizerHashFunctionFactory, MH: HashFunctionFa
 
     fn pop_base(target: &mut Self::TempUnitigColorStructure);
 
+    /// Moves the first run of kmers sharing the same colors subset from `ts` to `run`, returning its length in kmers
+    /// (0 if `ts` is empty). Used to split the unitigs at every colors subset change
+    fn split_first_colors_run(
+        ts: &mut Self::TempUnitigColorStructure,
+        run: &mut Self::TempUnitigColorStructure,
+    ) -> u64;
+
+    /// Returns the colors subset of a partial unitig, if all its kmers share the same one
+    fn get_single_colors_subset(
+        color: &Self::PartialUnitigsColorStructure,
+        colors_buffer: &<Self::PartialUnitigsColorStructure as SequenceExtraData>::TempBuffer,
+    ) -> Option<ColorIndexType>;
+
+    /// Appends the colors runs of a partial unitig to `runs`, as (colors subset, kmers count) pairs
+    fn get_colors_runs(
+        color: &Self::PartialUnitigsColorStructure,
+        colors_buffer: &<Self::PartialUnitigsColorStructure as SequenceExtraData>::TempBuffer,
+        runs: &mut Vec<(ColorIndexType, u64)>,
+    );
+
+    /// Returns the kmer abundance statistics of a partial unitig, empty if abundances are not tracked
+    fn get_unitig_abundance(color: &Self::PartialUnitigsColorStructure) -> UnitigAbundance;
+
     /// Encodes partial unitig colors into the extra data structure
     fn encode_part_unitigs_colors(
         ts: &mut Self::TempUnitigColorStructure,
@@ -16,11 +16,11 @@ pub struct ColorsMemMapWriter<C: ColorsSerializerTrait> {
 }
 
 impl<C: ColorsSerializerTrait> ColorsMemMapWriter<C> {
-    pub fn new(file: impl AsRef<Path>, color_names: Vec<String>) -> Self {
+    pub fn new(file: impl AsRef<Path>, color_names: Vec<String>, with_abundances: bool) -> Self {
         let mut rng = thread_rng();
         Self {
             colors: DashMap::with_hasher(DummyHasherBuilder),
-            colors_storage: ColorsSerializer::new(file, color_names),
+            colors_storage: ColorsSerializer::new(file, color_names, with_abundances),
             hash_keys: (rng.next_u64(), rng.next_u64()),
         }
     }
@@ -44,6 +44,31 @@ impl<C: ColorsSerializerTrait> ColorsMemMapWriter<C> {
         }
     }
 
+    /// Returns the id of a colors subset where each color has the given quantized abundance
+    pub fn get_id_with_abundances(
+        &self,
+        colors: &[ColorIndexType],
+        abundances: &[u8],
+    ) -> ColorIndexType {
+        let hash = {
+            let mut hasher = SipHasher13::new_with_keys(self.hash_keys.0, self.hash_keys.1);
+            colors.hash(&mut hasher);
+            abundances.hash(&mut hasher);
+            hasher.finish128().as_u128()
+        };
+
+        match self.colors.get(&hash) {
+            None => {
+                let color = self
+                    .colors_storage
+                    .serialize_colors_with_abundances(colors, abundances);
+                self.colors.insert(hash, color);
+                color
+            }
+            Some(id) => *id,
+        }
+    }
+
     pub fn print_stats(&self) {
         self.colors_storage.print_stats();
     }
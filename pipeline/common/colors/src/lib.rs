@@ -5,9 +5,11 @@
 #![feature(int_roundings)]
 #![feature(let_chains)]
 #![feature(int_log)]
+#![feature(slice_group_by)]
 
 use crate::storage::run_length::RunLengthColorsSerializer;
 
+pub mod abundance;
 pub mod bundles;
 pub mod colors_manager;
 pub mod colors_memmap_writer;
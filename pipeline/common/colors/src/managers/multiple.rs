@@ -1,10 +1,11 @@
+use crate::abundance::{quantize_color_abundance, UnitigAbundance};
 use crate::colors_manager::ColorsMergeManager;
 use crate::colors_memmap_writer::ColorsMemMapWriter;
 use crate::DefaultColorsSerializer;
 use byteorder::ReadBytesExt;
 use config::{
     get_compression_level_info, get_memory_mode, ColorIndexType, MinimizerType, SwapPriority,
-    PARTIAL_VECS_CHECKPOINT_SIZE, READ_FLAG_INCL_BEGIN, READ_FLAG_INCL_END,
+    COLORS_ABUNDANCES, PARTIAL_VECS_CHECKPOINT_SIZE, READ_FLAG_INCL_BEGIN, READ_FLAG_INCL_END,
 };
 use hashbrown::HashMap;
 use hashes::ExtendableHashTraitType;
@@ -90,7 +91,7 @@ impl SequencesStorage {
                     &(
                         get_memory_mode(SwapPriority::KmersMergeTempColors),
                         PARTIAL_VECS_CHECKPOINT_SIZE,
-                        get_compression_level_info(),
+                        get_compression_level_info(SwapPriority::KmersMergeTempColors),
                     ),
                     COLOR_STORAGE_INDEX.fetch_add(1, Ordering::Relaxed),
                 ))
@@ -108,11 +109,21 @@ pub struct MultipleColorsManager<H: MinimizerHashFunctionFactory, MH: HashFuncti
     kmers_count: usize,
     sequences_count: usize,
     temp_colors_buffer: Vec<ColorIndexType>,
+    temp_abundances_buffer: Vec<u8>,
     temp_dir: PathBuf,
     _phantom: PhantomData<(H, MH)>,
 }
 
 const VISITED_BIT: usize = 1 << (COUNTER_BITS - 1);
+// After processing, the entry counter holds the kmer color in the low bits and its multiplicity in the high bits
+const MULTIPLICITY_SHIFT: usize = size_of::<ColorIndexType>() * 8;
+const MULTIPLICITY_MAX: usize = (1 << (COUNTER_BITS - 1 - MULTIPLICITY_SHIFT)) - 1;
+const COLOR_MASK: usize = (1 << MULTIPLICITY_SHIFT) - 1;
+
+#[inline(always)]
+fn get_entry_multiplicity(entry: &MapEntry<()>) -> u64 {
+    ((entry.get_counter() & !VISITED_BIT) >> MULTIPLICITY_SHIFT) as u64
+}
 const TEMP_BUFFER_START_SIZE: usize = 1024 * 64;
 const READS_BUFFERS_MAX_CAPACITY: usize = 1024 * 32;
 
@@ -127,7 +138,11 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
         path: impl AsRef<Path>,
         color_names: Vec<String>,
     ) -> Self::GlobalColorsTableWriter {
-        ColorsMemMapWriter::new(path, color_names)
+        ColorsMemMapWriter::new(
+            path,
+            color_names,
+            COLORS_ABUNDANCES.load(Ordering::Relaxed),
+        )
     }
 
     fn open_colors_table(_path: impl AsRef<Path>) -> Self::GlobalColorsTableReader {
@@ -149,6 +164,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
             kmers_count: 0,
             sequences_count: 0,
             temp_colors_buffer: vec![],
+            temp_abundances_buffer: vec![],
             temp_dir: temp_dir.to_path_buf(),
             _phantom: PhantomData,
         }
@@ -225,6 +241,8 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
         k: usize,
         min_multiplicity: usize,
     ) {
+        let with_abundances = COLORS_ABUNDANCES.load(Ordering::Relaxed);
+
         for buffer in data.sequences.iter_mut() {
             data.temp_colors_buffer.clear();
 
@@ -292,8 +310,12 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
                     if entry_count & VISITED_BIT == 0 {
                         let colors_count = entry_count;
                         let start_temp_color_index = data.temp_colors_buffer.len();
+                        // The position must not overflow into the multiplicity bits
+                        assert!(start_temp_color_index <= COLOR_MASK);
 
-                        entry_count = VISITED_BIT | start_temp_color_index;
+                        entry_count = VISITED_BIT
+                            | (colors_count.min(MULTIPLICITY_MAX) << MULTIPLICITY_SHIFT)
+                            | start_temp_color_index;
                         entry.set_counter_after_check(entry_count);
 
                         data.temp_colors_buffer
@@ -302,7 +324,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
                         data.temp_colors_buffer[start_temp_color_index] = 1;
                     }
 
-                    let position = entry_count & !VISITED_BIT;
+                    let position = entry_count & COLOR_MASK;
 
                     let col_count = data.temp_colors_buffer[position] as usize;
                     data.temp_colors_buffer[position] += 1;
@@ -321,6 +343,16 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
 
                         colors_range.sort_unstable();
 
+                        if with_abundances {
+                            // Each color appears once for every occurrence of the kmer in it
+                            data.temp_abundances_buffer.clear();
+                            data.temp_abundances_buffer.extend(
+                                colors_range
+                                    .group_by(|a, b| a == b)
+                                    .map(|g| quantize_color_abundance(g.len() as u64)),
+                            );
+                        }
+
                         // Get the new partition indexes, start to dedup last element
                         let new_partition =
                             (position + 1)..(position + 1 + colors_range.partition_dedup().0.len());
@@ -328,12 +360,20 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
                         let unique_colors = &data.temp_colors_buffer[new_partition.clone()];
 
                         // Assign the subset color index to the current kmer
-                        if unique_colors != &data.temp_colors_buffer[last_partition.clone()] {
+                        if with_abundances {
+                            last_color = global_colors_table.get_id_with_abundances(
+                                unique_colors,
+                                &data.temp_abundances_buffer,
+                            );
+                        } else if unique_colors != &data.temp_colors_buffer[last_partition.clone()]
+                        {
                             last_color = global_colors_table.get_id(unique_colors);
                             last_partition = new_partition;
                         }
 
-                        entry.set_counter_after_check(VISITED_BIT | (last_color as usize));
+                        entry.set_counter_after_check(
+                            (entry_count & !COLOR_MASK) | (last_color as usize),
+                        );
                     }
                 }
             }
@@ -346,18 +386,24 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
     fn alloc_unitig_color_structure() -> Self::TempUnitigColorStructure {
         DefaultUnitigsTempColorData {
             colors: VecDeque::new(),
+            abundance: UnitigAbundance::default(),
         }
     }
 
     fn reset_unitig_color_structure(ts: &mut Self::TempUnitigColorStructure) {
         ts.colors.clear();
+        ts.abundance.clear();
     }
 
     fn extend_forward(
         ts: &mut Self::TempUnitigColorStructure,
         entry: &MapEntry<Self::HashMapTempColorIndex>,
     ) {
-        let kmer_color = (entry.get_counter() & !VISITED_BIT) as ColorIndexType;
+        let kmer_color = (entry.get_counter() & COLOR_MASK) as ColorIndexType;
+
+        if UnitigAbundance::is_enabled() {
+            ts.abundance.extend_forward(get_entry_multiplicity(entry));
+        }
 
         if let Some(back_ts) = ts.colors.back_mut() && back_ts.0 == kmer_color {
             back_ts.1 += 1;
@@ -366,11 +412,25 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
         }
     }
 
+    fn get_kmer_multiplicity(entry: &MapEntry<Self::HashMapTempColorIndex>) -> u64 {
+        get_entry_multiplicity(entry)
+    }
+
+    fn get_kmer_colors_subset(
+        entry: &MapEntry<Self::HashMapTempColorIndex>,
+    ) -> Option<ColorIndexType> {
+        Some((entry.get_counter() & COLOR_MASK) as ColorIndexType)
+    }
+
     fn extend_backward(
         ts: &mut Self::TempUnitigColorStructure,
         entry: &MapEntry<Self::HashMapTempColorIndex>,
     ) {
-        let kmer_color = (entry.get_counter() & !VISITED_BIT) as ColorIndexType;
+        let kmer_color = (entry.get_counter() & COLOR_MASK) as ColorIndexType;
+
+        if UnitigAbundance::is_enabled() {
+            ts.abundance.extend_backward(get_entry_multiplicity(entry));
+        }
 
         if let Some(front_ts) = ts.colors.front_mut()
             && front_ts.0 == kmer_color {
@@ -394,6 +454,8 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
             }
         };
 
+        dest.abundance.join::<REVERSE>(&src.abundance, skip);
+
         let len = src.slice.end - src.slice.start;
 
         let colors_slice = &src_buffer.colors.as_slice();
@@ -416,6 +478,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
     }
 
     fn pop_base(target: &mut Self::TempUnitigColorStructure) {
+        target.abundance.pop_kmer();
         if let Some(last) = target.colors.back_mut() {
             last.1 -= 1;
             if last.1 == 0 {
@@ -424,6 +487,49 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
         }
     }
 
+    fn split_first_colors_run(
+        ts: &mut Self::TempUnitigColorStructure,
+        run: &mut Self::TempUnitigColorStructure,
+    ) -> u64 {
+        run.colors.clear();
+
+        match ts.colors.pop_front() {
+            Some((color, kmers)) => {
+                run.colors.push_back((color, kmers));
+                run.abundance = ts.abundance.split_front(kmers);
+                kmers
+            }
+            None => {
+                run.abundance.clear();
+                0
+            }
+        }
+    }
+
+    fn get_single_colors_subset(
+        color: &Self::PartialUnitigsColorStructure,
+        colors_buffer: &<Self::PartialUnitigsColorStructure as SequenceExtraData>::TempBuffer,
+    ) -> Option<ColorIndexType> {
+        if color.slice.len() == 1 {
+            Some(colors_buffer.colors[color.slice.start].0)
+        } else {
+            None
+        }
+    }
+
+    fn get_colors_runs(
+        color: &Self::PartialUnitigsColorStructure,
+        colors_buffer: &<Self::PartialUnitigsColorStructure as SequenceExtraData>::TempBuffer,
+        runs: &mut Vec<(ColorIndexType, u64)>,
+    ) {
+        runs.extend_from_slice(&colors_buffer.colors[color.slice.clone()]);
+    }
+
+    #[inline(always)]
+    fn get_unitig_abundance(color: &Self::PartialUnitigsColorStructure) -> UnitigAbundance {
+        color.abundance.clone()
+    }
+
     fn encode_part_unitigs_colors(
         ts: &mut Self::TempUnitigColorStructure,
         colors_buffer: &mut <Self::PartialUnitigsColorStructure as SequenceExtraData>::TempBuffer,
@@ -433,6 +539,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
 
         UnitigColorDataSerializer {
             slice: 0..colors_buffer.colors.len(),
+            abundance: ts.abundance.clone(),
         }
     }
 
@@ -466,7 +573,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
                 .flatten(),
         ) {
             let entry = hmap.get(&hash.to_unextendable()).unwrap();
-            let kmer_color = (entry.get_counter() & !VISITED_BIT) as ColorIndexType;
+            let kmer_color = (entry.get_counter() & COLOR_MASK) as ColorIndexType;
             if kmer_color != color {
                 let hashes = MH::new(read, 31);
                 println!(
@@ -475,7 +582,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
                         .iter()
                         .map(|h| {
                             let entry = hmap.get(&h.to_unextendable()).unwrap();
-                            let kmer_color = (entry.get_counter() & !VISITED_BIT) as ColorIndexType;
+                            let kmer_color = (entry.get_counter() & COLOR_MASK) as ColorIndexType;
                             kmer_color
                         })
                         .zip(
@@ -496,6 +603,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
 #[derive(Debug)]
 pub struct DefaultUnitigsTempColorData {
     colors: VecDeque<(ColorIndexType, u64)>,
+    abundance: UnitigAbundance,
 }
 
 #[derive(Debug)]
@@ -506,6 +614,7 @@ pub struct UnitigsSerializerTempBuffer {
 #[derive(Clone, Debug)]
 pub struct UnitigColorDataSerializer {
     slice: Range<usize>,
+    abundance: UnitigAbundance,
 }
 
 impl SequenceExtraDataTempBufferManagement<UnitigsSerializerTempBuffer>
@@ -533,6 +642,7 @@ impl SequenceExtraDataTempBufferManagement<UnitigsSerializerTempBuffer>
         dst.colors.extend(&src.colors[extra.slice]);
         Self {
             slice: start..dst.colors.len(),
+            abundance: extra.abundance,
         }
     }
 }
@@ -551,8 +661,11 @@ impl SequenceExtraData for UnitigColorDataSerializer {
                 decode_varint(|| reader.read_u8().ok())?,
             ));
         }
+        let abundance = UnitigAbundance::decode(reader)?;
+
         Some(Self {
             slice: start..buffer.colors.len(),
+            abundance,
         })
     }
 
@@ -565,16 +678,19 @@ impl SequenceExtraData for UnitigColorDataSerializer {
             encode_varint(|b| writer.write_all(b), el.0 as u64).unwrap();
             encode_varint(|b| writer.write_all(b), el.1).unwrap();
         }
+        self.abundance.encode(writer);
     }
 
     #[inline(always)]
     fn max_size(&self) -> usize {
         (2 * (self.slice.end - self.slice.start) + 1) * VARINT_MAX_SIZE
+            + self.abundance.encoded_max_size()
     }
 }
 
 impl IdentSequenceWriter for UnitigColorDataSerializer {
     fn write_as_ident(&self, stream: &mut impl Write, extra_buffer: &Self::TempBuffer) {
+        self.abundance.write_tags(stream);
         for i in self.slice.clone() {
             write!(
                 stream,
@@ -1,3 +1,4 @@
+use crate::abundance::UnitigAbundance;
 use crate::colors_manager::ColorsMergeManager;
 use crate::storage::deserializer::ColorsDeserializer;
 use crate::DefaultColorsSerializer;
@@ -110,6 +111,16 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
         panic!("Unsupported!");
     }
 
+    fn get_kmer_multiplicity(_entry: &MapEntry<Self::HashMapTempColorIndex>) -> u64 {
+        panic!("Unsupported!");
+    }
+
+    fn get_kmer_colors_subset(
+        _entry: &MapEntry<Self::HashMapTempColorIndex>,
+    ) -> Option<ColorIndexType> {
+        panic!("Unsupported!");
+    }
+
     fn extend_backward(
         _ts: &mut Self::TempUnitigColorStructure,
         _entry: &MapEntry<Self::HashMapTempColorIndex>,
@@ -130,6 +141,32 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
         panic!("Unsupported!");
     }
 
+    fn split_first_colors_run(
+        _ts: &mut Self::TempUnitigColorStructure,
+        _run: &mut Self::TempUnitigColorStructure,
+    ) -> u64 {
+        panic!("Unsupported!");
+    }
+
+    fn get_single_colors_subset(
+        _color: &Self::PartialUnitigsColorStructure,
+        _colors_buffer: &<Self::PartialUnitigsColorStructure as SequenceExtraData>::TempBuffer,
+    ) -> Option<ColorIndexType> {
+        panic!("Unsupported!");
+    }
+
+    fn get_colors_runs(
+        _color: &Self::PartialUnitigsColorStructure,
+        _colors_buffer: &<Self::PartialUnitigsColorStructure as SequenceExtraData>::TempBuffer,
+        _runs: &mut Vec<(ColorIndexType, u64)>,
+    ) {
+        panic!("Unsupported!");
+    }
+
+    fn get_unitig_abundance(_color: &Self::PartialUnitigsColorStructure) -> UnitigAbundance {
+        UnitigAbundance::default()
+    }
+
     fn encode_part_unitigs_colors(
         _ts: &mut Self::TempUnitigColorStructure,
         _colors_buffer: &mut <Self::PartialUnitigsColorStructure as SequenceExtraData>::TempBuffer,
@@ -1,8 +1,9 @@
+use crate::abundance::UnitigAbundance;
 use crate::colors_manager::{
     ColorsManager, ColorsMergeManager, ColorsParser, MinimizerBucketingSeqColorData,
 };
 use crate::parsers::SingleSequenceInfo;
-use config::BucketIndexType;
+use config::{BucketIndexType, ColorIndexType};
 use hashbrown::HashMap;
 use hashes::{HashFunctionFactory, MinimizerHashFunctionFactory};
 use io::compressed_read::CompressedRead;
@@ -67,7 +68,7 @@ impl MinimizerBucketingSeqColorData for NonColoredManager {
     type KmerColorIterator<'a> = std::iter::Repeat<NonColoredManager>;
 
     #[inline(always)]
-    fn create(_file_index: SingleSequenceInfo, _: &mut ()) -> Self {
+    fn create(_sequence_info: SingleSequenceInfo, _: &mut ()) -> Self {
         NonColoredManager
     }
 
@@ -172,49 +173,100 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
         unreachable!()
     }
 
-    type PartialUnitigsColorStructure = NonColoredManager;
-    type TempUnitigColorStructure = NonColoredManager;
+    type PartialUnitigsColorStructure = UnitigAbundance;
+    type TempUnitigColorStructure = UnitigAbundance;
 
     #[inline(always)]
     fn alloc_unitig_color_structure() -> Self::TempUnitigColorStructure {
-        NonColoredManager
+        UnitigAbundance::default()
     }
 
     #[inline(always)]
-    fn reset_unitig_color_structure(_ts: &mut Self::TempUnitigColorStructure) {}
+    fn reset_unitig_color_structure(ts: &mut Self::TempUnitigColorStructure) {
+        ts.clear();
+    }
 
     #[inline(always)]
     fn extend_forward(
-        _ts: &mut Self::TempUnitigColorStructure,
-        _entry: &MapEntry<Self::HashMapTempColorIndex>,
+        ts: &mut Self::TempUnitigColorStructure,
+        entry: &MapEntry<Self::HashMapTempColorIndex>,
     ) {
+        if UnitigAbundance::is_enabled() {
+            ts.extend_forward(entry.get_kmer_multiplicity() as u64);
+        }
     }
 
     #[inline(always)]
-    fn extend_backward(
-        _ts: &mut Self::TempUnitigColorStructure,
+    fn get_kmer_multiplicity(entry: &MapEntry<Self::HashMapTempColorIndex>) -> u64 {
+        entry.get_kmer_multiplicity() as u64
+    }
+
+    #[inline(always)]
+    fn get_kmer_colors_subset(
         _entry: &MapEntry<Self::HashMapTempColorIndex>,
+    ) -> Option<ColorIndexType> {
+        None
+    }
+
+    #[inline(always)]
+    fn extend_backward(
+        ts: &mut Self::TempUnitigColorStructure,
+        entry: &MapEntry<Self::HashMapTempColorIndex>,
     ) {
+        if UnitigAbundance::is_enabled() {
+            ts.extend_backward(entry.get_kmer_multiplicity() as u64);
+        }
     }
 
     #[inline(always)]
     fn join_structures<const REVERSE: bool>(
-        _dest: &mut Self::TempUnitigColorStructure,
-        _src: &Self::PartialUnitigsColorStructure,
+        dest: &mut Self::TempUnitigColorStructure,
+        src: &Self::PartialUnitigsColorStructure,
         _src_buffer: &<Self::PartialUnitigsColorStructure as SequenceExtraData>::TempBuffer,
-        _skip: u64,
+        skip: u64,
     ) {
+        dest.join::<REVERSE>(src, skip);
     }
 
     #[inline(always)]
-    fn pop_base(_target: &mut Self::TempUnitigColorStructure) {}
+    fn pop_base(target: &mut Self::TempUnitigColorStructure) {
+        target.pop_kmer();
+    }
+
+    fn split_first_colors_run(
+        _ts: &mut Self::TempUnitigColorStructure,
+        _run: &mut Self::TempUnitigColorStructure,
+    ) -> u64 {
+        unimplemented!("Colors are not enabled")
+    }
+
+    #[inline(always)]
+    fn get_single_colors_subset(
+        _color: &Self::PartialUnitigsColorStructure,
+        _colors_buffer: &<Self::PartialUnitigsColorStructure as SequenceExtraData>::TempBuffer,
+    ) -> Option<ColorIndexType> {
+        None
+    }
+
+    #[inline(always)]
+    fn get_colors_runs(
+        _color: &Self::PartialUnitigsColorStructure,
+        _colors_buffer: &<Self::PartialUnitigsColorStructure as SequenceExtraData>::TempBuffer,
+        _runs: &mut Vec<(ColorIndexType, u64)>,
+    ) {
+    }
+
+    #[inline(always)]
+    fn get_unitig_abundance(color: &Self::PartialUnitigsColorStructure) -> UnitigAbundance {
+        color.clone()
+    }
 
     #[inline(always)]
     fn encode_part_unitigs_colors(
-        _ts: &mut Self::TempUnitigColorStructure,
+        ts: &mut Self::TempUnitigColorStructure,
         _colors_buffer: &mut <Self::PartialUnitigsColorStructure as SequenceExtraData>::TempBuffer,
     ) -> Self::PartialUnitigsColorStructure {
-        NonColoredManager
+        ts.clone()
     }
 
     fn debug_tucs(_str: &Self::TempUnitigColorStructure, _seq: &[u8]) {}
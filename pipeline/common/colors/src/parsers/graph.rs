@@ -210,7 +210,11 @@ impl SequenceExtraData for MinBkMultipleColors {
 
 fn parse_colors(ident: &[u8], colors_buffer: &mut Vec<(usize, ColorIndexType)>) -> Range<usize> {
     let mut colors_count = 0;
-    for col_pos in ident.find_iter(b"C:") {
+    // Only the C: fields, skipping the other tags ending with C (e.g. KC:i:)
+    for col_pos in ident
+        .find_iter(b"C:")
+        .filter(|pos| *pos == 0 || ident[pos - 1].is_ascii_whitespace())
+    {
         let (color_index, next_pos) = ColorIndexType::from_radix_16(&ident[(col_pos + 2)..]);
 
         let kmers_count = usize::from_radix_10(&ident[(col_pos + next_pos + 3)..]).0;
@@ -281,11 +285,23 @@ impl ColorsParser for GraphColorsParser {
 #[cfg(test)]
 mod tests {
     use crate::colors_manager::MinimizerBucketingSeqColorData;
-    use crate::parsers::graph::MinBkMultipleColors;
+    use crate::parsers::graph::{parse_colors, MinBkMultipleColors};
     use crate::parsers::SingleSequenceInfo;
     use io::concurrent::temp_reads::extra_data::SequenceExtraData;
     use std::io::Cursor;
 
+    #[test]
+    fn graph_colors_with_abundance_tags() {
+        let mut colors_buffer = Vec::new();
+        let colors = parse_colors(
+            b">12 LN:i:40 C:1:5 C:a:4 km:f:3.0 KC:i:27 mn:i:2 mx:i:4",
+            &mut colors_buffer,
+        );
+
+        assert_eq!(colors, 0..9);
+        assert_eq!(colors_buffer, [(5, 1), (4, 10)]);
+    }
+
     #[test]
     fn graph_multiple_colors_structure() {
         let input_colors = "C:1:12 C:2:1 C:3:3 C:4:23 C:5:7 C:6:24";
@@ -294,7 +310,8 @@ mod tests {
 
         let colors = MinBkMultipleColors::create(
             SingleSequenceInfo {
-                file_index: 0,
+                file_color: 0,
+                record_colors: None,
                 sequence_ident: input_colors.as_bytes(),
             },
             &mut extra_buffer,
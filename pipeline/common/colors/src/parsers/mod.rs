@@ -1,7 +1,14 @@
+use crate::parsers::records::RecordColorsTable;
+use config::ColorIndexType;
+
 pub mod graph;
+pub mod records;
 pub mod separate;
 
 pub struct SingleSequenceInfo<'a> {
-    pub file_index: usize,
+    /// The color assigned to the input file that contains the sequence
+    pub file_color: ColorIndexType,
+    /// If present, each sequence is colored by its header instead of by its file
+    pub record_colors: Option<&'a RecordColorsTable>,
     pub sequence_ident: &'a [u8],
 }
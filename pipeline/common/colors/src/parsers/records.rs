@@ -0,0 +1,154 @@
+use bstr::ByteSlice;
+use config::ColorIndexType;
+use hashbrown::HashMap;
+use io::sequences_reader::SequencesReader;
+use rayon::prelude::*;
+use regex::bytes::Regex;
+use std::path::PathBuf;
+
+/// Rule used to extract the color name of a sequence from its header
+#[derive(Clone, Debug)]
+pub enum RecordColorsMode {
+    /// The sequence identifier, up to the first whitespace
+    Identifier,
+    /// The part of the identifier preceding the first occurrence of the delimiter
+    IdentifierPrefix(u8),
+    /// The first capture group (or the whole match if there are no groups) of the regex
+    HeaderRegex(Regex),
+}
+
+impl RecordColorsMode {
+    pub fn from_regex(regex: &str) -> Result<Self, regex::Error> {
+        Ok(Self::HeaderRegex(Regex::new(regex)?))
+    }
+
+    /// Returns the color name of the sequence with the given header (including the leading '>' or '@')
+    pub fn extract_name<'a>(&self, header: &'a [u8]) -> &'a [u8] {
+        let header = match header.first() {
+            Some(b'>') | Some(b'@') => &header[1..],
+            _ => header,
+        };
+
+        let identifier = header
+            .fields_with(|c| c.is_ascii_whitespace())
+            .next()
+            .unwrap_or(header);
+
+        match self {
+            RecordColorsMode::Identifier => identifier,
+            RecordColorsMode::IdentifierPrefix(delimiter) => identifier
+                .find_byte(*delimiter)
+                .map(|pos| &identifier[..pos])
+                .unwrap_or(identifier),
+            RecordColorsMode::HeaderRegex(regex) => regex
+                .captures(header)
+                .and_then(|captures| captures.get(1).or_else(|| captures.get(0)))
+                .map(|m| m.as_bytes())
+                .unwrap_or(identifier),
+        }
+    }
+}
+
+/// Maps the header of each input sequence to its color, when colors are assigned per record instead of per file
+pub struct RecordColorsTable {
+    mode: RecordColorsMode,
+    colors: HashMap<Vec<u8>, ColorIndexType>,
+}
+
+impl RecordColorsTable {
+    /// Scans the headers of all the input files, assigning a color to each distinct record name.
+    /// Colors are numbered in order of first appearance, following the input files order.
+    /// Returns the table along with the color names.
+    pub fn build(input_files: &[PathBuf], mode: RecordColorsMode) -> (Self, Vec<String>) {
+        let files_names: Vec<Vec<Vec<u8>>> = input_files
+            .par_iter()
+            .map(|file| {
+                let mut seen = hashbrown::HashSet::new();
+                let mut names = Vec::new();
+
+                SequencesReader::new().process_file_extended(
+                    file,
+                    |seq| {
+                        let name = mode.extract_name(seq.ident);
+                        if !seen.contains(name) {
+                            seen.insert(name.to_vec());
+                            names.push(name.to_vec());
+                        }
+                    },
+                    None,
+                    true,
+                    false,
+                );
+                names
+            })
+            .collect();
+
+        let mut colors = HashMap::new();
+        let mut color_names = Vec::new();
+
+        for name in files_names.into_iter().flatten() {
+            colors.entry(name).or_insert_with_key(|name| {
+                color_names.push(name.to_str_lossy().to_string());
+                (color_names.len() - 1) as ColorIndexType
+            });
+        }
+
+        println!(
+            "Assigned {} colors from the records of {} files",
+            color_names.len(),
+            input_files.len()
+        );
+
+        (Self { mode, colors }, color_names)
+    }
+
+    /// Returns the color of the sequence with the given header, or None if its name was not found
+    /// while building the table
+    #[inline]
+    pub fn get_color(&self, header: &[u8]) -> Option<ColorIndexType> {
+        self.colors.get(self.mode.extract_name(header)).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parsers::records::RecordColorsMode;
+
+    #[test]
+    fn records_names_extraction() {
+        let header = b">sample1_contig12 length=1500 sample=abc";
+
+        assert_eq!(
+            RecordColorsMode::Identifier.extract_name(header),
+            b"sample1_contig12"
+        );
+        assert_eq!(
+            RecordColorsMode::IdentifierPrefix(b'_').extract_name(header),
+            b"sample1"
+        );
+        assert_eq!(
+            RecordColorsMode::IdentifierPrefix(b'|').extract_name(b"@read1 extra"),
+            b"read1"
+        );
+        assert_eq!(
+            RecordColorsMode::from_regex("sample=(\\w+)")
+                .unwrap()
+                .extract_name(header),
+            b"abc"
+        );
+        assert_eq!(
+            RecordColorsMode::from_regex("contig\\d+")
+                .unwrap()
+                .extract_name(header),
+            b"contig12"
+        );
+        // Without a match the whole identifier is used
+        assert_eq!(
+            RecordColorsMode::from_regex("missing=(\\w+)")
+                .unwrap()
+                .extract_name(header),
+            b"sample1_contig12"
+        );
+        assert_eq!(RecordColorsMode::Identifier.extract_name(b">"), b"");
+    }
+}
@@ -6,6 +6,7 @@ use io::concurrent::temp_reads::extra_data::SequenceExtraData;
 use io::varint::{decode_varint, encode_varint, VARINT_MAX_SIZE};
 use std::io::{Read, Write};
 use std::ops::Range;
+use std::process::exit;
 
 #[derive(Copy, Clone, Default, Debug, Eq, PartialEq)]
 pub struct MinBkSingleColor(ColorIndexType);
@@ -56,7 +57,18 @@ impl MinimizerBucketingSeqColorData for MinBkSingleColor {
     type KmerColorIterator<'a> = std::iter::Repeat<ColorIndexType>;
 
     fn create(sequence_info: SingleSequenceInfo, _: &mut ()) -> Self {
-        Self(sequence_info.file_index as ColorIndexType)
+        Self(match sequence_info.record_colors {
+            None => sequence_info.file_color,
+            Some(record_colors) => record_colors
+                .get_color(sequence_info.sequence_ident)
+                .unwrap_or_else(|| {
+                    println!(
+                        "ERROR: The color of record '{}' was not found, the input files may have changed while reading them",
+                        String::from_utf8_lossy(sequence_info.sequence_ident)
+                    );
+                    exit(1);
+                }),
+        })
     }
 
     fn get_iterator<'a>(&'a self, _: &'a ()) -> Self::KmerColorIterator<'a> {
@@ -1,5 +1,7 @@
 use crate::colors_manager::ColorMapReader;
-use crate::storage::serializer::{ColorsFileHeader, ColorsIndexEntry, ColorsIndexMap};
+use crate::storage::serializer::{
+    ColorsFileHeader, ColorsIndexEntry, ColorsIndexMap, STORAGE_VERSION_WITH_ABUNDANCES,
+};
 use crate::storage::ColorsSerializerTrait;
 use config::ColorIndexType;
 use desse::Desse;
@@ -18,6 +20,8 @@ pub struct ColorsDeserializer<DS: ColorsSerializerTrait> {
     current_chunk: ColorsIndexEntry,
     current_chunk_size: ColorIndexType,
     current_index: ColorIndexType,
+    has_abundances: bool,
+    skipped_colors_buffer: Vec<ColorIndexType>,
     _phantom: PhantomData<DS>,
 }
 
@@ -73,6 +77,8 @@ impl<DS: ColorsSerializerTrait> ColorsDeserializer<DS> {
             current_chunk: first_chunk,
             current_chunk_size,
             current_index: first_chunk.start_index,
+            has_abundances: header.version == STORAGE_VERSION_WITH_ABUNDANCES,
+            skipped_colors_buffer: Vec::new(),
             _phantom: Default::default(),
         }
     }
@@ -117,17 +123,58 @@ impl<DS: ColorsSerializerTrait> ColorsDeserializer<DS> {
         }
     }
 
-    pub fn get_color_mappings(&mut self, color: ColorIndexType, out_vec: &mut Vec<ColorIndexType>) {
+    fn skip_to_color(&mut self, color: ColorIndexType) {
         self.maybe_change_block(color);
 
         while self.current_index < color {
             // Skip the colors
-            DS::decode_color(&mut self.colormap_file, None);
+            if self.has_abundances {
+                DS::decode_color(
+                    &mut self.colormap_file,
+                    Some(&mut self.skipped_colors_buffer),
+                );
+                DS::decode_color_abundances(
+                    &mut self.colormap_file,
+                    self.skipped_colors_buffer.len(),
+                    None,
+                );
+            } else {
+                DS::decode_color(&mut self.colormap_file, None);
+            }
             self.current_index += 1;
         }
+    }
+
+    pub fn get_color_mappings(&mut self, color: ColorIndexType, out_vec: &mut Vec<ColorIndexType>) {
+        self.skip_to_color(color);
 
         // Decode the requested color
         DS::decode_color(&mut self.colormap_file, Some(out_vec));
+        if self.has_abundances {
+            DS::decode_color_abundances(&mut self.colormap_file, out_vec.len(), None);
+        }
+        self.current_index += 1;
+    }
+
+    /// Decodes the requested subset along with the quantized abundance of each color,
+    /// the abundances are left empty if the colormap does not store them
+    pub fn get_color_mappings_with_abundances(
+        &mut self,
+        color: ColorIndexType,
+        out_vec: &mut Vec<ColorIndexType>,
+        out_abundances: &mut Vec<u8>,
+    ) {
+        self.skip_to_color(color);
+
+        DS::decode_color(&mut self.colormap_file, Some(out_vec));
+        out_abundances.clear();
+        if self.has_abundances {
+            DS::decode_color_abundances(
+                &mut self.colormap_file,
+                out_vec.len(),
+                Some(out_abundances),
+            );
+        }
         self.current_index += 1;
     }
 }
@@ -148,4 +195,74 @@ impl<DS: ColorsSerializerTrait> ColorMapReader for ColorsDeserializer<DS> {
     fn colors_subsets_count(&self) -> u64 {
         self.colors_index.subsets_count as u64
     }
+
+    /// True if the colormap stores the quantized abundance of each color in the subsets
+    fn has_abundances(&self) -> bool {
+        self.has_abundances
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::colors_manager::ColorMapReader;
+    use crate::storage::deserializer::ColorsDeserializer;
+    use crate::storage::serializer::ColorsSerializer;
+    use crate::DefaultColorsSerializer;
+    use config::ColorIndexType;
+
+    #[test]
+    fn colormap_with_abundances_roundtrip() {
+        let colormap_file = std::env::temp_dir().join(format!(
+            "ggcat_colormap_with_abundances_roundtrip_{}.colors.dat",
+            std::process::id()
+        ));
+
+        let subsets: [(&[ColorIndexType], &[u8]); 4] = [
+            (&[0, 1, 2], &[1, 3, 2]),
+            (&[1], &[9]),
+            (&[0, 1, 2], &[1, 1, 1]),
+            (&[2], &[4]),
+        ];
+
+        {
+            let serializer = ColorsSerializer::<DefaultColorsSerializer>::new(
+                &colormap_file,
+                vec!["a".to_string(), "b".to_string(), "c".to_string()],
+                true,
+            );
+            for (index, (colors, abundances)) in subsets.iter().enumerate() {
+                assert_eq!(
+                    serializer.serialize_colors_with_abundances(colors, abundances),
+                    index as ColorIndexType
+                );
+            }
+        }
+
+        let mut deserializer =
+            ColorsDeserializer::<DefaultColorsSerializer>::new(&colormap_file, true);
+        assert!(deserializer.has_abundances());
+        assert_eq!(deserializer.colors_count(), 3);
+        assert_eq!(deserializer.colors_subsets_count(), 4);
+        assert_eq!(deserializer.get_color_name(1, false), "b");
+
+        // Out of order, to skip over the stored abundances and to seek back
+        let mut colors = Vec::new();
+        let mut abundances = Vec::new();
+        for subset in [1, 3, 0, 2] {
+            colors.clear();
+            deserializer.get_color_mappings_with_abundances(
+                subset as ColorIndexType,
+                &mut colors,
+                &mut abundances,
+            );
+            assert_eq!(colors, subsets[subset].0);
+            assert_eq!(abundances, subsets[subset].1);
+        }
+
+        colors.clear();
+        deserializer.get_color_mappings(1, &mut colors);
+        assert_eq!(colors, subsets[1].0);
+
+        std::fs::remove_file(&colormap_file).unwrap();
+    }
 }
@@ -11,10 +11,18 @@ pub trait ColorsSerializerTrait: 'static {
     const MAGIC: [u8; 16];
 
     fn decode_color(reader: impl Read, out_vec: Option<&mut Vec<ColorIndexType>>);
+    /// Decodes the quantized abundances stored after a colors subset with the given number of colors
+    fn decode_color_abundances(reader: impl Read, colors_count: usize, out_vec: Option<&mut Vec<u8>>);
     // fn decode_colors(reader: impl Read) -> ;
 
     fn new(writer: ColorsFlushProcessing, checkpoint_distance: usize, colors_count: u64) -> Self;
     fn serialize_colors(&self, colors: &[ColorIndexType]) -> ColorIndexType;
+    /// Serializes a colors subset followed by the quantized abundance of each color
+    fn serialize_colors_with_abundances(
+        &self,
+        colors: &[ColorIndexType],
+        abundances: &[u8],
+    ) -> ColorIndexType;
     fn get_subsets_count(&self) -> u64;
     fn print_stats(&self);
     fn finalize(self) -> ColorsFlushProcessing;
@@ -95,6 +95,14 @@ impl ColorsSerializerTrait for RoaringColorsSerializer {
         todo!()
     }
 
+    fn decode_color_abundances(
+        _reader: impl Read,
+        _colors_count: usize,
+        _out_vec: Option<&mut Vec<u8>>,
+    ) {
+        todo!()
+    }
+
     fn new(writer: ColorsFlushProcessing, checkpoint_distance: usize, colors_count: u64) -> Self {
         todo!("Fix meaning of 'stride'!");
         let stride = rayon::current_num_threads() as ColorIndexType;
@@ -133,6 +141,14 @@ impl ColorsSerializerTrait for RoaringColorsSerializer {
         color_index
     }
 
+    fn serialize_colors_with_abundances(
+        &self,
+        _colors: &[ColorIndexType],
+        _abundances: &[u8],
+    ) -> ColorIndexType {
+        todo!()
+    }
+
     fn get_subsets_count(&self) -> u64 {
         self.colors_index.load(Ordering::Relaxed) as u64
     }
@@ -118,6 +118,25 @@ impl ColorsSerializerTrait for RunLengthColorsSerializer {
         }
     }
 
+    fn decode_color_abundances(
+        mut reader: impl Read,
+        colors_count: usize,
+        out_vec: Option<&mut Vec<u8>>,
+    ) {
+        match out_vec {
+            None => {
+                for _ in 0..colors_count {
+                    reader.read_u8().unwrap();
+                }
+            }
+            Some(out_vec) => {
+                out_vec.clear();
+                out_vec.resize(colors_count, 0);
+                reader.read_exact(&mut out_vec[..]).unwrap();
+            }
+        }
+    }
+
     fn new(writer: ColorsFlushProcessing, checkpoint_distance: usize, _colors_count: u64) -> Self {
         Self {
             async_buffer: AsyncSliceQueue::new(
@@ -137,6 +156,15 @@ impl ColorsSerializerTrait for RunLengthColorsSerializer {
         }
     }
 
+    fn serialize_colors_with_abundances(&self, colors: &[u32], abundances: &[u8]) -> u32 {
+        unsafe {
+            TEMP_COLOR_BUFFER.clear();
+            ColorIndexSerializer::serialize_colors(&mut TEMP_COLOR_BUFFER, colors);
+            TEMP_COLOR_BUFFER.extend_from_slice(abundances);
+            self.async_buffer.add_data(TEMP_COLOR_BUFFER.as_slice()) as ColorIndexType
+        }
+    }
+
     fn get_subsets_count(&self) -> u64 {
         self.async_buffer.get_counter()
     }
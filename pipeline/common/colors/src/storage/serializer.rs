@@ -13,6 +13,8 @@ use std::path::Path;
 use std::sync::atomic::{AtomicU64, Ordering};
 
 const STORAGE_VERSION: u64 = 1;
+/// Same as the base version, but each subset is followed by the quantized abundance of its colors
+pub(crate) const STORAGE_VERSION_WITH_ABUNDANCES: u64 = 2;
 
 #[derive(Debug, Desse, DesseSized, Default)]
 pub(crate) struct ColorsFileHeader {
@@ -39,11 +41,12 @@ pub(crate) struct ColorsIndexMap {
 
 pub struct ColorsSerializer<SI: ColorsSerializerTrait> {
     colors_count: u64,
+    with_abundances: bool,
     serializer_impl: ManuallyDrop<SI>,
 }
 
 impl<SI: ColorsSerializerTrait> ColorsSerializer<SI> {
-    pub fn new(file: impl AsRef<Path>, color_names: Vec<String>) -> Self {
+    pub fn new(file: impl AsRef<Path>, color_names: Vec<String>, with_abundances: bool) -> Self {
         let mut colormap_file = File::create(file).unwrap();
 
         colormap_file
@@ -80,6 +83,7 @@ impl<SI: ColorsSerializerTrait> ColorsSerializer<SI> {
 
         Self {
             colors_count,
+            with_abundances,
             serializer_impl: ManuallyDrop::new(SI::new(
                 color_processor,
                 COLORS_SINGLE_BATCH_SIZE as usize,
@@ -92,6 +96,16 @@ impl<SI: ColorsSerializerTrait> ColorsSerializer<SI> {
         self.serializer_impl.serialize_colors(colors)
     }
 
+    pub fn serialize_colors_with_abundances(
+        &self,
+        colors: &[ColorIndexType],
+        abundances: &[u8],
+    ) -> ColorIndexType {
+        debug_assert!(self.with_abundances);
+        self.serializer_impl
+            .serialize_colors_with_abundances(colors, abundances)
+    }
+
     pub fn print_stats(&self) {
         self.serializer_impl.print_stats()
     }
@@ -127,7 +141,11 @@ impl<SI: ColorsSerializerTrait> Drop for ColorsSerializer<SI> {
             .write_all(
                 &ColorsFileHeader {
                     magic: SI::MAGIC,
-                    version: STORAGE_VERSION,
+                    version: if self.with_abundances {
+                        STORAGE_VERSION_WITH_ABUNDANCES
+                    } else {
+                        STORAGE_VERSION
+                    },
                     index_offset: index_position,
                     colors_count: self.colors_count,
                     subsets_count,
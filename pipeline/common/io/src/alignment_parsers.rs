@@ -0,0 +1,452 @@
+use crate::sequences_reader::{normalize_sequence, FastaSequence, ParsingPosition};
+use byteorder::{ByteOrder, LittleEndian};
+use std::mem::take;
+
+const FLAG_REVERSE_COMPLEMENTED: u16 = 0x10;
+const FLAG_SECONDARY: u16 = 0x100;
+const FLAG_SUPPLEMENTARY: u16 = 0x800;
+
+/// Secondary and supplementary alignments repeat (parts of) a read already present in the file
+#[inline(always)]
+fn is_primary_alignment(flag: u16) -> bool {
+    flag & (FLAG_SECONDARY | FLAG_SUPPLEMENTARY) == 0
+}
+
+/// Restores the original orientation of a normalized sequence stored as reverse complemented
+fn reverse_complement(seq: &mut [u8]) {
+    seq.reverse();
+    for base in seq.iter_mut() {
+        *base = match *base {
+            b'A' => b'T',
+            b'C' => b'G',
+            b'G' => b'C',
+            b'T' => b'A',
+            // The IUPAC codes are kept when they are expanded
+            b'R' => b'Y',
+            b'Y' => b'R',
+            b'K' => b'M',
+            b'M' => b'K',
+            b'B' => b'V',
+            b'V' => b'B',
+            b'D' => b'H',
+            b'H' => b'D',
+            b'S' => b'S',
+            b'W' => b'W',
+            _ => b'N',
+        };
+    }
+}
+
+struct AlignedRead {
+    ident: Vec<u8>,
+    seq: Vec<u8>,
+}
+
+impl AlignedRead {
+    fn new() -> Self {
+        Self {
+            ident: Vec::new(),
+            seq: Vec::new(),
+        }
+    }
+
+    fn emit(&mut self, flag: u16, func: &mut impl FnMut(FastaSequence)) {
+        normalize_sequence(&mut self.seq);
+        if flag & FLAG_REVERSE_COMPLEMENTED != 0 {
+            reverse_complement(&mut self.seq);
+        }
+        func(FastaSequence {
+            ident: &self.ident,
+            seq: &self.seq,
+            qual: None,
+        });
+    }
+}
+
+pub(crate) struct SamParser {
+    partial_line: Vec<u8>,
+    read: AlignedRead,
+    record: usize,
+}
+
+impl SamParser {
+    pub(crate) fn new() -> Self {
+        Self {
+            partial_line: Vec::new(),
+            read: AlignedRead::new(),
+            record: 0,
+        }
+    }
+
+    pub(crate) fn process_line(
+        &mut self,
+        line: &[u8],
+        partial: bool,
+        _finished: bool,
+        func: &mut impl FnMut(FastaSequence),
+        position: &mut ParsingPosition,
+    ) {
+        // A line without newline at the end of the file is processed when finished is set
+        if partial || self.partial_line.len() > 0 {
+            self.partial_line.extend_from_slice(line);
+            if partial {
+                return;
+            }
+
+            let line = take(&mut self.partial_line);
+            self.process_record(&line, func, position);
+            self.partial_line = line;
+            self.partial_line.clear();
+        } else {
+            self.process_record(line, func, position);
+        }
+    }
+
+    fn process_record(
+        &mut self,
+        record: &[u8],
+        func: &mut impl FnMut(FastaSequence),
+        position: &mut ParsingPosition,
+    ) {
+        // Header lines
+        if record.len() == 0 || record[0] == b'@' {
+            return;
+        }
+        self.record += 1;
+
+        let mut fields = record.split(|c| *c == b'\t');
+        let (name, flag, seq) = match (fields.next(), fields.next(), fields.nth(7)) {
+            (Some(name), Some(flag), Some(seq)) => (name, flag, seq),
+            _ => {
+                position.report_malformation(self.record, "expected at least 10 fields");
+                return;
+            }
+        };
+
+        let flag = match std::str::from_utf8(flag)
+            .ok()
+            .and_then(|flag| flag.parse().ok())
+        {
+            Some(flag) => flag,
+            None => {
+                position.report_malformation(self.record, "invalid flag");
+                return;
+            }
+        };
+
+        if !is_primary_alignment(flag) || seq == b"*" {
+            return;
+        }
+
+        self.read.ident.clear();
+        self.read.ident.push(b'@');
+        self.read.ident.extend_from_slice(name);
+        self.read.seq.clear();
+        self.read.seq.extend_from_slice(seq);
+        self.read.emit(flag, func);
+    }
+}
+
+/// Bases encoded as 4-bit values in BAM records, `=ACMGRSVTWYHKDBN`. Ambiguous ones are mapped to N
+const BAM_BASES: [u8; 16] = *b"NACNGNNNTNNNNNNN";
+
+const BAM_RECORD_FIXED_SIZE: usize = 32;
+
+pub(crate) struct BamParser {
+    buffer: Vec<u8>,
+    header_parsed: bool,
+    read: AlignedRead,
+    record: usize,
+}
+
+impl BamParser {
+    pub(crate) fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            header_parsed: false,
+            read: AlignedRead::new(),
+            record: 0,
+        }
+    }
+
+    /// Returns the length of the BAM header (magic, text and references), if it is fully contained in `data`
+    fn header_length(data: &[u8]) -> Option<usize> {
+        let read_length = |position: usize| {
+            data.get(position..position + 4)
+                .map(|bytes| LittleEndian::read_u32(bytes) as usize)
+        };
+
+        let mut position = 8 + read_length(4)?;
+        let references_count = read_length(position)?;
+        position += 4;
+
+        for _ in 0..references_count {
+            position += 4 + read_length(position)? + 4;
+        }
+
+        if position > data.len() {
+            return None;
+        }
+        Some(position)
+    }
+
+    /// Processes the decompressed BAM data, `data` is empty at the end of the file
+    pub(crate) fn process_data(
+        &mut self,
+        data: &[u8],
+        func: &mut impl FnMut(FastaSequence),
+        parsing_position: &mut ParsingPosition,
+    ) {
+        let finished = data.len() == 0;
+        self.buffer.extend_from_slice(data);
+
+        let buffer = take(&mut self.buffer);
+        let mut position = 0;
+
+        if !self.header_parsed {
+            match Self::header_length(&buffer) {
+                Some(length) => {
+                    position = length;
+                    self.header_parsed = true;
+                }
+                None => {
+                    if finished {
+                        parsing_position.report_malformation(1, "truncated BAM header");
+                    }
+                    self.buffer = buffer;
+                    return;
+                }
+            }
+        }
+
+        while let Some(block_size) = buffer.get(position..position + 4) {
+            let record_end = position + 4 + LittleEndian::read_u32(block_size) as usize;
+            if record_end > buffer.len() {
+                break;
+            }
+            self.process_record(&buffer[position + 4..record_end], func, parsing_position);
+            position = record_end;
+        }
+
+        if finished && position < buffer.len() {
+            parsing_position
+                .report_malformation(self.record + 1, "truncated record at the end of the file");
+        }
+
+        self.buffer = buffer;
+        self.buffer.drain(..position);
+    }
+
+    fn process_record(
+        &mut self,
+        record: &[u8],
+        func: &mut impl FnMut(FastaSequence),
+        position: &mut ParsingPosition,
+    ) {
+        self.record += 1;
+        if record.len() < BAM_RECORD_FIXED_SIZE {
+            position.report_malformation(self.record, "record shorter than its fixed fields");
+            return;
+        }
+
+        let name_length = record[8] as usize;
+        let cigar_ops_count = LittleEndian::read_u16(&record[12..14]) as usize;
+        let flag = LittleEndian::read_u16(&record[14..16]);
+        let seq_length = LittleEndian::read_u32(&record[16..20]) as usize;
+
+        let name_end = BAM_RECORD_FIXED_SIZE + name_length;
+        let seq_start = name_end + cigar_ops_count * 4;
+        let seq_end = seq_start + (seq_length + 1) / 2;
+
+        if seq_end > record.len() {
+            position.report_malformation(self.record, "sequence past the end of the record");
+            return;
+        }
+
+        if !is_primary_alignment(flag) || seq_length == 0 {
+            return;
+        }
+
+        // The read name is NUL terminated
+        let name = &record[BAM_RECORD_FIXED_SIZE..name_end];
+        let name = name.strip_suffix(&[0]).unwrap_or(name);
+
+        self.read.ident.clear();
+        self.read.ident.push(b'@');
+        self.read.ident.extend_from_slice(name);
+
+        self.read.seq.clear();
+        self.read.seq.extend(
+            record[seq_start..seq_end]
+                .iter()
+                .flat_map(|pair| {
+                    [
+                        BAM_BASES[(pair >> 4) as usize],
+                        BAM_BASES[(pair & 0xF) as usize],
+                    ]
+                })
+                .take(seq_length),
+        );
+        self.read.emit(flag, func);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::alignment_parsers::{reverse_complement, BamParser, SamParser};
+    use crate::sequences_reader::ParsingPosition;
+    use byteorder::{LittleEndian, WriteBytesExt};
+    use std::io::Write;
+    use std::path::Path;
+
+    fn collect_sequences(process: impl FnOnce(&mut dyn FnMut(&[u8], &[u8]))) -> Vec<String> {
+        let mut sequences = Vec::new();
+        process(&mut |ident, seq| {
+            sequences.push(format!(
+                "{} {}",
+                std::str::from_utf8(ident).unwrap(),
+                std::str::from_utf8(seq).unwrap()
+            ))
+        });
+        sequences
+    }
+
+    fn parse_sam(sam: &[&str], position: &mut ParsingPosition) -> Vec<String> {
+        collect_sequences(|func| {
+            let mut parser = SamParser::new();
+            let mut func = |seq: crate::sequences_reader::FastaSequence| func(seq.ident, seq.seq);
+            for line in sam {
+                // Split the lines to check the partial lines handling
+                let (first, second) = line.split_at(line.len() / 2);
+                parser.process_line(first.as_bytes(), true, false, &mut func, position);
+                parser.process_line(second.as_bytes(), false, false, &mut func, position);
+            }
+            parser.process_line(&[], false, true, &mut func, position);
+        })
+    }
+
+    #[test]
+    fn sam_parsing() {
+        let sam = [
+            "@HD\tVN:1.6\tSO:unsorted",
+            "read1\t4\t*\t0\t0\t*\t*\t0\t0\tACGTN\tIIIII",
+            "read2\t20\t*\t0\t0\t*\t*\t0\t0\tAACGR\tIIIII",
+            "read3\t260\t*\t0\t0\t*\t*\t0\t0\tACGTA\tIIIII",
+            "read4\t4\t*\t0\t0\t*\t*\t0\t0\t*\t*",
+        ];
+
+        let mut position = ParsingPosition::new(Path::new("test.sam"), 1);
+        let sequences = parse_sam(&sam, &mut position);
+
+        assert_eq!(sequences, ["@read1 ACGTN", "@read2 NCGTT"]);
+        assert!(!position.malformation_reported);
+    }
+
+    #[test]
+    fn iupac_reverse_complement() {
+        let mut seq = b"ACGTRYKMBVDHSWN".to_vec();
+        reverse_complement(&mut seq);
+        assert_eq!(seq, b"NWSDHBVKMRYACGT");
+    }
+
+    #[test]
+    fn sam_malformed_records() {
+        let sam = [
+            "@HD\tVN:1.6\tSO:unsorted",
+            "read1\t4\t*\t0\t0\t*\t*\t0\t0\tACGT\tIIII",
+            "read2\t4\t*\t0\t0\t*",
+            "read3\tx\t*\t0\t0\t*\t*\t0\t0\tACGT\tIIII",
+            "read4\t20\t*\t0\t0\t*\t*\t0\t0\tAACG\tIIII",
+        ];
+
+        let mut position = ParsingPosition::new(Path::new("test.sam"), 1);
+        let sequences = parse_sam(&sam, &mut position);
+
+        assert_eq!(sequences, ["@read1 ACGT", "@read4 CGTT"]);
+        assert!(position.malformation_reported);
+    }
+
+    fn bam_header() -> Vec<u8> {
+        let mut bam = Vec::new();
+        bam.write_all(b"BAM\x01").unwrap();
+        bam.write_u32::<LittleEndian>(3).unwrap();
+        bam.write_all(b"@CO").unwrap();
+        bam.write_u32::<LittleEndian>(1).unwrap();
+        bam.write_u32::<LittleEndian>(5).unwrap();
+        bam.write_all(b"chr1\0").unwrap();
+        bam.write_u32::<LittleEndian>(1000).unwrap();
+        bam
+    }
+
+    fn write_bam_record(bam: &mut Vec<u8>, name: &str, flag: u16, seq_length: u32, seq: &[u8]) {
+        let mut record = Vec::new();
+        record.write_i32::<LittleEndian>(-1).unwrap();
+        record.write_i32::<LittleEndian>(-1).unwrap();
+        record.write_u8(name.len() as u8 + 1).unwrap();
+        record.write_u8(0).unwrap();
+        record.write_u16::<LittleEndian>(4680).unwrap();
+        record.write_u16::<LittleEndian>(0).unwrap();
+        record.write_u16::<LittleEndian>(flag).unwrap();
+        record.write_u32::<LittleEndian>(seq_length).unwrap();
+        record.write_i32::<LittleEndian>(-1).unwrap();
+        record.write_i32::<LittleEndian>(-1).unwrap();
+        record.write_i32::<LittleEndian>(0).unwrap();
+        record.write_all(name.as_bytes()).unwrap();
+        record.write_u8(0).unwrap();
+        record.write_all(seq).unwrap();
+        record.write_all(&vec![30; seq_length as usize]).unwrap();
+
+        bam.write_u32::<LittleEndian>(record.len() as u32).unwrap();
+        bam.write_all(&record).unwrap();
+    }
+
+    fn parse_bam(bam: &[u8], position: &mut ParsingPosition) -> Vec<String> {
+        collect_sequences(|func| {
+            let mut parser = BamParser::new();
+            let mut func = |seq: crate::sequences_reader::FastaSequence| func(seq.ident, seq.seq);
+            for chunk in bam.chunks(7) {
+                parser.process_data(chunk, &mut func, position);
+            }
+            parser.process_data(&[], &mut func, position);
+        })
+    }
+
+    #[test]
+    fn bam_parsing() {
+        let mut bam = bam_header();
+        write_bam_record(&mut bam, "read1", 4, 5, &[0x12, 0x48, 0xF0]);
+        write_bam_record(&mut bam, "read2", 16, 5, &[0x11, 0x24, 0x80]);
+        write_bam_record(&mut bam, "read3", 2048, 5, &[0x12, 0x48, 0xF0]);
+
+        let mut position = ParsingPosition::new(Path::new("test.bam"), 0);
+        let sequences = parse_bam(&bam, &mut position);
+
+        assert_eq!(sequences, ["@read1 ACGTN", "@read2 ACGTT"]);
+        assert!(!position.malformation_reported);
+    }
+
+    #[test]
+    fn bam_malformed_records() {
+        let mut bam = bam_header();
+        write_bam_record(&mut bam, "read1", 4, 5, &[0x12, 0x48, 0xF0]);
+        // Record shorter than the fixed fields
+        bam.write_u32::<LittleEndian>(8).unwrap();
+        bam.write_all(&[0; 8]).unwrap();
+        // Sequence past the end of the record, cut after its first byte
+        let record_start = bam.len();
+        write_bam_record(&mut bam, "read2", 4, 5, &[0x12, 0x48, 0xF0]);
+        bam.truncate(bam.len() - 7);
+        let record_length = (bam.len() - record_start - 4) as u32;
+        bam[record_start..record_start + 4].copy_from_slice(&record_length.to_le_bytes());
+        write_bam_record(&mut bam, "read3", 16, 5, &[0x11, 0x24, 0x80]);
+        // Truncated record at the end of the file
+        write_bam_record(&mut bam, "read4", 4, 5, &[0x12, 0x48, 0xF0]);
+        bam.truncate(bam.len() - 10);
+
+        let mut position = ParsingPosition::new(Path::new("test.bam"), 0);
+        let sequences = parse_bam(&bam, &mut position);
+
+        assert_eq!(sequences, ["@read1 ACGTN", "@read3 ACGTT"]);
+        assert!(position.malformation_reported);
+    }
+}
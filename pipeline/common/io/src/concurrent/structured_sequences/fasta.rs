@@ -1,7 +1,6 @@
 use crate::concurrent::structured_sequences::{IdentSequenceWriter, StructuredSequenceBackend};
 use config::{DEFAULT_OUTPUT_BUFFER_SIZE, DEFAULT_PER_CPU_BUFFER_SIZE};
-use flate2::write::GzEncoder;
-use flate2::Compression;
+use libdeflate_rs::parallel::bgzf_writer::BgzfWriter;
 use lz4::{BlockMode, BlockSize, ContentChecksum};
 use std::fs::File;
 use std::io::{BufWriter, Write};
@@ -27,10 +26,11 @@ unsafe impl<ColorInfo: IdentSequenceWriter, LinksInfo: IdentSequenceWriter> Sync
 impl<ColorInfo: IdentSequenceWriter, LinksInfo: IdentSequenceWriter>
     FastaWriter<ColorInfo, LinksInfo>
 {
+    /// The output is written in the BGZF format, compressing its blocks with multiple threads
     pub fn new_compressed_gzip(path: impl AsRef<Path>, level: u32) -> Self {
-        let compress_stream = GzEncoder::new(
+        let compress_stream = BgzfWriter::new(
             BufWriter::with_capacity(DEFAULT_OUTPUT_BUFFER_SIZE, File::create(&path).unwrap()),
-            Compression::new(level),
+            level,
         );
 
         FastaWriter {
@@ -65,6 +65,24 @@ impl<ColorInfo: IdentSequenceWriter, LinksInfo: IdentSequenceWriter>
         }
     }
 
+    pub fn new_compressed_zstd(path: impl AsRef<Path>, level: i32) -> Self {
+        let compress_stream = zstd::stream::write::Encoder::new(
+            BufWriter::with_capacity(DEFAULT_OUTPUT_BUFFER_SIZE, File::create(&path).unwrap()),
+            level,
+        )
+        .unwrap()
+        .auto_finish();
+
+        FastaWriter {
+            writer: Box::new(BufWriter::with_capacity(
+                DEFAULT_OUTPUT_BUFFER_SIZE,
+                compress_stream,
+            )),
+            path: path.as_ref().to_path_buf(),
+            _phantom: PhantomData,
+        }
+    }
+
     pub fn new_plain(path: impl AsRef<Path>) -> Self {
         FastaWriter {
             writer: Box::new(BufWriter::with_capacity(
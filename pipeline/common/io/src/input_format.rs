@@ -0,0 +1,149 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Compression of an input file, detected from its magic bytes
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CompressionFormat {
+    None,
+    /// Also covers bgzip files, as they are made of multiple gzip members
+    Gzip,
+    Lz4,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+impl CompressionFormat {
+    /// Number of bytes needed to detect the compression format
+    pub const MAGIC_LENGTH: usize = 6;
+
+    pub fn detect(header: &[u8]) -> Self {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Self::Gzip
+        } else if header.starts_with(&[0x04, 0x22, 0x4d, 0x18]) {
+            Self::Lz4
+        } else if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Self::Zstd
+        } else if header.starts_with(b"BZh") {
+            Self::Bzip2
+        } else if header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Self::Xz
+        } else {
+            Self::None
+        }
+    }
+
+    /// Detects the compression of a file, reading its first bytes
+    pub fn detect_file(path: impl AsRef<Path>) -> Self {
+        let mut header = Vec::with_capacity(Self::MAGIC_LENGTH);
+        if let Ok(file) = File::open(path) {
+            let _ = file
+                .take(Self::MAGIC_LENGTH as u64)
+                .read_to_end(&mut header);
+        }
+        Self::detect(&header)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::None => "uncompressed",
+            Self::Gzip => "gzip",
+            Self::Lz4 => "lz4",
+            Self::Zstd => "zstd",
+            Self::Bzip2 => "bzip2",
+            Self::Xz => "xz",
+        }
+    }
+}
+
+/// Format of the (decompressed) sequences of an input file
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SequencesFormat {
+    Fasta,
+    Fastq,
+    Sam,
+    Bam,
+}
+
+impl SequencesFormat {
+    pub const BAM_MAGIC: &'static [u8] = b"BAM\x01";
+
+    /// Detects the binary formats from the first bytes of the decompressed data
+    pub fn detect_binary(header: &[u8]) -> Option<Self> {
+        if header.starts_with(Self::BAM_MAGIC) {
+            Some(Self::Bam)
+        } else {
+            None
+        }
+    }
+
+    /// Detects the text formats from the first non empty line
+    pub fn detect(first_line: &[u8]) -> Option<Self> {
+        match first_line.first()? {
+            b'>' | b';' => Some(Self::Fasta),
+            // SAM header lines start with a two letters record type, as @HD or @SQ
+            b'@' if first_line.len() > 3
+                && first_line[1..3].iter().all(|c| c.is_ascii_alphabetic())
+                && first_line[3] == b'\t' =>
+            {
+                Some(Self::Sam)
+            }
+            b'@' => Some(Self::Fastq),
+            // SAM files without header start with an alignment, made of at least 11 fields
+            _ if first_line.iter().filter(|c| **c == b'\t').count() >= 10 => Some(Self::Sam),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input_format::{CompressionFormat, SequencesFormat};
+
+    #[test]
+    fn formats_detection() {
+        assert_eq!(
+            CompressionFormat::detect(&[0x1f, 0x8b, 0x08, 0x04]),
+            CompressionFormat::Gzip
+        );
+        assert_eq!(
+            CompressionFormat::detect(&[0x04, 0x22, 0x4d, 0x18]),
+            CompressionFormat::Lz4
+        );
+        assert_eq!(
+            CompressionFormat::detect(&[0x28, 0xb5, 0x2f, 0xfd]),
+            CompressionFormat::Zstd
+        );
+        assert_eq!(CompressionFormat::detect(b"BZh9"), CompressionFormat::Bzip2);
+        assert_eq!(
+            CompressionFormat::detect(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]),
+            CompressionFormat::Xz
+        );
+        assert_eq!(CompressionFormat::detect(b">seq"), CompressionFormat::None);
+        assert_eq!(CompressionFormat::detect(b""), CompressionFormat::None);
+
+        assert_eq!(
+            SequencesFormat::detect(b">seq1"),
+            Some(SequencesFormat::Fasta)
+        );
+        assert_eq!(
+            SequencesFormat::detect(b"@read1"),
+            Some(SequencesFormat::Fastq)
+        );
+        assert_eq!(
+            SequencesFormat::detect(b"@HD\tVN:1.6\tSO:unsorted"),
+            Some(SequencesFormat::Sam)
+        );
+        assert_eq!(
+            SequencesFormat::detect(b"read1\t4\t*\t0\t0\t*\t*\t0\t0\tACGT\t!!!!"),
+            Some(SequencesFormat::Sam)
+        );
+        assert_eq!(SequencesFormat::detect(b"ACGT"), None);
+        assert_eq!(
+            SequencesFormat::detect_binary(b"BAM\x01\x00"),
+            Some(SequencesFormat::Bam)
+        );
+        assert_eq!(SequencesFormat::detect_binary(b">seq"), None);
+    }
+}
@@ -1,12 +1,19 @@
 #![feature(int_log, let_chains)]
 
-use config::{BucketIndexType, MAX_BUCKETS_COUNT_LOG, MAX_BUCKET_SIZE, MIN_BUCKETS_COUNT_LOG};
+use crate::input_format::CompressionFormat;
+use config::{
+    BucketIndexType, MAX_BUCKETS_COUNT_LOG, MAX_BUCKET_SIZE, MIN_BUCKETS_COUNT_LOG,
+    STREAMED_INPUTS_SIZE_HINT,
+};
 use std::cmp::{max, min};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
 
+mod alignment_parsers;
 pub mod chunks_writer;
 pub mod compressed_read;
 pub mod concurrent;
+pub mod input_format;
 pub mod lines_reader;
 // pub mod reads_writer;
 pub mod sequences_reader;
@@ -57,22 +64,48 @@ pub struct FilesStatsInfo {
     // pub best_lz4_compression_level: u32,
 }
 
+/// Path used to read the sequences from the standard input
+pub const STDIN_INPUT_PATH: &str = "-";
+
+pub fn is_stdin_input(file: impl AsRef<Path>) -> bool {
+    file.as_ref().as_os_str() == STDIN_INPUT_PATH
+}
+
+/// Returns the size of an input file, or None if it is streamed from the standard input or from a named pipe
+pub fn get_input_file_size(file: impl AsRef<Path>) -> Option<u64> {
+    if is_stdin_input(&file) {
+        return None;
+    }
+
+    let metadata = std::fs::metadata(&file).expect(&format!(
+        "Error while opening file {}",
+        file.as_ref().display()
+    ));
+
+    if metadata.is_file() {
+        Some(metadata.len())
+    } else {
+        None
+    }
+}
+
 pub fn compute_stats_from_input_files(files: &[PathBuf]) -> FilesStatsInfo {
     // TODO: Improve this ratio estimation
     const COMPRESSED_READS_RATIO: f64 = 0.5;
 
     let mut bases_count = 0;
+    let mut has_streamed_inputs = false;
 
     for file in files {
-        let length = std::fs::metadata(file)
-            .expect(&format!("Error while opening file {}", file.display()))
-            .len();
-
-        let file_bases_count = if file
-            .extension()
-            .map(|x| x == "gz" || x == "lz4")
-            .unwrap_or(false)
-        {
+        let length = match get_input_file_size(file) {
+            Some(length) => length,
+            None => {
+                has_streamed_inputs = true;
+                continue;
+            }
+        };
+
+        let file_bases_count = if CompressionFormat::detect_file(file) != CompressionFormat::None {
             (length as f64 * COMPRESSED_READS_RATIO) as u64
         } else {
             length
@@ -80,6 +113,19 @@ pub fn compute_stats_from_input_files(files: &[PathBuf]) -> FilesStatsInfo {
         bases_count += file_bases_count;
     }
 
+    if has_streamed_inputs {
+        match STREAMED_INPUTS_SIZE_HINT.load(Ordering::Relaxed) {
+            0 => {
+                // The oversized buckets are split again while merging the kmers
+                println!("Warning: the size of the streamed inputs is unknown, using the maximum buckets count");
+                return FilesStatsInfo {
+                    best_buckets_count_log: MAX_BUCKETS_COUNT_LOG,
+                };
+            }
+            size_hint => bases_count += size_hint,
+        }
+    }
+
     let buckets_count = bases_count / MAX_BUCKET_SIZE;
 
     let buckets_log = (max(1, buckets_count) - 1).next_power_of_two().ilog2() as usize;
@@ -92,3 +138,60 @@ pub fn compute_stats_from_input_files(files: &[PathBuf]) -> FilesStatsInfo {
         // best_lz4_compression_level: 0,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{compute_stats_from_input_files, get_input_file_size, STDIN_INPUT_PATH};
+    use config::{
+        MAX_BUCKETS_COUNT_LOG, MAX_BUCKET_SIZE, MIN_BUCKETS_COUNT_LOG, STREAMED_INPUTS_SIZE_HINT,
+    };
+    use std::path::PathBuf;
+    use std::process::Command;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn streamed_inputs_buckets_count() {
+        let inputs = [PathBuf::from(STDIN_INPUT_PATH)];
+
+        // Without a size hint the maximum buckets count is used
+        STREAMED_INPUTS_SIZE_HINT.store(0, Ordering::Relaxed);
+        assert_eq!(
+            compute_stats_from_input_files(&inputs).best_buckets_count_log,
+            MAX_BUCKETS_COUNT_LOG
+        );
+
+        STREAMED_INPUTS_SIZE_HINT.store(MAX_BUCKET_SIZE, Ordering::Relaxed);
+        assert_eq!(
+            compute_stats_from_input_files(&inputs).best_buckets_count_log,
+            MIN_BUCKETS_COUNT_LOG
+        );
+
+        STREAMED_INPUTS_SIZE_HINT.store(MAX_BUCKET_SIZE << 11, Ordering::Relaxed);
+        assert_eq!(
+            compute_stats_from_input_files(&inputs).best_buckets_count_log,
+            11
+        );
+
+        STREAMED_INPUTS_SIZE_HINT.store(0, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn fifo_input_file_size() {
+        let fifo = std::env::temp_dir().join(format!("ggcat_io_fifo_{}", std::process::id()));
+        let file = std::env::temp_dir().join(format!("ggcat_io_file_{}.fa", std::process::id()));
+
+        assert!(Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .unwrap()
+            .success());
+        std::fs::write(&file, ">seq\nACGT\n").unwrap();
+
+        assert_eq!(get_input_file_size(STDIN_INPUT_PATH), None);
+        assert_eq!(get_input_file_size(&fifo), None);
+        assert_eq!(get_input_file_size(&file), Some(10));
+
+        std::fs::remove_file(fifo).unwrap();
+        std::fs::remove_file(file).unwrap();
+    }
+}
@@ -1,11 +1,15 @@
+use crate::input_format::CompressionFormat;
+use crate::{get_input_file_size, is_stdin_input};
 use bstr::ByteSlice;
-use config::DEFAULT_OUTPUT_BUFFER_SIZE;
-use libdeflate_rs::decompress_file_buffered;
+use config::{DEFAULT_OUTPUT_BUFFER_SIZE, PARALLEL_DECOMPRESSION_MEMORY, STRICT_INPUT_PARSING};
+use libdeflate_rs::{decompress_file_parallel, decompress_stream_buffered};
 use parallel_processor::counter_stats::counter::{AtomicCounter, AvgMode, SumMode};
 use parallel_processor::counter_stats::{declare_avg_counter_i64, declare_counter_i64};
+use std::cmp::min;
 use std::fs::File;
-use std::io::Read;
+use std::io::{BufRead, BufReader, ErrorKind, Read};
 use std::path::Path;
+use std::sync::atomic::Ordering;
 
 pub struct LinesReader {
     buffer: Vec<u8>,
@@ -34,10 +38,18 @@ impl LinesReader {
         &mut self,
         mut stream: impl Read,
         mut callback: impl FnMut(&[u8]),
-    ) -> Result<(), ()> {
+    ) -> Result<(), String> {
         COUNTER_THREADS_BUSY_READING.inc();
 
-        while let Ok(count) = stream.read(self.buffer.as_mut_slice()) {
+        loop {
+            let count = match stream.read(self.buffer.as_mut_slice()) {
+                Ok(count) => count,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    COUNTER_THREADS_BUSY_READING.sub(1);
+                    return Err(err.to_string());
+                }
+            };
             COUNTER_THREADS_READ_BYTES.inc_by(count as i64);
             COUNTER_THREADS_READ_BYTES_AVG.add_value(count as i64);
             COUNTER_THREADS_BUSY_READING.sub(1);
@@ -52,52 +64,92 @@ impl LinesReader {
             COUNTER_THREADS_PROCESSING_READS.sub(1);
             COUNTER_THREADS_BUSY_READING.inc();
         }
-        Err(())
     }
 
-    fn read_binary_file(
+    /// Reads the decompressed contents of a file, calling `callback` with an empty buffer at its end
+    pub(crate) fn read_binary_file(
         &mut self,
         path: impl AsRef<Path>,
         mut callback: impl FnMut(&[u8]),
         remove: bool,
     ) {
-        if path.as_ref().extension().filter(|x| *x == "gz").is_some() {
-            if let Err(_err) = decompress_file_buffered(
-                &path,
-                |data| {
-                    callback(data);
+        let input: Box<dyn Read> = if is_stdin_input(&path) {
+            Box::new(std::io::stdin())
+        } else {
+            Box::new(
+                File::open(&path).expect(&format!("Cannot open file {}", path.as_ref().display())),
+            )
+        };
+
+        // Only used to peek the magic bytes, larger reads bypass its buffer
+        let mut file = BufReader::new(input);
+
+        let header = file.fill_buf().unwrap_or(&[]);
+        let compression = CompressionFormat::detect(
+            &header[..min(header.len(), CompressionFormat::MAGIC_LENGTH)],
+        );
+
+        let result = match compression {
+            CompressionFormat::Gzip => {
+                let decompress_callback = |data: &[u8]| -> Result<(), ()> {
+                    // Empty gzip members (as the BGZF end of file marker) must not end the reading
+                    if data.len() > 0 {
+                        callback(data);
+                    }
                     Ok(())
-                },
-                DEFAULT_OUTPUT_BUFFER_SIZE,
-            ) {
+                };
+
+                // Regular files can be decompressed by multiple threads
+                let result = if get_input_file_size(&path).is_some() {
+                    drop(file);
+                    decompress_file_parallel(
+                        &path,
+                        decompress_callback,
+                        DEFAULT_OUTPUT_BUFFER_SIZE,
+                        PARALLEL_DECOMPRESSION_MEMORY.load(Ordering::Relaxed),
+                    )
+                } else {
+                    decompress_stream_buffered(
+                        file,
+                        decompress_callback,
+                        DEFAULT_OUTPUT_BUFFER_SIZE,
+                    )
+                }
+                .map_err(|err| format!("{:?}", err));
+                callback(&[]);
+                result
+            }
+            CompressionFormat::Lz4 => {
+                self.read_stream_buffered(lz4::Decoder::new(file).unwrap(), callback)
+            }
+            CompressionFormat::Zstd => {
+                self.read_stream_buffered(zstd::Decoder::with_buffer(file).unwrap(), callback)
+            }
+            CompressionFormat::Bzip2 => {
+                self.read_stream_buffered(bzip2::read::MultiBzDecoder::new(file), callback)
+            }
+            CompressionFormat::Xz => {
+                self.read_stream_buffered(xz2::read::XzDecoder::new_multi_decoder(file), callback)
+            }
+            CompressionFormat::None => self.read_stream_buffered(file, callback),
+        };
+
+        if let Err(err) = result {
+            if STRICT_INPUT_PARSING.load(Ordering::Relaxed) {
                 println!(
-                    "WARNING: Error while reading file {}",
-                    path.as_ref().display()
+                    "ERROR: Error while reading {} file {}: {}",
+                    compression.name(),
+                    path.as_ref().display(),
+                    err
                 );
+                std::process::exit(1);
             }
-            callback(&[]);
-        } else if path.as_ref().extension().filter(|x| *x == "lz4").is_some() {
-            let file = lz4::Decoder::new(
-                File::open(&path).expect(&format!("Cannot open file {}", path.as_ref().display())),
-            )
-            .unwrap();
-            self.read_stream_buffered(file, callback)
-                .unwrap_or_else(|_| {
-                    println!(
-                        "WARNING: Error while reading file {}",
-                        path.as_ref().display()
-                    );
-                });
-        } else {
-            let file =
-                File::open(&path).expect(&format!("Cannot open file {}", path.as_ref().display()));
-            self.read_stream_buffered(file, callback)
-                .unwrap_or_else(|_| {
-                    println!(
-                        "WARNING: Error while reading file {}",
-                        path.as_ref().display()
-                    );
-                });
+            println!(
+                "WARNING: Error while reading {} file {}: {}, its sequences may be incomplete",
+                compression.name(),
+                path.as_ref().display(),
+                err
+            );
         }
 
         if remove {
@@ -134,6 +186,42 @@ impl LinesReader {
         }
     }
 
+    /// Splits the data read from a file into lines, calling `callback` with each of them.
+    /// `line_pending` keeps track of a line continuing in the next buffer
+    pub(crate) fn split_lines(
+        file: &Path,
+        mut buffer: &[u8],
+        line_pending: &mut bool,
+        callback: &mut impl FnMut(
+            &[u8],
+            bool, /* partial (line continues on next call) */
+            bool, /* finished (last line) */
+        ),
+    ) {
+        // File finished
+        if buffer.len() == 0 {
+            if *line_pending {
+                eprintln!("WARNING: No newline at ending of file '{}'", file.display());
+            }
+            callback(&[], false, true);
+            return;
+        }
+
+        loop {
+            let (full, line) = Self::split_line(&mut buffer);
+
+            if full {
+                callback(line, false, false);
+            } else {
+                *line_pending = line.len() > 0;
+                if *line_pending {
+                    callback(line, true, false);
+                }
+                break;
+            }
+        }
+    }
+
     pub fn process_lines(
         &mut self,
         file: impl AsRef<Path>,
@@ -148,34 +236,85 @@ impl LinesReader {
 
         self.read_binary_file(
             file.as_ref(),
-            |mut buffer: &[u8]| {
-                // File finished
-                if buffer.len() == 0 {
-                    if line_pending {
-                        eprintln!(
-                            "WARNING: No newline at ending of file '{}'",
-                            file.as_ref().display()
-                        );
-                    }
-                    callback(&[], false, true);
-                    return;
-                }
-
-                loop {
-                    let (full, line) = Self::split_line(&mut buffer);
-
-                    if full {
-                        callback(line, false, false);
-                    } else {
-                        line_pending = line.len() > 0;
-                        if line_pending {
-                            callback(line, true, false);
-                        }
-                        break;
-                    }
-                }
+            |buffer: &[u8]| {
+                Self::split_lines(file.as_ref(), buffer, &mut line_pending, &mut callback)
             },
             remove,
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::sequences_reader::SequencesReader;
+    use std::io::Write;
+
+    #[test]
+    fn compressed_files_reading() {
+        let records = [
+            (">seq1", "ACGTACGT"),
+            (">seq2", "GGNTA"),
+            (">seq3", "TTTTCCCCAAAAGGGG"),
+        ];
+
+        let mut fasta = Vec::new();
+        for (ident, seq) in records {
+            writeln!(fasta, "{}\n{}", ident, seq).unwrap();
+        }
+
+        // Each file is made of two concatenated streams
+        let (first_part, second_part) = fasta.split_at(20);
+        let compress: [(&str, fn(&[u8]) -> Vec<u8>); 3] = [
+            ("zstd", |data| zstd::encode_all(data, 3).unwrap()),
+            ("bzip2", |data| {
+                let mut encoder =
+                    bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }),
+            ("xz", |data| {
+                let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }),
+        ];
+
+        for (name, compress) in compress {
+            // No extension, the compression is detected from the file contents
+            let file = std::env::temp_dir().join(format!(
+                "ggcat_lines_reader_{}_{}",
+                name,
+                std::process::id()
+            ));
+
+            let mut data = compress(first_part);
+            data.extend(compress(second_part));
+            std::fs::write(&file, data).unwrap();
+
+            let mut sequences = Vec::new();
+            SequencesReader::new().process_file_extended(
+                &file,
+                |seq| {
+                    sequences.push((
+                        String::from_utf8(seq.ident.to_vec()).unwrap(),
+                        String::from_utf8(seq.seq.to_vec()).unwrap(),
+                    ))
+                },
+                None,
+                true,
+                true,
+            );
+
+            assert_eq!(
+                sequences,
+                records
+                    .iter()
+                    .map(|(ident, seq)| (ident.to_string(), seq.to_string()))
+                    .collect::<Vec<_>>(),
+                "{}",
+                name
+            );
+            assert!(!file.exists());
+        }
+    }
+}
@@ -1,8 +1,7 @@
 use crate::sequences_reader::FastaSequence;
 use byteorder::WriteBytesExt;
 use config::DEFAULT_OUTPUT_BUFFER_SIZE;
-use flate2::write::GzEncoder;
-use flate2::Compression;
+use libdeflate_rs::parallel::bgzf_writer::BgzfWriter;
 use lz4::{BlockMode, BlockSize, ContentChecksum};
 use std::fs::File;
 use std::io::{BufWriter, Write};
@@ -11,7 +10,7 @@ use std::path::{Path, PathBuf};
 enum WriterChannels {
     None,
     File(BufWriter<File>),
-    CompressedFileGzip(BufWriter<GzEncoder<BufWriter<File>>>),
+    CompressedFileGzip(BufWriter<BgzfWriter<BufWriter<File>>>),
     CompressedFileLZ4(BufWriter<lz4::Encoder<BufWriter<File>>>),
 }
 
@@ -34,9 +33,9 @@ pub struct ReadsWriter {
 
 impl ReadsWriter {
     pub fn new_compressed_gzip(path: impl AsRef<Path>, level: u32) -> ReadsWriter {
-        let compress_stream = GzEncoder::new(
+        let compress_stream = BgzfWriter::new(
             BufWriter::with_capacity(DEFAULT_OUTPUT_BUFFER_SIZE, File::create(&path).unwrap()),
-            Compression::new(level),
+            level,
         );
 
         ReadsWriter {
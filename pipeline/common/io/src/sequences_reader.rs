@@ -1,17 +1,17 @@
+use crate::alignment_parsers::{BamParser, SamParser};
+use crate::input_format::SequencesFormat;
 use crate::lines_reader::LinesReader;
-use config::DEFAULT_OUTPUT_BUFFER_SIZE;
+use config::{DEFAULT_OUTPUT_BUFFER_SIZE, IUPAC_EXPANSION_MAX_BASES, STRICT_INPUT_PARSING};
 use nightly_quirks::branch_pred::unlikely;
 use std::cmp::max;
+use std::mem::take;
 use std::path::Path;
+use std::sync::atomic::Ordering;
 
 const IDENT_STATE: usize = 0;
 const SEQ_STATE: usize = 1;
 const QUAL_STATE: usize = 2;
-
-enum FileType {
-    Fasta,
-    Fastq,
-}
+const PLUS_STATE: usize = 3;
 
 #[derive(Copy, Clone)]
 pub struct FastaSequence<'a> {
@@ -33,199 +33,558 @@ const SEQ_LETTERS_MAPPING: [u8; 256] = {
     lookup
 };
 
+/// Bases compatible with each IUPAC code (or with a base itself), empty for the other characters
+pub const fn iupac_compatible_bases(code: u8) -> &'static [u8] {
+    match code {
+        b'A' => b"A",
+        b'C' => b"C",
+        b'G' => b"G",
+        b'T' => b"T",
+        b'R' => b"AG",
+        b'Y' => b"CT",
+        b'S' => b"CG",
+        b'W' => b"AT",
+        b'K' => b"GT",
+        b'M' => b"AC",
+        b'B' => b"CGT",
+        b'D' => b"AGT",
+        b'H' => b"ACT",
+        b'V' => b"ACG",
+        b'N' => b"ACGT",
+        _ => b"",
+    }
+}
+
+/// As `SEQ_LETTERS_MAPPING`, but keeping the IUPAC ambiguity codes so that they can be expanded
+const SEQ_LETTERS_MAPPING_IUPAC: [u8; 256] = {
+    let mut lookup = [b'N'; 256];
+    let mut index = 0;
+    while index < 256 {
+        let code = (index as u8).to_ascii_uppercase();
+        if !iupac_compatible_bases(code).is_empty() {
+            lookup[index] = code;
+        }
+        index += 1;
+    }
+    lookup
+};
+
 pub struct SequencesReader {
     lines_reader: LinesReader,
+    get_quality: bool,
 }
 
 impl SequencesReader {
     pub fn new() -> Self {
-        Self {
-            lines_reader: LinesReader::new(),
-        }
+        Self::with_quality(false)
     }
 
-    fn normalize_sequence(seq: &mut [u8]) {
-        for el in seq.iter_mut() {
-            *el = SEQ_LETTERS_MAPPING[*el as usize];
+    /// Creates a reader that also returns the quality lines of the FASTQ records
+    pub fn with_quality(get_quality: bool) -> Self {
+        Self {
+            lines_reader: LinesReader::new(),
+            get_quality,
         }
     }
 
+    /// Reads a FASTA, FASTQ, SAM or (unaligned) BAM file, optionally compressed. Both the compression
+    /// and the sequences format are detected from the file contents, regardless of its name
     pub fn process_file_extended<F: FnMut(FastaSequence)>(
         &mut self,
         source: impl AsRef<Path>,
-        func: F,
+        mut func: F,
         line_split_copyback: Option<usize>,
         copy_ident: bool,
         remove_file: bool,
     ) {
-        const FASTQ_EXTS: &[&str] = &["fq", "fastq"];
-        const FASTA_EXTS: &[&str] = &["fa", "fasta", "fna", "ffn"];
+        let source = source.as_ref();
 
-        let mut file_type = None;
-        let mut tmp = source.as_ref().file_name().unwrap().to_str().unwrap();
-        let mut path: &Path = tmp.as_ref();
+        let mut header = Vec::new();
+        let mut is_binary = None;
+        let mut line_pending = false;
+        let mut bam_parser = BamParser::new();
+        let mut bam_position = ParsingPosition::new(source, 0);
+        let mut text_parser =
+            TextParser::new(source, line_split_copyback, copy_ident, self.get_quality);
 
-        while let Some(ext) = path.extension() {
-            if FASTQ_EXTS.contains(&ext.to_str().unwrap()) {
-                file_type = Some(FileType::Fastq);
-                break;
+        let mut process_data = |data: &[u8], is_binary: bool| {
+            if is_binary {
+                bam_parser.process_data(data, &mut func, &mut bam_position);
+            } else {
+                LinesReader::split_lines(
+                    source,
+                    data,
+                    &mut line_pending,
+                    &mut |line, partial, finished| {
+                        text_parser.process_line(line, partial, finished, &mut func)
+                    },
+                );
             }
-            if FASTA_EXTS.contains(&ext.to_str().unwrap()) {
-                file_type = Some(FileType::Fasta);
-                break;
-            }
-            tmp = &tmp[0..tmp.len() - ext.len() - 1];
-            path = tmp.as_ref()
-        }
+        };
 
-        match file_type {
-            None => panic!(
-                "Cannot recognize file type of '{}'",
-                source.as_ref().display()
-            ),
-            Some(ftype) => match ftype {
-                FileType::Fasta => {
-                    self.process_fasta(source, func, line_split_copyback, copy_ident, remove_file);
-                }
-                FileType::Fastq => {
-                    self.process_fastq(source, func, false, remove_file);
+        self.lines_reader.read_binary_file(
+            source,
+            |data: &[u8]| match is_binary {
+                Some(is_binary) => process_data(data, is_binary),
+                None => {
+                    // Wait for enough data to recognize the binary formats
+                    header.extend_from_slice(data);
+                    if header.len() < SequencesFormat::BAM_MAGIC.len() && data.len() > 0 {
+                        return;
+                    }
+
+                    let binary = SequencesFormat::detect_binary(&header).is_some();
+                    is_binary = Some(binary);
+                    if header.len() > 0 {
+                        process_data(&take(&mut header), binary);
+                    }
+                    if data.len() == 0 {
+                        process_data(&[], binary);
+                    }
                 }
             },
+            remove_file,
+        );
+    }
+}
+
+/// Position in an input file, to report where its first malformed record is
+pub(crate) struct ParsingPosition<'a> {
+    source: &'a Path,
+    /// Number of the line being parsed, starting from 1, or 0 for the binary formats
+    line: usize,
+    pub(crate) malformation_reported: bool,
+}
+
+impl<'a> ParsingPosition<'a> {
+    pub(crate) fn new(source: &'a Path, line: usize) -> Self {
+        Self {
+            source,
+            line,
+            malformation_reported: false,
         }
     }
 
-    fn process_fasta(
-        &mut self,
-        source: impl AsRef<Path>,
-        mut func: impl FnMut(FastaSequence),
+    /// Reports a malformed record, failing if the strict parsing is enabled.
+    /// Otherwise the record is skipped, warning only about the first one of each file
+    pub(crate) fn report_malformation(&mut self, record: usize, message: &str) {
+        let description = if self.line > 0 {
+            format!(
+                "file '{}', record {} (line {}): {}",
+                self.source.display(),
+                record,
+                self.line,
+                message
+            )
+        } else {
+            format!(
+                "file '{}', record {}: {}",
+                self.source.display(),
+                record,
+                message
+            )
+        };
+
+        if STRICT_INPUT_PARSING.load(Ordering::Relaxed) {
+            println!("ERROR: Malformed input in {}", description);
+            std::process::exit(1);
+        }
+
+        if !self.malformation_reported {
+            self.malformation_reported = true;
+            println!(
+                "WARNING: Malformed input in {}, skipping the malformed records",
+                description
+            );
+        }
+    }
+}
+
+struct TextParser<'a> {
+    position: ParsingPosition<'a>,
+    line_pending: bool,
+    file_type: Option<SequencesFormat>,
+    /// The file type cannot be recognized, so all its lines are skipped
+    unrecognized: bool,
+    fasta_parser: FastaParser,
+    fastq_parser: FastqParser,
+    sam_parser: SamParser,
+}
+
+impl<'a> TextParser<'a> {
+    fn new(
+        source: &'a Path,
         line_split_copyback: Option<usize>,
         copy_ident: bool,
-        remove_file: bool,
+        get_quality: bool,
+    ) -> Self {
+        Self {
+            position: ParsingPosition::new(source, 1),
+            line_pending: false,
+            file_type: None,
+            unrecognized: false,
+            fasta_parser: FastaParser::new(line_split_copyback, copy_ident),
+            fastq_parser: FastqParser::new(get_quality),
+            sam_parser: SamParser::new(),
+        }
+    }
+
+    fn process_line(
+        &mut self,
+        line: &[u8],
+        partial: bool,
+        finished: bool,
+        func: &mut impl FnMut(FastaSequence),
     ) {
-        let mut intermediate = [Vec::new(), Vec::new()];
-        let mut on_comment = false;
-        let mut state = SEQ_STATE;
-        let mut new_line = true;
-
-        let flush_size = max(
-            DEFAULT_OUTPUT_BUFFER_SIZE,
-            line_split_copyback.unwrap_or(0) * 2,
-        );
+        // At the end of the file refer to its last line
+        if finished && !self.line_pending && self.position.line > 1 {
+            self.position.line -= 1;
+        }
 
-        self.lines_reader.process_lines(
-            source,
-            |line: &[u8], partial, finished| {
-                if on_comment {
-                    on_comment = !partial;
+        let ftype = match self.file_type {
+            Some(ftype) => Some(ftype),
+            // Skip the empty lines before the first record
+            None if finished || line.is_empty() || self.unrecognized => None,
+            None => match SequencesFormat::detect(line) {
+                Some(ftype) => Some(*self.file_type.insert(ftype)),
+                None => {
+                    self.position
+                        .report_malformation(1, "cannot recognize the file type");
+                    self.unrecognized = true;
+                    None
                 }
-                // If a new ident line is found (or it's the last line)
-                else if finished || (new_line && line.len() > 0 && line[0] == b'>') {
-                    if intermediate[SEQ_STATE].len() > 0 {
-                        func(FastaSequence {
-                            ident: &intermediate[IDENT_STATE],
-                            seq: &intermediate[SEQ_STATE],
-                            qual: None,
-                        });
-                    }
+            },
+        };
 
-                    if copy_ident {
-                        intermediate[IDENT_STATE].clear();
-                        intermediate[IDENT_STATE].extend_from_slice(line);
-                    }
-                    intermediate[SEQ_STATE].clear();
-
-                    state = if partial { IDENT_STATE } else { SEQ_STATE };
-                } else if new_line && line.len() > 0 && line[0] == b';' {
-                    on_comment = true;
-                } else if state == IDENT_STATE {
-                    if copy_ident {
-                        intermediate[IDENT_STATE].extend_from_slice(line);
-                    }
+        match ftype {
+            Some(SequencesFormat::Fasta) => {
+                self.fasta_parser
+                    .process_line(line, partial, finished, func, &mut self.position)
+            }
+            Some(SequencesFormat::Fastq) => {
+                self.fastq_parser
+                    .process_line(line, partial, finished, func, &mut self.position)
+            }
+            Some(SequencesFormat::Sam) => {
+                self.sam_parser
+                    .process_line(line, partial, finished, func, &mut self.position)
+            }
+            Some(SequencesFormat::Bam) => unreachable!(),
+            None => {}
+        }
 
-                    if !partial {
-                        state = SEQ_STATE;
-                    }
-                } else {
-                    intermediate[SEQ_STATE].extend_from_slice(line);
-                }
+        self.line_pending = partial;
+        if !partial && !finished {
+            self.position.line += 1;
+        }
+    }
+}
 
-                if let Some(copyback) = line_split_copyback &&
-                    (intermediate[SEQ_STATE].len() >= flush_size) {
-                    Self::normalize_sequence(&mut intermediate[SEQ_STATE]);
-                    func(FastaSequence {
-                        ident: &intermediate[IDENT_STATE],
-                        seq: &intermediate[SEQ_STATE],
-                        qual: None,
-                    });
-                    let copy_start = intermediate[SEQ_STATE].len() - copyback;
-                    intermediate[SEQ_STATE].copy_within(copy_start.., 0);
-                    intermediate[SEQ_STATE].truncate(copyback);
-                }
+struct FastaParser {
+    intermediate: [Vec<u8>; 2],
+    on_comment: bool,
+    record: usize,
+    state: usize,
+    new_line: bool,
+    line_split_copyback: Option<usize>,
+    flush_size: usize,
+    copy_ident: bool,
+}
 
-                new_line = !partial;
-            },
-            remove_file,
-        );
+impl FastaParser {
+    fn new(line_split_copyback: Option<usize>, copy_ident: bool) -> Self {
+        Self {
+            intermediate: [Vec::new(), Vec::new()],
+            on_comment: false,
+            record: 0,
+            state: SEQ_STATE,
+            new_line: true,
+            line_split_copyback,
+            flush_size: max(
+                DEFAULT_OUTPUT_BUFFER_SIZE,
+                line_split_copyback.unwrap_or(0) * 2,
+            ),
+            copy_ident,
+        }
     }
 
-    fn process_fastq(
+    fn process_line(
         &mut self,
-        source: impl AsRef<Path>,
-        mut func: impl FnMut(FastaSequence),
-        get_quality: bool,
-        remove_file: bool,
+        line: &[u8],
+        partial: bool,
+        finished: bool,
+        func: &mut impl FnMut(FastaSequence),
+        position: &mut ParsingPosition,
     ) {
-        let mut state = IDENT_STATE;
-        let mut skipped_plus = false;
+        let intermediate = &mut self.intermediate;
 
-        let mut intermediate = [Vec::new(), Vec::new(), Vec::new()];
+        // Skip the rest of a comment (or malformed) line
+        if self.on_comment {
+            self.on_comment = partial;
+        }
+        // If a new ident line is found (or it's the last line)
+        else if finished || (self.new_line && line.len() > 0 && line[0] == b'>') {
+            if intermediate[SEQ_STATE].len() > 0 {
+                normalize_sequence(&mut intermediate[SEQ_STATE]);
+                func(FastaSequence {
+                    ident: &intermediate[IDENT_STATE],
+                    seq: &intermediate[SEQ_STATE],
+                    qual: None,
+                });
+            }
 
-        self.lines_reader.process_lines(
-            source,
-            |line: &[u8], partial, finished| {
-                if unlikely(finished) {
-                    return;
-                }
+            if self.copy_ident {
+                intermediate[IDENT_STATE].clear();
+                intermediate[IDENT_STATE].extend_from_slice(line);
+            }
+            intermediate[SEQ_STATE].clear();
 
-                if state == QUAL_STATE {
-                    if !skipped_plus {
-                        if !partial {
-                            skipped_plus = true;
-                        }
-                        return;
-                    }
+            self.record += 1;
+            self.state = if partial { IDENT_STATE } else { SEQ_STATE };
+        } else if self.new_line && line.len() > 0 && line[0] == b';' {
+            self.on_comment = partial;
+        } else if self.state == IDENT_STATE {
+            if self.copy_ident {
+                intermediate[IDENT_STATE].extend_from_slice(line);
+            }
 
-                    if get_quality {
-                        intermediate[state].extend_from_slice(line);
-                    }
+            if !partial {
+                self.state = SEQ_STATE;
+            }
+        } else if self.new_line && !line.is_empty() && !is_sequence_line_start(line[0]) {
+            position.report_malformation(self.record, "unexpected characters in a sequence line");
+            self.on_comment = partial;
+        } else {
+            intermediate[SEQ_STATE].extend_from_slice(line);
+        }
+
+        if let Some(copyback) = self.line_split_copyback &&
+            (intermediate[SEQ_STATE].len() >= self.flush_size) {
+            normalize_sequence(&mut intermediate[SEQ_STATE]);
+            func(FastaSequence {
+                ident: &intermediate[IDENT_STATE],
+                seq: &intermediate[SEQ_STATE],
+                qual: None,
+            });
+            let copy_start = intermediate[SEQ_STATE].len() - copyback;
+            intermediate[SEQ_STATE].copy_within(copy_start.., 0);
+            intermediate[SEQ_STATE].truncate(copyback);
+        }
+
+        self.new_line = !partial;
+    }
+}
+
+struct FastqParser {
+    intermediate: [Vec<u8>; 3],
+    state: usize,
+    new_line: bool,
+    skipping_line: bool,
+    qual_length: usize,
+    record: usize,
+    get_quality: bool,
+}
+
+impl FastqParser {
+    fn new(get_quality: bool) -> Self {
+        Self {
+            intermediate: [Vec::new(), Vec::new(), Vec::new()],
+            state: IDENT_STATE,
+            new_line: true,
+            skipping_line: false,
+            qual_length: 0,
+            record: 0,
+            get_quality,
+        }
+    }
+
+    fn process_line(
+        &mut self,
+        line: &[u8],
+        partial: bool,
+        finished: bool,
+        func: &mut impl FnMut(FastaSequence),
+        position: &mut ParsingPosition,
+    ) {
+        if unlikely(finished) {
+            // The last quality line can be missing its newline
+            if self.state == QUAL_STATE && !self.new_line {
+                self.complete_record(func, position);
+            } else if self.state != IDENT_STATE || !self.new_line {
+                position
+                    .report_malformation(self.record, "truncated record at the end of the file");
+            }
+            return;
+        }
+
+        let line_start = self.new_line;
+        self.new_line = !partial;
+
+        if self.skipping_line {
+            self.skipping_line = partial;
+            return;
+        }
 
-                    if !partial {
-                        Self::normalize_sequence(&mut intermediate[SEQ_STATE]);
-                        func(FastaSequence {
-                            ident: &intermediate[IDENT_STATE],
-                            seq: &intermediate[SEQ_STATE],
-                            qual: if get_quality {
-                                Some(&intermediate[QUAL_STATE])
-                            } else {
-                                None
-                            },
-                        });
-
-                        intermediate[IDENT_STATE].clear();
-                        intermediate[SEQ_STATE].clear();
-                        intermediate[QUAL_STATE].clear();
-
-                        skipped_plus = false;
+        match self.state {
+            IDENT_STATE => {
+                if line_start {
+                    // Skip the empty lines between the records
+                    if line.is_empty() {
+                        return;
                     }
-                } else {
-                    intermediate[state].extend_from_slice(line);
+                    if line[0] != b'@' {
+                        position.report_malformation(self.record + 1, "expected a '@' header line");
+                        self.skipping_line = partial;
+                        return;
+                    }
+                    self.record += 1;
+                }
+                self.intermediate[IDENT_STATE].extend_from_slice(line);
+            }
+            SEQ_STATE => {
+                self.intermediate[SEQ_STATE].extend_from_slice(line);
+            }
+            PLUS_STATE => {
+                if line_start && line.first() != Some(&b'+') {
+                    position.report_malformation(self.record, "expected a '+' separator line");
+                    self.clear_record();
+                    // The line can be the header of the next record
+                    self.new_line = true;
+                    self.process_line(line, partial, finished, func, position);
+                    return;
+                }
+            }
+            _ => {
+                self.qual_length += line.len();
+                if self.get_quality {
+                    self.intermediate[QUAL_STATE].extend_from_slice(line);
                 }
-
                 if !partial {
-                    state = (state + 1) % 3;
+                    self.complete_record(func, position);
                 }
-            },
-            remove_file,
-        );
+                return;
+            }
+        }
+
+        if !partial {
+            self.state = match self.state {
+                IDENT_STATE => SEQ_STATE,
+                SEQ_STATE => PLUS_STATE,
+                _ => QUAL_STATE,
+            };
+        }
+    }
+
+    fn complete_record(
+        &mut self,
+        func: &mut impl FnMut(FastaSequence),
+        position: &mut ParsingPosition,
+    ) {
+        let intermediate = &mut self.intermediate;
+
+        if self.qual_length != intermediate[SEQ_STATE].len() {
+            position.report_malformation(
+                self.record,
+                &format!(
+                    "quality length {} differs from the sequence length {}",
+                    self.qual_length,
+                    intermediate[SEQ_STATE].len()
+                ),
+            );
+        } else {
+            normalize_sequence(&mut intermediate[SEQ_STATE]);
+            func(FastaSequence {
+                ident: &intermediate[IDENT_STATE],
+                seq: &intermediate[SEQ_STATE],
+                qual: if self.get_quality {
+                    Some(&intermediate[QUAL_STATE])
+                } else {
+                    None
+                },
+            });
+        }
+
+        self.clear_record();
+    }
+
+    fn clear_record(&mut self) {
+        for buffer in self.intermediate.iter_mut() {
+            buffer.clear();
+        }
+        self.qual_length = 0;
+        self.state = IDENT_STATE;
+    }
+}
+
+/// Sequence lines must start with a base (or with a gap/stop symbol)
+fn is_sequence_line_start(char: u8) -> bool {
+    char.is_ascii_alphabetic() || matches!(char, b'-' | b'*' | b'.')
+}
+
+pub(crate) fn normalize_sequence(seq: &mut [u8]) {
+    let mapping = if IUPAC_EXPANSION_MAX_BASES.load(Ordering::Relaxed) > 0 {
+        &SEQ_LETTERS_MAPPING_IUPAC
+    } else {
+        &SEQ_LETTERS_MAPPING
+    };
+
+    for el in seq.iter_mut() {
+        *el = mapping[*el as usize];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sequences_reader::TextParser;
+    use std::path::Path;
+
+    fn parse_lines(parser: &mut TextParser, lines: &[&str]) -> Vec<String> {
+        let mut sequences = Vec::new();
+        let mut func = |seq: crate::sequences_reader::FastaSequence| {
+            sequences.push(format!(
+                "{} {} {}",
+                std::str::from_utf8(seq.ident).unwrap(),
+                std::str::from_utf8(seq.seq).unwrap(),
+                std::str::from_utf8(seq.qual.unwrap()).unwrap()
+            ))
+        };
+        for line in lines {
+            // Split the lines to check the partial lines handling
+            let (first, second) = line.split_at(line.len() / 2);
+            if !first.is_empty() {
+                parser.process_line(first.as_bytes(), true, false, &mut func);
+            }
+            parser.process_line(second.as_bytes(), false, false, &mut func);
+        }
+        parser.process_line(&[], false, true, &mut func);
+        sequences
+    }
+
+    #[test]
+    fn fastq_malformed_records() {
+        let fastq = [
+            "@read1", "ACGT", "+", "IIII", "@read2", "ACGT", "@read3", "ACGT", "+", "III", "",
+            "@read4", "acgn", "+read4", "IIII", "@read5", "AC",
+        ];
+
+        let mut parser = TextParser::new(Path::new("test.fq"), None, true, true);
+        let sequences = parse_lines(&mut parser, &fastq);
+
+        assert_eq!(sequences, ["@read1 ACGT IIII", "@read4 ACGN IIII"]);
+        assert!(parser.position.malformation_reported);
+        assert_eq!(parser.position.line, 17);
+    }
+
+    #[test]
+    fn unrecognized_file_type() {
+        let lines = ["", "ACGT", ">read1", "ACGT"];
+
+        let mut parser = TextParser::new(Path::new("test.txt"), None, true, false);
+        let sequences = parse_lines(&mut parser, &lines);
+
+        // The whole file is skipped, also the lines that look like a valid record
+        assert!(sequences.is_empty());
+        assert!(parser.position.malformation_reported);
+        assert_eq!(parser.file_type, None);
     }
 }
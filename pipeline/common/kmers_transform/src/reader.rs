@@ -254,7 +254,7 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
                         &(
                             get_memory_mode(SwapPriority::ResultBuckets),
                             PARTIAL_VECS_CHECKPOINT_SIZE,
-                            get_compression_level_info(),
+                            get_compression_level_info(SwapPriority::ResultBuckets),
                         ),
                         SUBSPLIT_INDEX.fetch_add(1, Ordering::Relaxed),
                     );
@@ -84,7 +84,7 @@ impl<F: KmersTransformExecutorFactory> KmersTransformResplitter<F> {
             &(
                 get_memory_mode(SwapPriority::MinimizerBuckets),
                 MINIMIZER_BUCKETS_CHECKPOINT_SIZE,
-                get_compression_level_info(),
+                get_compression_level_info(SwapPriority::MinimizerBuckets),
             ),
         ));
 
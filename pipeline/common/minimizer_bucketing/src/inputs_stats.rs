@@ -0,0 +1,95 @@
+use parking_lot::{const_mutex, Mutex};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Maximum number of inputs listed individually, the ones with most skipped bases first
+const MAX_PRINTED_INPUTS: usize = 100;
+
+/// Statistics of the sequences read from an input file
+pub struct InputStats {
+    pub path: PathBuf,
+    /// Bases read, including the ones of the discarded sequences
+    pub bases: AtomicU64,
+    /// Bases belonging to at least one kmer
+    pub used_bases: AtomicU64,
+    /// Bases different from ACGT (N or IUPAC ambiguity codes)
+    pub non_acgt_bases: AtomicU64,
+    /// Sequences shorter than k
+    pub short_sequences: AtomicU64,
+    /// Sequences long enough but without any valid kmer
+    pub dropped_sequences: AtomicU64,
+}
+
+impl InputStats {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            bases: AtomicU64::new(0),
+            used_bases: AtomicU64::new(0),
+            non_acgt_bases: AtomicU64::new(0),
+            short_sequences: AtomicU64::new(0),
+            dropped_sequences: AtomicU64::new(0),
+        }
+    }
+
+    fn skipped_bases(&self) -> u64 {
+        self.bases
+            .load(Ordering::Relaxed)
+            .saturating_sub(self.used_bases.load(Ordering::Relaxed))
+    }
+
+    fn describe(&self) -> String {
+        let bases = self.bases.load(Ordering::Relaxed);
+        format!(
+            "{} bases read, {} skipped ({:.2}%) of which {} non-ACGT, {} sequences shorter than k, {} sequences without valid kmers",
+            bases,
+            self.skipped_bases(),
+            self.skipped_bases() as f64 / bases.max(1) as f64 * 100.0,
+            self.non_acgt_bases.load(Ordering::Relaxed),
+            self.short_sequences.load(Ordering::Relaxed),
+            self.dropped_sequences.load(Ordering::Relaxed)
+        )
+    }
+}
+
+static LAST_INPUTS_STATS: Mutex<Vec<InputStats>> = const_mutex(Vec::new());
+
+/// Saves the statistics of the last minimizer bucketing run, to print them with the final stats
+pub(crate) fn store_inputs_stats(inputs_stats: Vec<InputStats>) {
+    *LAST_INPUTS_STATS.lock() = inputs_stats;
+}
+
+/// Prints the totals of the inputs statistics of the last minimizer bucketing run, followed by each input
+pub fn print_inputs_stats() {
+    let mut inputs_stats = LAST_INPUTS_STATS.lock();
+    if inputs_stats.is_empty() {
+        return;
+    }
+
+    let total = InputStats::new(PathBuf::new());
+    for stats in inputs_stats.iter() {
+        for (total, value) in [
+            (&total.bases, &stats.bases),
+            (&total.used_bases, &stats.used_bases),
+            (&total.non_acgt_bases, &stats.non_acgt_bases),
+            (&total.short_sequences, &stats.short_sequences),
+            (&total.dropped_sequences, &stats.dropped_sequences),
+        ] {
+            total.fetch_add(value.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+    }
+    println!("Input sequences: {}", total.describe());
+
+    if inputs_stats.len() > 1 {
+        inputs_stats.sort_by_key(|stats| std::cmp::Reverse(stats.skipped_bases()));
+        for stats in inputs_stats.iter().take(MAX_PRINTED_INPUTS) {
+            println!("  {}: {}", stats.path.display(), stats.describe());
+        }
+        if inputs_stats.len() > MAX_PRINTED_INPUTS {
+            println!(
+                "  ... and {} more inputs",
+                inputs_stats.len() - MAX_PRINTED_INPUTS
+            );
+        }
+    }
+}
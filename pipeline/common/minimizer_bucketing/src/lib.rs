@@ -2,11 +2,13 @@
 #![feature(type_alias_impl_trait)]
 
 pub mod counters_analyzer;
+pub mod inputs_stats;
 mod queue_data;
 mod reader;
 mod sequences_splitter;
 
 use crate::counters_analyzer::CountersAnalyzer;
+use crate::inputs_stats::{store_inputs_stats, InputStats};
 use crate::queue_data::MinimizerBucketingQueueData;
 use crate::reader::MinimizerBucketingFilesReader;
 use crate::sequences_splitter::SequencesSplitter;
@@ -20,6 +22,7 @@ use hashes::HashableSequence;
 use io::compressed_read::CompressedRead;
 use io::concurrent::temp_reads::creads_utils::CompressedReadsBucketHelper;
 use io::concurrent::temp_reads::extra_data::SequenceExtraData;
+use io::get_input_file_size;
 use io::sequences_reader::FastaSequence;
 use parallel_processor::buckets::concurrent::{BucketsThreadBuffer, BucketsThreadDispatcher};
 use parallel_processor::buckets::writers::compressed_binary_writer::CompressedBinaryWriter;
@@ -186,6 +189,7 @@ pub struct MinimizerBucketingExecutionContext<GlobalData> {
 
     pub partial_read_copyback: Option<usize>,
     pub copy_ident: bool,
+    pub inputs_stats: Vec<InputStats>,
 }
 
 pub struct GenericMinimizerBucketing;
@@ -275,6 +279,18 @@ impl<E: MinimizerBucketingExecutorFactory + Sync + Send + 'static> MinimizerBuck
                 sequences_count += 1;
             }
 
+            let input_stats = &context.inputs_stats[input_packet.input_index];
+            input_stats.bases.fetch_add(total_bases, Ordering::Relaxed);
+            input_stats
+                .used_bases
+                .fetch_add(sequences_splitter.valid_bases, Ordering::Relaxed);
+            input_stats
+                .non_acgt_bases
+                .fetch_add(sequences_splitter.non_acgt_bases, Ordering::Relaxed);
+            input_stats
+                .dropped_sequences
+                .fetch_add(sequences_splitter.dropped_sequences, Ordering::Relaxed);
+
             SEQ_COUNT.fetch_add(sequences_count, Ordering::Relaxed);
             let total_bases_count =
                 TOT_BASES_COUNT.fetch_add(total_bases, Ordering::Relaxed) + total_bases;
@@ -409,17 +425,19 @@ impl GenericMinimizerBucketing {
             &(
                 get_memory_mode(SwapPriority::MinimizerBuckets),
                 MINIMIZER_BUCKETS_CHECKPOINT_SIZE,
-                get_compression_level_info(),
+                get_compression_level_info(SwapPriority::MinimizerBuckets),
             ),
         ));
 
-        input_files.sort_by_cached_key(|(file, _)| {
-            std::fs::metadata(file)
-                .expect(&format!("Error while opening file {}", file.display()))
-                .len()
-        });
+        // Streamed inputs have an unknown size, start reading them first
+        input_files.sort_by_cached_key(|(file, _)| get_input_file_size(file).unwrap_or(u64::MAX));
         input_files.reverse();
 
+        let inputs_stats = input_files
+            .iter()
+            .map(|(file, _)| InputStats::new(file.clone()))
+            .collect();
+
         let second_buckets_count = max(
             MAXIMUM_SECOND_BUCKETS_COUNT,
             threads_count.next_power_of_two(),
@@ -444,6 +462,7 @@ impl GenericMinimizerBucketing {
             partial_read_copyback,
             read_threads_count,
             copy_ident,
+            inputs_stats,
         });
 
         {
@@ -460,8 +479,13 @@ impl GenericMinimizerBucketing {
             let compute_thread_pool =
                 ExecThreadPool::new(&execution_context, compute_threads_count, "mm_comp");
 
-            let mut input_files =
-                ExecutorInput::from_iter(input_files.into_iter(), ExecutorInputAddressMode::Single);
+            let mut input_files = ExecutorInput::from_iter(
+                input_files
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, (file, file_info))| (file, file_info, index)),
+                ExecutorInputAddressMode::Single,
+            );
 
             let reader_executors = disk_thread_pool
                 .register_executors::<MinimizerBucketingFilesReader<E::GlobalData, E::FileInfo>>(
@@ -511,6 +535,8 @@ impl GenericMinimizerBucketing {
         let global_context = Arc::try_unwrap(global_context)
             .unwrap_or_else(|_| panic!("Cannot get execution context!"));
 
+        store_inputs_stats(global_context.inputs_stats);
+
         let common_context = Arc::try_unwrap(global_context.common)
             .unwrap_or_else(|_| panic!("Cannot get common execution context!"));
 
@@ -10,6 +10,7 @@ pub struct MinimizerBucketingQueueData<F: Clone + Sync + Send + Default + 'stati
     pub sequences: Vec<SequencesType>,
     pub file_info: F,
     pub start_read_index: u64,
+    pub input_index: usize,
 }
 
 impl<F: Clone + Sync + Send + Default + 'static> MinimizerBucketingQueueData<F> {
@@ -19,6 +20,7 @@ impl<F: Clone + Sync + Send + Default + 'static> MinimizerBucketingQueueData<F>
             sequences: Vec::with_capacity(capacity / 512),
             file_info,
             start_read_index: 0,
+            input_index: 0,
         }
     }
 
@@ -1,5 +1,6 @@
 use crate::queue_data::MinimizerBucketingQueueData;
 use crate::MinimizerBucketingExecutionContext;
+use config::use_fastq_qualities;
 use io::sequences_reader::SequencesReader;
 use nightly_quirks::branch_pred::unlikely;
 use parallel_processor::execution_manager::executor::{
@@ -32,21 +33,25 @@ impl<GlobalData: Sync + Send + 'static, FileInfo: Clone + Sync + Send + Default
     ) {
         let packets_pool = ops.pool_alloc_await(0).await;
 
-        let mut sequences_reader = SequencesReader::new();
+        let mut sequences_reader = SequencesReader::with_quality(use_fastq_qualities());
 
         while let Some(input_packet) = ops.receive_packet().await {
             let mut data_packet = packets_pool.alloc_packet().await;
             let file_info = input_packet.1.clone();
+            let input_index = input_packet.2;
 
             let data = data_packet.deref_mut();
             data.file_info = file_info.clone();
             data.start_read_index = 0;
+            data.input_index = input_index;
 
             let mut read_index = 0;
 
             context.current_file.fetch_add(1, Ordering::Relaxed);
 
             let mut max_len = 0;
+            let mut short_sequences = 0;
+            let mut short_sequences_bases = 0;
 
             sequences_reader.process_file_extended(
                 &input_packet.0,
@@ -54,6 +59,8 @@ impl<GlobalData: Sync + Send + 'static, FileInfo: Clone + Sync + Send + Default
                     let mut data = data_packet.deref_mut();
 
                     if x.seq.len() < context.common.k {
+                        short_sequences += 1;
+                        short_sequences_bases += x.seq.len() as u64;
                         return;
                     }
 
@@ -86,6 +93,7 @@ impl<GlobalData: Sync + Send + 'static, FileInfo: Clone + Sync + Send + Default
                         data = data_packet.deref_mut();
                         data.file_info = file_info.clone();
                         data.start_read_index = read_index;
+                        data.input_index = input_index;
 
                         if !data.push_sequences(x) {
                             panic!("Out of memory!");
@@ -110,6 +118,14 @@ impl<GlobalData: Sync + Send + 'static, FileInfo: Clone + Sync + Send + Default
                 );
             }
 
+            let input_stats = &context.inputs_stats[input_index];
+            input_stats
+                .short_sequences
+                .fetch_add(short_sequences, Ordering::Relaxed);
+            input_stats
+                .bases
+                .fetch_add(short_sequences_bases, Ordering::Relaxed);
+
             context.processed_files.fetch_add(1, Ordering::Relaxed);
         }
     }
@@ -118,7 +134,7 @@ impl<GlobalData: Sync + Send + 'static, FileInfo: Clone + Sync + Send + Default
 impl<GlobalData: Sync + Send + 'static, FileInfo: Clone + Sync + Send + Default + 'static>
     AsyncExecutor for MinimizerBucketingFilesReader<GlobalData, FileInfo>
 {
-    type InputPacket = (PathBuf, FileInfo);
+    type InputPacket = (PathBuf, FileInfo, usize);
     type OutputPacket = MinimizerBucketingQueueData<FileInfo>;
     type GlobalParams = MinimizerBucketingExecutionContext<GlobalData>;
     type InitData = ();
@@ -1,14 +1,72 @@
-use io::sequences_reader::FastaSequence;
+use config::{IUPAC_EXPANSION_MAX_BASES, MIN_BASE_QUALITY, MIN_KMER_CORRECTNESS_PROBABILITY};
+use io::sequences_reader::{iupac_compatible_bases, FastaSequence};
+use std::mem::take;
 use std::ops::Range;
+use std::sync::atomic::Ordering;
+
+/// Offset of the Phred quality values in the FASTQ quality lines
+const PHRED_OFFSET: u8 = 33;
+
+/// Number of bases compatible with each character, 1 for ACGT and 0 for the unknown ones
+const COMPATIBLE_BASES_COUNT: [u8; 256] = {
+    let mut counts = [0; 256];
+    let mut index = 0;
+    while index < 256 {
+        counts[index] = iupac_compatible_bases(index as u8).len() as u8;
+        index += 1;
+    }
+    counts
+};
 
 pub struct SequencesSplitter {
     k: usize,
+    /// Bases belonging to at least one of the returned sequences
     pub valid_bases: u64,
+    /// Bases different from ACGT (N or IUPAC ambiguity codes)
+    pub non_acgt_bases: u64,
+    /// Sequences without any valid kmer
+    pub dropped_sequences: u64,
+    used_end: usize,
+    min_quality_char: u8,
+    max_expanded_bases: u8,
+    expansion_buffer: Vec<u8>,
+    /// Log correctness probability of each quality character, if the kmers are filtered by quality
+    log_probabilities: Option<Box<[f64; 256]>>,
+    min_kmer_log_probability: f64,
 }
 
 impl SequencesSplitter {
     pub fn new(k: usize) -> Self {
-        Self { k, valid_bases: 0 }
+        let min_base_quality = MIN_BASE_QUALITY.load(Ordering::Relaxed);
+        let min_kmer_probability =
+            f64::from_bits(MIN_KMER_CORRECTNESS_PROBABILITY.load(Ordering::Relaxed));
+
+        Self {
+            k,
+            valid_bases: 0,
+            non_acgt_bases: 0,
+            dropped_sequences: 0,
+            used_end: 0,
+            min_quality_char: if min_base_quality > 0 {
+                (min_base_quality as u8).saturating_add(PHRED_OFFSET)
+            } else {
+                0
+            },
+            log_probabilities: (min_kmer_probability > 0.0).then(|| {
+                let mut log_probabilities = Box::new([0.0; 256]);
+                for (char, log_probability) in log_probabilities.iter_mut().enumerate() {
+                    let quality = (char as u8).saturating_sub(PHRED_OFFSET) as f64;
+                    // Keep the value finite for quality 0, so that it can be removed from the window sum
+                    *log_probability = (1.0 - 10f64.powf(-quality / 10.0))
+                        .max(f64::MIN_POSITIVE)
+                        .ln();
+                }
+                log_probabilities
+            }),
+            min_kmer_log_probability: min_kmer_probability.ln(),
+            max_expanded_bases: IUPAC_EXPANSION_MAX_BASES.load(Ordering::Relaxed) as u8,
+            expansion_buffer: Vec::new(),
+        }
     }
 
     #[inline]
@@ -17,25 +75,215 @@ impl SequencesSplitter {
         fasta_seq: &FastaSequence,
         mut process_fn: impl FnMut(&[u8], Range<usize>),
     ) {
-        let mut start;
-        let mut end = 0;
+        let seq = fasta_seq.seq;
+        // The qualities are ignored if they do not match the sequence
+        let qual = fasta_seq.qual.filter(|qual| qual.len() == seq.len());
+
+        let min_quality_char = self.min_quality_char;
+        let max_expanded_bases = self.max_expanded_bases;
+        // 1 for the valid bases, the number of compatible bases for the expandable ambiguity codes, 0 otherwise
+        let compatible_bases = |index: usize| {
+            if qual.map_or(false, |qual| qual[index] < min_quality_char) {
+                return 0;
+            }
+            match COMPATIBLE_BASES_COUNT[seq[index] as usize] {
+                1 => 1,
+                count if count <= max_expanded_bases => count,
+                _ => 0,
+            }
+        };
+
+        let valid_bases = self.valid_bases;
+        self.used_end = 0;
+
+        // Start of the current run of valid bases
+        let mut start = 0;
+        for index in 0..=seq.len() {
+            let compatible = if index < seq.len() {
+                compatible_bases(index)
+            } else {
+                0
+            };
+            if compatible == 1 {
+                continue;
+            }
+
+            // If the length of the run is long enough, return it
+            if index - start >= self.k {
+                self.emit_range(&seq[start..index], qual, start..index, &mut process_fn);
+            }
+
+            if index < seq.len() && COMPATIBLE_BASES_COUNT[seq[index] as usize] != 1 {
+                self.non_acgt_bases += 1;
+            }
+
+            // Expand the kmers containing only this ambiguity code
+            if compatible > 1 {
+                let mut end = index + 1;
+                while end < seq.len() && end < index + self.k && compatible_bases(end) == 1 {
+                    end += 1;
+                }
+                let range = start.max((index + 1).saturating_sub(self.k))..end;
+
+                if range.len() >= self.k {
+                    let mut buffer = take(&mut self.expansion_buffer);
+                    for base in iupac_compatible_bases(seq[index]) {
+                        buffer.clear();
+                        buffer.extend_from_slice(&seq[range.clone()]);
+                        buffer[index - range.start] = *base;
+                        self.emit_range(&buffer, qual, range.clone(), &mut process_fn);
+                    }
+                    self.expansion_buffer = buffer;
+                }
+            }
+
+            start = index + 1;
+        }
+
+        if self.valid_bases == valid_bases {
+            self.dropped_sequences += 1;
+        }
+    }
+
+    /// Returns `sequence`, the bases of `range`, or only its parts whose kmers pass the quality filter
+    fn emit_range(
+        &mut self,
+        sequence: &[u8],
+        qual: Option<&[u8]>,
+        range: Range<usize>,
+        process_fn: &mut impl FnMut(&[u8], Range<usize>),
+    ) {
+        // The ranges are returned in order, overlapping only with the expanded ambiguity codes
+        let mut count_valid_bases = |valid_range: &Range<usize>| {
+            self.valid_bases += valid_range
+                .end
+                .saturating_sub(valid_range.start.max(self.used_end))
+                as u64;
+            self.used_end = self.used_end.max(valid_range.end);
+        };
 
-        while end < fasta_seq.seq.len() {
-            start = end;
-            // Skip all not recognized characters
-            while start < fasta_seq.seq.len() && fasta_seq.seq[start] == b'N' {
-                start += 1;
+        match (qual, &self.log_probabilities) {
+            (Some(qual), Some(log_probabilities)) => Self::split_low_quality_kmers(
+                self.k,
+                self.min_kmer_log_probability,
+                log_probabilities,
+                &qual[range.clone()],
+                |valid_range| {
+                    let sequence = &sequence[valid_range.clone()];
+                    let valid_range =
+                        range.start + valid_range.start..range.start + valid_range.end;
+                    count_valid_bases(&valid_range);
+                    process_fn(sequence, valid_range);
+                },
+            ),
+            _ => {
+                count_valid_bases(&range);
+                process_fn(sequence, range);
             }
-            end = start;
-            // Find the last valid character in this sequence
-            while end < fasta_seq.seq.len() && fasta_seq.seq[end] != b'N' {
-                end += 1;
+        }
+    }
+
+    /// Calls `process_fn` with the maximal ranges whose kmers all have a correctness probability
+    /// (the product of the ones of their bases) of at least the minimum one
+    fn split_low_quality_kmers(
+        k: usize,
+        min_kmer_log_probability: f64,
+        log_probabilities: &[f64; 256],
+        qual: &[u8],
+        mut process_fn: impl FnMut(Range<usize>),
+    ) {
+        let mut window_log_probability: f64 = qual[..k - 1]
+            .iter()
+            .map(|q| log_probabilities[*q as usize])
+            .sum();
+        let mut run_start = None;
+
+        for kmer_start in 0..=(qual.len() - k) {
+            window_log_probability += log_probabilities[qual[kmer_start + k - 1] as usize];
+            if kmer_start > 0 {
+                window_log_probability -= log_probabilities[qual[kmer_start - 1] as usize];
             }
-            // If the length of the read is long enough, return it
-            if end - start >= self.k {
-                self.valid_bases += (end - start) as u64;
-                process_fn(&fasta_seq.seq[start..end], start..end);
+
+            if window_log_probability >= min_kmer_log_probability {
+                run_start.get_or_insert(kmer_start);
+            } else if let Some(start) = run_start.take() {
+                process_fn(start..kmer_start - 1 + k);
             }
         }
+
+        if let Some(start) = run_start {
+            process_fn(start..qual.len());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sequences_splitter::SequencesSplitter;
+    use io::sequences_reader::FastaSequence;
+
+    #[test]
+    fn low_quality_bases_and_kmers_splitting() {
+        let mut splitter = SequencesSplitter::new(3);
+        splitter.min_quality_char = b'#';
+
+        let mut ranges = vec![];
+        splitter.process_sequences(
+            &FastaSequence {
+                ident: b"@read",
+                seq: b"ACGTNACGTACGTA",
+                qual: Some(b"IIIIIIII\"IIIII"),
+            },
+            |_, range| ranges.push(range),
+        );
+        assert_eq!(ranges, [0..4, 5..8, 9..14]);
+        assert_eq!(splitter.valid_bases, 12);
+
+        let mut log_probabilities = Box::new([0.0; 256]);
+        log_probabilities[b'!' as usize] = 0.5f64.ln();
+
+        let mut ranges = vec![];
+        SequencesSplitter::split_low_quality_kmers(
+            3,
+            0.9f64.ln(),
+            &log_probabilities,
+            b"IIIII!IIIIII!II",
+            |range| ranges.push(range),
+        );
+        assert_eq!(ranges, [0..5, 6..12]);
+    }
+
+    #[test]
+    fn iupac_codes_expansion() {
+        let mut splitter = SequencesSplitter::new(3);
+        splitter.max_expanded_bases = 2;
+
+        let mut sequences = vec![];
+        for seq in [&b"AACRTTNNAAYCC"[..], b"ACNNB"] {
+            splitter.process_sequences(
+                &FastaSequence {
+                    ident: b">seq",
+                    seq,
+                    qual: None,
+                },
+                |sequence, range| {
+                    sequences.push((std::str::from_utf8(sequence).unwrap().to_string(), range))
+                },
+            );
+        }
+
+        assert_eq!(
+            sequences,
+            [
+                ("AAC".to_string(), 0..3),
+                ("ACATT".to_string(), 1..6),
+                ("ACGTT".to_string(), 1..6),
+                ("AACCC".to_string(), 8..13),
+                ("AATCC".to_string(), 8..13),
+            ]
+        );
+        assert_eq!(splitter.valid_bases, 11);
+        assert_eq!(splitter.non_acgt_bases, 7);
+        assert_eq!(splitter.dropped_sequences, 1);
     }
 }
@@ -34,6 +34,11 @@ impl<CHI> MapEntry<CHI> {
         self.count_flags.set(self.count_flags.get() | USED_MARKER);
     }
 
+    #[inline(always)]
+    pub fn clear_used(&self) {
+        self.count_flags.set(self.count_flags.get() & !USED_MARKER);
+    }
+
     #[inline(always)]
     pub fn is_used(&self) -> bool {
         (self.count_flags.get() & USED_MARKER) == USED_MARKER
@@ -0,0 +1,92 @@
+use crate::{ColorsRun, UnitigLink, INDEX_MAGIC, INDEX_VERSION};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Builds a graph index, the unitigs must be added in increasing id order
+pub struct UnitigGraphBuilder {
+    k: usize,
+    sequence_offsets: Vec<u64>,
+    links_offsets: Vec<u64>,
+    links: Vec<u64>,
+    colors_offsets: Vec<u64>,
+    colors_runs: Vec<u64>,
+    packed_sequences: Vec<u8>,
+}
+
+impl UnitigGraphBuilder {
+    pub fn new(k: usize) -> Self {
+        Self {
+            k,
+            sequence_offsets: vec![0],
+            links_offsets: vec![0],
+            links: vec![],
+            colors_offsets: vec![0],
+            colors_runs: vec![],
+            packed_sequences: vec![],
+        }
+    }
+
+    pub fn add_unitig(
+        &mut self,
+        sequence: &[u8],
+        links: impl Iterator<Item = UnitigLink>,
+        colors: impl Iterator<Item = ColorsRun>,
+    ) {
+        let mut position = *self.sequence_offsets.last().unwrap() as usize;
+        for base in sequence {
+            let value = match base {
+                b'A' => 0,
+                b'C' => 1,
+                b'G' => 2,
+                b'T' => 3,
+                _ => panic!("Unsupported base {} in unitig", *base as char),
+            };
+
+            if position % 4 == 0 {
+                self.packed_sequences.push(0);
+            }
+            *self.packed_sequences.last_mut().unwrap() |= value << ((position % 4) * 2);
+            position += 1;
+        }
+        self.sequence_offsets.push(position as u64);
+
+        self.links.extend(links.map(|link| link.encode()));
+        self.links_offsets.push(self.links.len() as u64);
+
+        for run in colors {
+            self.colors_runs.push(run.subset);
+            self.colors_runs.push(run.kmers);
+        }
+        self.colors_offsets
+            .push((self.colors_runs.len() / 2) as u64);
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(INDEX_MAGIC)?;
+        for value in [
+            INDEX_VERSION,
+            self.k as u64,
+            (self.sequence_offsets.len() - 1) as u64,
+        ] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+
+        for section in [
+            &self.sequence_offsets,
+            &self.links_offsets,
+            &self.links,
+            &self.colors_offsets,
+            &self.colors_runs,
+        ] {
+            for value in section {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+
+        writer.write_all(&self.packed_sequences)?;
+        writer.flush()
+    }
+}
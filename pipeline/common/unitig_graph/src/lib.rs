@@ -0,0 +1,267 @@
+//! Binary adjacency index of a compacted de Bruijn graph, written alongside the unitigs output,
+//! allowing to walk the graph without parsing the FASTA headers.
+
+pub mod builder;
+
+use std::fs::File;
+use std::io::{BufReader, Error, ErrorKind, Read};
+use std::path::Path;
+
+pub(crate) const INDEX_MAGIC: &[u8; 8] = b"GGCATIDX";
+pub(crate) const INDEX_VERSION: u64 = 1;
+
+pub(crate) const EDGE_FLIP_OTHER: u64 = 1;
+pub(crate) const EDGE_FLIP_CURRENT: u64 = 2;
+pub(crate) const EDGE_INDEX_SHIFT: usize = 2;
+
+/// Orientation in which a unitig is traversed
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Strand {
+    Forward,
+    Reverse,
+}
+
+impl Strand {
+    #[inline(always)]
+    pub fn is_forward(&self) -> bool {
+        *self == Strand::Forward
+    }
+
+    #[inline(always)]
+    pub fn flip(&self) -> Self {
+        match self {
+            Strand::Forward => Strand::Reverse,
+            Strand::Reverse => Strand::Forward,
+        }
+    }
+}
+
+/// Link between two unitigs, in the same convention of the BCALM2 L:<+/->:<other id>:<+/-> tags:
+/// traversing the unitig in the `from` orientation, it continues with `to` traversed in the `to_strand` orientation
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct UnitigLink {
+    pub from: Strand,
+    pub to: u64,
+    pub to_strand: Strand,
+}
+
+impl UnitigLink {
+    pub(crate) fn encode(&self) -> u64 {
+        let mut value = self.to << EDGE_INDEX_SHIFT;
+        if !self.from.is_forward() {
+            value |= EDGE_FLIP_CURRENT;
+        }
+        if !self.to_strand.is_forward() {
+            value |= EDGE_FLIP_OTHER;
+        }
+        value
+    }
+
+    pub(crate) fn decode(value: u64) -> Self {
+        Self {
+            from: if value & EDGE_FLIP_CURRENT == 0 {
+                Strand::Forward
+            } else {
+                Strand::Reverse
+            },
+            to: value >> EDGE_INDEX_SHIFT,
+            to_strand: if value & EDGE_FLIP_OTHER == 0 {
+                Strand::Forward
+            } else {
+                Strand::Reverse
+            },
+        }
+    }
+}
+
+/// Colors run of a unitig, made of `kmers` consecutive kmers sharing the same colors subset
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ColorsRun {
+    pub subset: u64,
+    pub kmers: u64,
+}
+
+const BASES: [u8; 4] = [b'A', b'C', b'G', b'T'];
+
+/// In memory view of a graph index file
+pub struct UnitigGraph {
+    k: usize,
+    sequence_offsets: Vec<u64>,
+    links_offsets: Vec<u64>,
+    links: Vec<u64>,
+    colors_offsets: Vec<u64>,
+    colors_runs: Vec<u64>,
+    packed_sequences: Vec<u8>,
+}
+
+fn read_u64(reader: &mut impl Read) -> std::io::Result<u64> {
+    let mut buffer = [0; 8];
+    reader.read_exact(&mut buffer)?;
+    Ok(u64::from_le_bytes(buffer))
+}
+
+fn read_u64_vec(reader: &mut impl Read, len: usize) -> std::io::Result<Vec<u64>> {
+    (0..len).map(|_| read_u64(reader)).collect()
+}
+
+impl UnitigGraph {
+    /// Loads a graph index, as written by the `--graph-index` build option
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != INDEX_MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "Not a graph index file"));
+        }
+
+        let version = read_u64(&mut reader)?;
+        if version != INDEX_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Unsupported graph index version {}", version),
+            ));
+        }
+
+        let k = read_u64(&mut reader)? as usize;
+        let unitigs_count = read_u64(&mut reader)? as usize;
+
+        let sequence_offsets = read_u64_vec(&mut reader, unitigs_count + 1)?;
+        let links_offsets = read_u64_vec(&mut reader, unitigs_count + 1)?;
+        let links_count = links_offsets[unitigs_count] as usize;
+        let links = read_u64_vec(&mut reader, links_count)?;
+        let colors_offsets = read_u64_vec(&mut reader, unitigs_count + 1)?;
+        let colors_runs_count = colors_offsets[unitigs_count] as usize;
+        let colors_runs = read_u64_vec(&mut reader, colors_runs_count * 2)?;
+
+        let mut packed_sequences = vec![0; (sequence_offsets[unitigs_count] as usize + 3) / 4];
+        reader.read_exact(&mut packed_sequences)?;
+
+        Ok(Self {
+            k,
+            sequence_offsets,
+            links_offsets,
+            links,
+            colors_offsets,
+            colors_runs,
+            packed_sequences,
+        })
+    }
+
+    /// The kmers length of the graph
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    pub fn unitigs_count(&self) -> usize {
+        self.sequence_offsets.len() - 1
+    }
+
+    /// Length in bases of a unitig
+    pub fn sequence_len(&self, id: u64) -> usize {
+        (self.sequence_offsets[id as usize + 1] - self.sequence_offsets[id as usize]) as usize
+    }
+
+    /// Returns the bases of a unitig, in its forward orientation
+    pub fn sequence(&self, id: u64) -> Vec<u8> {
+        let start = self.sequence_offsets[id as usize] as usize;
+        let end = self.sequence_offsets[id as usize + 1] as usize;
+
+        (start..end)
+            .map(|pos| {
+                let byte = self.packed_sequences[pos / 4];
+                BASES[((byte >> ((pos % 4) * 2)) & 0x3) as usize]
+            })
+            .collect()
+    }
+
+    /// Returns all the links of a unitig, in both orientations
+    pub fn links(&self, id: u64) -> impl Iterator<Item = UnitigLink> + '_ {
+        let start = self.links_offsets[id as usize] as usize;
+        let end = self.links_offsets[id as usize + 1] as usize;
+        self.links[start..end]
+            .iter()
+            .map(|link| UnitigLink::decode(*link))
+    }
+
+    /// Returns the unitigs that can follow the given one when it is traversed in the `strand` orientation,
+    /// each one with the orientation in which it is traversed
+    pub fn neighbors(&self, id: u64, strand: Strand) -> impl Iterator<Item = (u64, Strand)> + '_ {
+        self.links(id)
+            .filter(move |link| link.from == strand)
+            .map(|link| (link.to, link.to_strand))
+    }
+
+    /// Returns the colors runs of a unitig, in its forward orientation (empty if the graph is not colored)
+    pub fn colors(&self, id: u64) -> impl Iterator<Item = ColorsRun> + '_ {
+        let start = self.colors_offsets[id as usize] as usize;
+        let end = self.colors_offsets[id as usize + 1] as usize;
+        self.colors_runs[start * 2..end * 2]
+            .chunks_exact(2)
+            .map(|run| ColorsRun {
+                subset: run[0],
+                kmers: run[1],
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::UnitigGraphBuilder;
+    use crate::{ColorsRun, Strand, UnitigGraph, UnitigLink};
+
+    #[test]
+    fn index_roundtrip() {
+        let mut builder = UnitigGraphBuilder::new(3);
+        builder.add_unitig(
+            b"ACGTTG",
+            [UnitigLink {
+                from: Strand::Forward,
+                to: 1,
+                to_strand: Strand::Reverse,
+            }]
+            .into_iter(),
+            [ColorsRun {
+                subset: 2,
+                kmers: 4,
+            }]
+            .into_iter(),
+        );
+        builder.add_unitig(
+            b"CAAC",
+            [UnitigLink {
+                from: Strand::Forward,
+                to: 0,
+                to_strand: Strand::Reverse,
+            }]
+            .into_iter(),
+            [].into_iter(),
+        );
+
+        let path = std::env::temp_dir().join(format!(
+            "ggcat_unitig_graph_index_roundtrip_{}.idx",
+            std::process::id()
+        ));
+        builder.write(&path).unwrap();
+        let graph = UnitigGraph::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(graph.k(), 3);
+        assert_eq!(graph.unitigs_count(), 2);
+        assert_eq!(graph.sequence(0), b"ACGTTG");
+        assert_eq!(graph.sequence(1), b"CAAC");
+        assert_eq!(
+            graph.neighbors(0, Strand::Forward).collect::<Vec<_>>(),
+            vec![(1, Strand::Reverse)]
+        );
+        assert_eq!(graph.neighbors(0, Strand::Reverse).count(), 0);
+        assert_eq!(
+            graph.colors(0).collect::<Vec<_>>(),
+            vec![ColorsRun {
+                subset: 2,
+                kmers: 4
+            }]
+        );
+        assert_eq!(graph.colors(1).count(), 0);
+    }
+}
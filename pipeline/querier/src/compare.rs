@@ -0,0 +1,213 @@
+use crate::pipeline::compare_minimizer_bucketing::{compare_minimizer_bucketing, GraphSide};
+use crate::pipeline::compare_output::write_differing_fragments;
+use crate::pipeline::parallel_kmers_compare::parallel_kmers_compare;
+pub use crate::pipeline::parallel_kmers_compare::CompareKmersCounts;
+use ::static_dispatch::static_dispatch;
+use config::{INTERMEDIATE_COMPRESSION_LEVEL_FAST, INTERMEDIATE_COMPRESSION_LEVEL_SLOW};
+use hashes::{HashFunctionFactory, MinimizerHashFunctionFactory};
+use io::compute_stats_from_input_files;
+use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+
+/// Computes the kmers shared by two graphs and the ones present in only one of them.
+/// If an output prefix is given, the maximal runs of kmers of each graph missing from the other one
+/// are written to `<prefix>.only-a.fasta` and `<prefix>.only-b.fasta`. Returns the kmers counts
+#[static_dispatch(BucketingHash = [
+    hashes::cn_nthash::CanonicalNtHashIteratorFactory,
+    #[cfg(not(feature = "devel-build"))]  hashes::fw_nthash::ForwardNtHashIteratorFactory
+], MergingHash = [
+    #[cfg(not(feature = "devel-build"))] hashes::fw_seqhash::u16::ForwardSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::fw_seqhash::u32::ForwardSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::fw_seqhash::u64::ForwardSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::fw_seqhash::u128::ForwardSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::fw_rkhash::u32::ForwardRabinKarpHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::fw_rkhash::u64::ForwardRabinKarpHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::fw_rkhash::u128::ForwardRabinKarpHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::cn_seqhash::u16::CanonicalSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::cn_seqhash::u32::CanonicalSeqHashFactory,
+    hashes::cn_seqhash::u64::CanonicalSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::cn_seqhash::u128::CanonicalSeqHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::cn_rkhash::u32::CanonicalRabinKarpHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::cn_rkhash::u64::CanonicalRabinKarpHashFactory,
+    #[cfg(not(feature = "devel-build"))] hashes::cn_rkhash::u128::CanonicalRabinKarpHashFactory,
+])]
+pub fn run_compare<
+    BucketingHash: MinimizerHashFunctionFactory,
+    MergingHash: HashFunctionFactory,
+>(
+    k: usize,
+    m: usize,
+    graph_a: PathBuf,
+    graph_b: PathBuf,
+    output_file_prefix: Option<PathBuf>,
+    temp_dir: PathBuf,
+    buckets_count_log: Option<usize>,
+    threads_count: usize,
+    default_compression_level: Option<u32>,
+) -> CompareKmersCounts {
+    PHASES_TIMES_MONITOR.write().init();
+
+    BucketingHash::initialize(k);
+    MergingHash::initialize(k);
+
+    let file_stats = compute_stats_from_input_files(&[graph_a.clone(), graph_b.clone()]);
+
+    let buckets_count_log = buckets_count_log.unwrap_or_else(|| file_stats.best_buckets_count_log);
+
+    if let Some(default_compression_level) = default_compression_level {
+        INTERMEDIATE_COMPRESSION_LEVEL_SLOW.store(default_compression_level, Ordering::Relaxed);
+        INTERMEDIATE_COMPRESSION_LEVEL_FAST.store(default_compression_level, Ordering::Relaxed);
+    }
+
+    let buckets_count = 1 << buckets_count_log;
+
+    let (buckets, counters) = compare_minimizer_bucketing::<BucketingHash>(
+        graph_a.clone(),
+        graph_b.clone(),
+        temp_dir.as_path(),
+        buckets_count,
+        threads_count,
+        k,
+        m,
+    );
+
+    let (counts, positions_buckets) = parallel_kmers_compare::<BucketingHash, MergingHash, _>(
+        buckets,
+        counters,
+        buckets_count,
+        temp_dir.as_path(),
+        k,
+        m,
+        threads_count,
+        output_file_prefix.is_some(),
+    );
+
+    if let Some(output_file_prefix) = &output_file_prefix {
+        PHASES_TIMES_MONITOR
+            .write()
+            .start_phase("phase: differing fragments output".to_string());
+
+        for (side, graph, suffix) in [
+            (GraphSide::A, &graph_a, ".only-a.fasta"),
+            (GraphSide::B, &graph_b, ".only-b.fasta"),
+        ] {
+            // Appended to the prefix, that may already contain dots
+            let mut output_file = output_file_prefix.as_os_str().to_os_string();
+            output_file.push(suffix);
+            let output_file = PathBuf::from(output_file);
+            let fragments_count = write_differing_fragments(
+                k,
+                graph,
+                &positions_buckets[side as usize],
+                &output_file,
+            );
+            println!(
+                "Written {} fragments to {}",
+                fragments_count,
+                output_file.display()
+            );
+        }
+    }
+
+    let _ = std::fs::remove_dir(temp_dir.as_path());
+
+    PHASES_TIMES_MONITOR
+        .write()
+        .print_stats("Compare completed.".to_string());
+
+    let total_kmers = counts.shared + counts.only_a + counts.only_b;
+    println!(
+        "Shared kmers: {} ({:.2}%)",
+        counts.shared,
+        counts.shared as f64 / total_kmers.max(1) as f64 * 100.0
+    );
+    println!("Kmers only in {}: {}", graph_a.display(), counts.only_a);
+    println!("Kmers only in {}: {}", graph_b.display(), counts.only_b);
+
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compare::run_compare;
+    use config::FLUSH_QUEUE_FACTOR;
+    use hashes::cn_nthash::CanonicalNtHashIteratorFactory;
+    use hashes::cn_seqhash::u64::CanonicalSeqHashFactory;
+    use parallel_processor::memory_data_size::MemoryDataSize;
+    use parallel_processor::memory_fs::MemoryFs;
+
+    #[test]
+    fn graphs_comparison() {
+        let test_dir =
+            std::env::temp_dir().join(format!("ggcat_graphs_comparison_{}", std::process::id()));
+        let temp_dir = test_dir.join("temp");
+        std::fs::create_dir_all(&temp_dir).unwrap();
+
+        MemoryFs::init(
+            MemoryDataSize::from_mebioctets(256),
+            FLUSH_QUEUE_FACTOR * 2,
+            1,
+            8192,
+        );
+
+        // With k = 5, unitig 1 of A contains an N and unitig 2 is shorter than k,
+        // A:0 shares its last 4 kmers with B:0 and A:3 its last 3 kmers with B:2
+        let graph_a = test_dir.join("a.fasta");
+        std::fs::write(
+            &graph_a,
+            concat!(
+                ">0 LN:i:13\nCAGATTTTCATAT\n",
+                ">1 LN:i:13\nATCTANCTTCGCC\n",
+                ">2 LN:i:3\nACG\n",
+                ">3 LN:i:10\nTATGCAGAAA\n",
+            ),
+        )
+        .unwrap();
+        let graph_b = test_dir.join("b.fasta");
+        std::fs::write(
+            &graph_b,
+            concat!(
+                ">0 LN:i:11\nTTTCATATTGA\n",
+                ">1 LN:i:11\nTACGAGTCGGN\n",
+                ">2 LN:i:9\nGCAGAAATT\n",
+            ),
+        )
+        .unwrap();
+
+        // The dot in the prefix must be kept in the output files names
+        let output_prefix = test_dir.join("compare.v2");
+        let counts = run_compare::<CanonicalNtHashIteratorFactory, CanonicalSeqHashFactory>(
+            5,
+            3,
+            graph_a,
+            graph_b,
+            Some(output_prefix),
+            temp_dir,
+            Some(2),
+            2,
+            None,
+        );
+
+        assert_eq!((counts.shared, counts.only_a, counts.only_b), (7, 12, 11));
+        assert_eq!(
+            std::fs::read_to_string(test_dir.join("compare.v2.only-a.fasta")).unwrap(),
+            concat!(
+                ">0:1-9 LN:i:9\nCAGATTTTC\n",
+                ">1:1-5 LN:i:5\nATCTA\n",
+                ">1:7-13 LN:i:7\nCTTCGCC\n",
+                ">3:1-7 LN:i:7\nTATGCAG\n",
+            )
+        );
+        assert_eq!(
+            std::fs::read_to_string(test_dir.join("compare.v2.only-b.fasta")).unwrap(),
+            concat!(
+                ">0:5-11 LN:i:7\nATATTGA\n",
+                ">1:1-10 LN:i:10\nTACGAGTCGG\n",
+                ">2:4-9 LN:i:6\nGAAATT\n",
+            )
+        );
+
+        std::fs::remove_dir_all(&test_dir).unwrap();
+    }
+}
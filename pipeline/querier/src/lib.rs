@@ -20,6 +20,7 @@ use std::io::{BufRead, BufReader};
 use std::path::PathBuf;
 use std::sync::atomic::Ordering;
 
+pub mod compare;
 mod pipeline;
 mod structs;
 
@@ -1,14 +1,15 @@
 use crate::structs::query_colored_counters::{ColorsRange, QueryColoredCounters};
 use crate::ColoredQueryOutputFormat;
+use colors::abundance::dequantize_color_abundance;
 use colors::colors_manager::ColorMapReader;
 use colors::colors_manager::{ColorsManager, ColorsMergeManager};
 use config::{
     get_compression_level_info, get_memory_mode, ColorIndexType, SwapPriority,
     DEFAULT_PREFETCH_AMOUNT, KEEP_FILES, QUERIES_COUNT_MIN_BATCH,
 };
-use flate2::Compression;
 use hashes::{HashFunctionFactory, MinimizerHashFunctionFactory};
 use io::get_bucket_index;
+use libdeflate_rs::parallel::bgzf_writer::BgzfWriter;
 use parallel_processor::buckets::readers::compressed_binary_reader::CompressedBinaryReader;
 use parallel_processor::buckets::readers::BucketReader;
 use parallel_processor::buckets::writers::compressed_binary_writer::CompressedBinaryWriter;
@@ -27,7 +28,7 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 enum QueryOutputFileWriter {
     Plain(File),
     LZ4Compressed(lz4::Encoder<File>),
-    GzipCompressed(flate2::write::GzEncoder<File>),
+    GzipCompressed(BgzfWriter<File>),
 }
 
 impl Write for QueryOutputFileWriter {
@@ -93,9 +94,9 @@ pub fn colored_query_output<
                         .build(query_output_file)
                         .unwrap(),
                 ),
-                Some("gz") => QueryOutputFileWriter::GzipCompressed(
-                    flate2::GzBuilder::new().write(query_output_file, Compression::default()),
-                ),
+                Some("gz") => {
+                    QueryOutputFileWriter::GzipCompressed(BgzfWriter::new(query_output_file, 6))
+                }
                 _ => QueryOutputFileWriter::Plain(query_output_file),
             },
         ),
@@ -103,6 +104,8 @@ pub fn colored_query_output<
     ));
     let output_sync_condvar = Condvar::new();
 
+    let has_abundances = colormap.has_abundances();
+
     (0..rayon::current_num_threads())
         .into_par_iter()
         .for_each(|_| {
@@ -110,6 +113,7 @@ pub fn colored_query_output<
             struct QueryColorListItem {
                 color: ColorIndexType,
                 count: u64,
+                abundance: u8,
                 next_index: usize,
             }
 
@@ -140,7 +144,7 @@ pub fn colored_query_output<
                     DEFAULT_PREFETCH_AMOUNT,
                 )
                 .decode_all_bucket_items::<QueryColoredCounters, _>(
-                    (Vec::new(), Vec::new()),
+                    (Vec::new(), Vec::new(), Vec::new()),
                     &mut (),
                     |counters, _| {
                         for query in counters.queries {
@@ -153,8 +157,10 @@ pub fn colored_query_output<
                             }
 
                             assert_eq!(counters.colors.len() % 2, 0);
-                            for range in counters.colors.chunks(2) {
+                            for (range_index, range) in counters.colors.chunks(2).enumerate() {
                                 let ColorsRange::Range(range) = ColorsRange::from_slice(range);
+                                let abundance =
+                                    counters.abundances.get(range_index).copied().unwrap_or(0);
 
                                 OPS_COUNT.fetch_add(1, Ordering::Relaxed);
                                 COL_COUNT.fetch_add(range.len(), Ordering::Relaxed);
@@ -163,6 +169,7 @@ pub fn colored_query_output<
                                     queries_colors_list_pool.push(QueryColorListItem {
                                         color,
                                         count: query.count,
+                                        abundance,
                                         next_index: *colors_map_index,
                                     });
                                     *colors_map_index = queries_colors_list_pool.len() - 1;
@@ -179,7 +186,7 @@ pub fn colored_query_output<
                     &(
                         get_memory_mode(SwapPriority::ColoredQueryBuckets),
                         CompressedBinaryWriter::CHECKPOINT_SIZE_UNLIMITED,
-                        get_compression_level_info(),
+                        get_compression_level_info(SwapPriority::ColoredQueryBuckets),
                     ),
                     bucket_index as usize,
                 );
@@ -200,14 +207,21 @@ pub fn colored_query_output<
                     temp_colors_list.clear();
                     while query_colors_list_index != usize::MAX {
                         let el = &queries_colors_list_pool[query_colors_list_index];
-                        temp_colors_list.push((el.color, el.count));
+                        temp_colors_list.push((el.color, el.count, el.abundance));
                         query_colors_list_index = el.next_index;
                     }
                     temp_colors_list.sort_unstable_by_key(|r| r.0);
 
                     for (i, qc) in temp_colors_list.group_by(|a, b| a.0 == b.0).enumerate() {
                         let color_index = qc[0].0;
-                        let color_presence = qc.iter().map(|x| x.1).sum::<u64>();
+                        // With abundances, report the mean abundance of the query kmers in the color (0 for the missing ones)
+                        let color_value = if has_abundances {
+                            qc.iter()
+                                .map(|x| x.1 as f64 * dequantize_color_abundance(x.2))
+                                .sum::<f64>()
+                        } else {
+                            qc.iter().map(|x| x.1).sum::<u64>() as f64
+                        };
 
                         if i != 0 {
                             write!(jsonline_buffer, ",").unwrap();
@@ -230,7 +244,7 @@ pub fn colored_query_output<
                         write!(
                             jsonline_buffer,
                             ": {:.2}",
-                            (color_presence as f64) / (query_kmers_count[query as usize] as f64)
+                            color_value / (query_kmers_count[query as usize] as f64)
                         )
                         .unwrap();
                     }
@@ -40,7 +40,7 @@ pub fn colormap_reading<CD: ColorsSerializerTrait>(
         &(
             get_memory_mode(SwapPriority::MinimizerBuckets),
             MINIMIZER_BUCKETS_CHECKPOINT_SIZE,
-            get_compression_level_info(),
+            get_compression_level_info(SwapPriority::MinimizerBuckets),
         ),
     ));
 
@@ -54,6 +54,7 @@ pub fn colormap_reading<CD: ColorsSerializerTrait>(
     colored_query_buckets.par_iter().for_each(|input| {
         let mut colormap_decoder = tlocal_colormap_decoder.get();
         let mut temp_colors_buffer = Vec::new();
+        let mut temp_abundances_buffer = Vec::new();
         let mut temp_queries_buffer = Vec::new();
         let mut temp_encoded_buffer = Vec::new();
 
@@ -95,9 +96,19 @@ pub fn colormap_reading<CD: ColorsSerializerTrait>(
         for queries_by_color in counters_vec.group_by_mut(|a, b| a.1 == b.1) {
             let color = queries_by_color[0].1;
             temp_colors_buffer.clear();
-            colormap_decoder.get_color_mappings(color, &mut temp_colors_buffer);
-
-            {
+            colormap_decoder.get_color_mappings_with_abundances(
+                color,
+                &mut temp_colors_buffer,
+                &mut temp_abundances_buffer,
+            );
+
+            if !temp_abundances_buffer.is_empty() {
+                // Colors with abundances are not merged in ranges, as each one has its own abundance
+                temp_encoded_buffer.clear();
+                for color in temp_colors_buffer.iter().copied() {
+                    ColorsRange::Range(color..(color + 1)).write_to_vec(&mut temp_encoded_buffer);
+                }
+            } else {
                 temp_encoded_buffer.clear();
                 let mut range_start = ColorIndexType::MAX;
                 let mut range_end = ColorIndexType::MAX;
@@ -151,6 +162,7 @@ pub fn colormap_reading<CD: ColorsSerializerTrait>(
                     &QueryColoredCounters {
                         queries: entries,
                         colors: &temp_encoded_buffer,
+                        abundances: &temp_abundances_buffer,
                     },
                 );
             }
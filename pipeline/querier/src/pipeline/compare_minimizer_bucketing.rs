@@ -0,0 +1,214 @@
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use config::BucketIndexType;
+use hashes::rolling::minqueue::RollingMinQueue;
+use hashes::ExtendableHashTraitType;
+use hashes::HashFunction;
+use hashes::MinimizerHashFunctionFactory;
+use io::concurrent::temp_reads::extra_data::SequenceExtraData;
+use io::sequences_reader::FastaSequence;
+use io::varint::{decode_varint, encode_varint, VARINT_MAX_SIZE};
+use minimizer_bucketing::{
+    GenericMinimizerBucketing, MinimizerBucketingCommonData, MinimizerBucketingExecutor,
+    MinimizerBucketingExecutorFactory, MinimizerInputSequence,
+};
+use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
+use std::io::{Read, Write};
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
+pub enum GraphSide {
+    A = 0,
+    B = 1,
+}
+
+impl Default for GraphSide {
+    fn default() -> Self {
+        Self::A
+    }
+}
+
+/// Position of the first kmer of a sequence chunk in the graph it comes from
+#[derive(Copy, Clone, Debug, Default, Ord, PartialOrd, Eq, PartialEq)]
+pub struct CompareKmersData {
+    pub side: GraphSide,
+    pub sequence_index: u64,
+    pub kmer_offset: u64,
+}
+
+impl SequenceExtraData for CompareKmersData {
+    type TempBuffer = ();
+
+    #[inline(always)]
+    fn decode_extended(_: &mut (), reader: &mut impl Read) -> Option<Self> {
+        let side = match reader.read_u8().ok()? {
+            0 => GraphSide::A,
+            _ => GraphSide::B,
+        };
+        Some(Self {
+            side,
+            sequence_index: decode_varint(|| reader.read_u8().ok())?,
+            kmer_offset: decode_varint(|| reader.read_u8().ok())?,
+        })
+    }
+
+    #[inline(always)]
+    fn encode_extended(&self, _: &(), writer: &mut impl Write) {
+        writer.write_u8(self.side as u8).unwrap();
+        encode_varint(|b| writer.write_all(b), self.sequence_index).unwrap();
+        encode_varint(|b| writer.write_all(b), self.kmer_offset).unwrap();
+    }
+
+    #[inline(always)]
+    fn max_size(&self) -> usize {
+        1 + VARINT_MAX_SIZE * 2
+    }
+}
+
+pub struct CompareMinimizerBucketingExecutor<H: MinimizerHashFunctionFactory> {
+    minimizer_queue: RollingMinQueue<H>,
+    global_data: Arc<MinimizerBucketingCommonData<()>>,
+}
+
+pub struct CompareMinimizerBucketingExecutorFactory<H: MinimizerHashFunctionFactory>(
+    PhantomData<H>,
+);
+
+impl<H: MinimizerHashFunctionFactory> MinimizerBucketingExecutorFactory
+    for CompareMinimizerBucketingExecutorFactory<H>
+{
+    type GlobalData = ();
+    type ExtraData = CompareKmersData;
+    type PreprocessInfo = CompareKmersData;
+    type FileInfo = GraphSide;
+
+    #[allow(non_camel_case_types)]
+    type FLAGS_COUNT = typenum::U0;
+
+    type ExecutorType = CompareMinimizerBucketingExecutor<H>;
+
+    fn new(
+        global_data: &Arc<MinimizerBucketingCommonData<Self::GlobalData>>,
+    ) -> Self::ExecutorType {
+        Self::ExecutorType {
+            minimizer_queue: RollingMinQueue::new(global_data.k - global_data.m + 1),
+            global_data: global_data.clone(),
+        }
+    }
+}
+
+impl<H: MinimizerHashFunctionFactory>
+    MinimizerBucketingExecutor<CompareMinimizerBucketingExecutorFactory<H>>
+    for CompareMinimizerBucketingExecutor<H>
+{
+    fn preprocess_fasta(
+        &mut self,
+        file_info: &GraphSide,
+        read_index: u64,
+        _sequence: &FastaSequence,
+        preprocess_info: &mut CompareKmersData,
+    ) {
+        *preprocess_info = CompareKmersData {
+            side: *file_info,
+            sequence_index: read_index,
+            kmer_offset: 0,
+        };
+    }
+
+    fn reprocess_sequence(
+        &mut self,
+        _flags: u8,
+        extra_data: &CompareKmersData,
+        _extra_data_buffer: &(),
+        preprocess_info: &mut CompareKmersData,
+    ) {
+        *preprocess_info = *extra_data;
+    }
+
+    fn process_sequence<
+        S: MinimizerInputSequence,
+        F: FnMut(BucketIndexType, BucketIndexType, S, u8, CompareKmersData, &()),
+    >(
+        &mut self,
+        preprocess_info: &CompareKmersData,
+        sequence: S,
+        range: Range<usize>,
+        used_bits: usize,
+        first_bits: usize,
+        second_bits: usize,
+        mut push_sequence: F,
+    ) {
+        let hashes = H::new(sequence, self.global_data.m);
+
+        let mut rolling_iter = self
+            .minimizer_queue
+            .make_iter(hashes.iter().map(|x| x.to_unextendable()));
+
+        // The sequence can be a chunk of the original one, split at the N bases
+        let base_offset = preprocess_info.kmer_offset + range.start as u64;
+
+        let mut last_index = 0;
+        let mut last_hash = rolling_iter.next().unwrap();
+
+        for (index, min_hash) in rolling_iter.enumerate() {
+            if H::get_full_minimizer(min_hash) != H::get_full_minimizer(last_hash) {
+                push_sequence(
+                    H::get_bucket(used_bits, first_bits, last_hash),
+                    H::get_bucket(used_bits + first_bits, second_bits, last_hash),
+                    sequence.get_subslice(last_index..(index + self.global_data.k)),
+                    0,
+                    CompareKmersData {
+                        kmer_offset: base_offset + last_index as u64,
+                        ..*preprocess_info
+                    },
+                    &(),
+                );
+
+                last_index = index + 1;
+                last_hash = min_hash;
+            }
+        }
+
+        push_sequence(
+            H::get_bucket(used_bits, first_bits, last_hash),
+            H::get_bucket(used_bits + first_bits, second_bits, last_hash),
+            sequence.get_subslice(last_index..sequence.seq_len()),
+            0,
+            CompareKmersData {
+                kmer_offset: base_offset + last_index as u64,
+                ..*preprocess_info
+            },
+            &(),
+        );
+    }
+}
+
+pub fn compare_minimizer_bucketing<H: MinimizerHashFunctionFactory>(
+    graph_a: PathBuf,
+    graph_b: PathBuf,
+    output_path: &Path,
+    buckets_count: usize,
+    threads_count: usize,
+    k: usize,
+    m: usize,
+) -> (Vec<PathBuf>, PathBuf) {
+    PHASES_TIMES_MONITOR
+        .write()
+        .start_phase("phase: graphs bucketing".to_string());
+
+    let input_files = vec![(graph_a, GraphSide::A), (graph_b, GraphSide::B)];
+
+    GenericMinimizerBucketing::do_bucketing::<CompareMinimizerBucketingExecutorFactory<H>>(
+        input_files,
+        output_path,
+        buckets_count,
+        threads_count,
+        k,
+        m,
+        (),
+        None,
+        false,
+    )
+}
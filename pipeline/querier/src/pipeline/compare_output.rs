@@ -0,0 +1,92 @@
+use crate::pipeline::parallel_kmers_compare::DifferingKmerPosition;
+use config::{DEFAULT_OUTPUT_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT, KEEP_FILES};
+use io::sequences_reader::SequencesReader;
+use parallel_processor::buckets::readers::lock_free_binary_reader::LockFreeBinaryReader;
+use parallel_processor::buckets::readers::BucketReader;
+use parallel_processor::memory_fs::RemoveFileMode;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+
+/// Writes the maximal runs of consecutive kmers of the graph that are missing from the other one,
+/// named `<unitig id>:<start>-<end>` (1-based, inclusive). Returns the number of written fragments
+pub fn write_differing_fragments(
+    k: usize,
+    input_graph: impl AsRef<Path>,
+    positions_bucket: impl AsRef<Path>,
+    output_file: impl AsRef<Path>,
+) -> u64 {
+    let mut positions = Vec::new();
+    LockFreeBinaryReader::new(
+        positions_bucket,
+        RemoveFileMode::Remove {
+            remove_fs: !KEEP_FILES.load(Ordering::Relaxed),
+        },
+        DEFAULT_PREFETCH_AMOUNT,
+    )
+    .decode_all_bucket_items::<DifferingKmerPosition, _>((), &mut (), |position, _| {
+        positions.push(position);
+    });
+
+    positions.sort_unstable();
+    // Repeated kmers in tigs are found once for each occurrence
+    positions.dedup();
+
+    let mut output = BufWriter::with_capacity(
+        DEFAULT_OUTPUT_BUFFER_SIZE,
+        File::create(&output_file).unwrap_or_else(|err| {
+            println!(
+                "ERROR: Cannot create output file {}: {}",
+                output_file.as_ref().display(),
+                err
+            );
+            std::process::exit(1);
+        }),
+    );
+
+    let mut sequence_index = 0;
+    let mut remaining = &positions[..];
+    let mut fragments_count = 0;
+
+    SequencesReader::new().process_file_extended(
+        input_graph,
+        |sequence| {
+            // Sequences shorter than k are not counted by the minimizer bucketing
+            if sequence.seq.len() < k {
+                return;
+            }
+
+            let sequence_positions_count = remaining
+                .iter()
+                .take_while(|p| p.sequence_index == sequence_index)
+                .count();
+            let (sequence_positions, next) = remaining.split_at(sequence_positions_count);
+            remaining = next;
+            sequence_index += 1;
+
+            let ident = sequence.ident.strip_prefix(b">").unwrap_or(sequence.ident);
+            let ident = ident
+                .split(|c| c.is_ascii_whitespace())
+                .next()
+                .unwrap_or(ident);
+
+            for run in sequence_positions.group_by(|a, b| a.kmer_offset + 1 == b.kmer_offset) {
+                let start = run[0].kmer_offset as usize;
+                let end = run[run.len() - 1].kmer_offset as usize + k;
+
+                output.write_all(b">").unwrap();
+                output.write_all(ident).unwrap();
+                writeln!(output, ":{}-{} LN:i:{}", start + 1, end, end - start).unwrap();
+                output.write_all(&sequence.seq[start..end]).unwrap();
+                output.write_all(b"\n").unwrap();
+                fragments_count += 1;
+            }
+        },
+        None,
+        true,
+        false,
+    );
+
+    fragments_count
+}
@@ -103,7 +103,7 @@ pub fn counters_sorting<CX: ColorsManager>(
             &(
                 get_memory_mode(SwapPriority::MinimizerBuckets),
                 MINIMIZER_BUCKETS_CHECKPOINT_SIZE,
-                get_compression_level_info(),
+                get_compression_level_info(SwapPriority::MinimizerBuckets),
             ),
         ))
     } else {
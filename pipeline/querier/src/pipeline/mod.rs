@@ -1,5 +1,8 @@
 pub mod colored_query_output;
 pub mod colormap_reading;
+pub mod compare_minimizer_bucketing;
+pub mod compare_output;
 pub mod counters_sorting;
+pub mod parallel_kmers_compare;
 pub mod parallel_kmers_query;
 pub mod querier_minimizer_bucketing;
@@ -0,0 +1,432 @@
+use crate::pipeline::compare_minimizer_bucketing::{
+    CompareKmersData, CompareMinimizerBucketingExecutorFactory, GraphSide,
+};
+use byteorder::ReadBytesExt;
+use config::{
+    get_memory_mode, BucketIndexType, SwapPriority, DEFAULT_PER_CPU_BUFFER_SIZE,
+    MINIMUM_SUBBUCKET_KMERS_COUNT, RESPLITTING_MAX_K_M_DIFFERENCE,
+};
+use hashbrown::HashMap;
+use hashes::HashFunction;
+use hashes::HashFunctionFactory;
+use hashes::{ExtendableHashTraitType, MinimizerHashFunctionFactory};
+use io::compressed_read::CompressedRead;
+use io::compressed_read::CompressedReadIndipendent;
+use io::varint::{decode_varint, encode_varint, VARINT_MAX_SIZE};
+use kmers_transform::processor::KmersTransformProcessor;
+use kmers_transform::{
+    GroupProcessStats, KmersTransform, KmersTransformExecutorFactory, KmersTransformFinalExecutor,
+    KmersTransformMapProcessor, KmersTransformPreprocessor,
+};
+use minimizer_bucketing::{MinimizerBucketingCommonData, MinimizerBucketingExecutorFactory};
+use parallel_processor::buckets::bucket_writer::BucketItem;
+use parallel_processor::buckets::concurrent::{BucketsThreadBuffer, BucketsThreadDispatcher};
+use parallel_processor::buckets::writers::lock_free_binary_writer::LockFreeBinaryWriter;
+use parallel_processor::buckets::MultiThreadBuckets;
+use parallel_processor::execution_manager::memory_tracker::MemoryTracker;
+use parallel_processor::execution_manager::objects_pool::PoolObjectTrait;
+use parallel_processor::execution_manager::packet::{Packet, PacketTrait};
+use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
+use std::cmp::min;
+use std::io::{Read, Write};
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+const SIDE_A: u8 = 1 << (GraphSide::A as u8);
+const SIDE_B: u8 = 1 << (GraphSide::B as u8);
+
+/// Position of a kmer present in only one of the two graphs
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
+pub struct DifferingKmerPosition {
+    pub sequence_index: u64,
+    pub kmer_offset: u64,
+}
+
+impl BucketItem for DifferingKmerPosition {
+    type ExtraData = ();
+    type ReadBuffer = ();
+    type ExtraDataBuffer = ();
+    type ReadType<'a> = Self;
+
+    #[inline(always)]
+    fn write_to(
+        &self,
+        bucket: &mut Vec<u8>,
+        _extra_data: &Self::ExtraData,
+        _: &Self::ExtraDataBuffer,
+    ) {
+        encode_varint(|b| bucket.write_all(b), self.sequence_index).unwrap();
+        encode_varint(|b| bucket.write_all(b), self.kmer_offset).unwrap();
+    }
+
+    fn read_from<'a, S: Read>(
+        mut stream: S,
+        _read_buffer: &'a mut Self::ReadBuffer,
+        _: &mut Self::ExtraDataBuffer,
+    ) -> Option<Self::ReadType<'a>> {
+        let sequence_index = decode_varint(|| stream.read_u8().ok())?;
+        let kmer_offset = decode_varint(|| stream.read_u8().ok())?;
+        Some(Self {
+            sequence_index,
+            kmer_offset,
+        })
+    }
+
+    #[inline(always)]
+    fn get_size(&self, _: &()) -> usize {
+        VARINT_MAX_SIZE * 2
+    }
+}
+
+/// Number of distinct kmers shared by the two graphs or present in only one of them
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CompareKmersCounts {
+    pub shared: u64,
+    pub only_a: u64,
+    pub only_b: u64,
+}
+
+struct GlobalCompareMergeData {
+    k: usize,
+    m: usize,
+    shared_kmers: AtomicU64,
+    only_a_kmers: AtomicU64,
+    only_b_kmers: AtomicU64,
+    /// One bucket for each graph, with the positions of its kmers missing from the other one
+    positions_buckets: Option<Arc<MultiThreadBuckets<LockFreeBinaryWriter>>>,
+    global_resplit_data: Arc<MinimizerBucketingCommonData<()>>,
+}
+
+struct ParallelKmersCompareFactory<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory>(
+    PhantomData<(H, MH)>,
+);
+
+impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> KmersTransformExecutorFactory
+    for ParallelKmersCompareFactory<H, MH>
+{
+    type SequencesResplitterFactory = CompareMinimizerBucketingExecutorFactory<H>;
+    type GlobalExtraData = GlobalCompareMergeData;
+    type AssociatedExtraData = CompareKmersData;
+
+    type PreprocessorType = ParallelKmersComparePreprocessor<H, MH>;
+    type MapProcessorType = ParallelKmersCompareMapProcessor<H, MH>;
+    type FinalExecutorType = ParallelKmersCompareFinalExecutor<H, MH>;
+
+    #[allow(non_camel_case_types)]
+    type FLAGS_COUNT = typenum::U0;
+    const HAS_COLORS: bool = false;
+
+    fn new_resplitter(
+        global_data: &Arc<Self::GlobalExtraData>,
+    ) -> <Self::SequencesResplitterFactory as MinimizerBucketingExecutorFactory>::ExecutorType {
+        CompareMinimizerBucketingExecutorFactory::new(&global_data.global_resplit_data)
+    }
+
+    fn new_preprocessor(_global_data: &Arc<Self::GlobalExtraData>) -> Self::PreprocessorType {
+        Self::PreprocessorType {
+            _phantom: PhantomData,
+        }
+    }
+
+    fn new_map_processor(
+        _global_data: &Arc<Self::GlobalExtraData>,
+        _mem_tracker: MemoryTracker<KmersTransformProcessor<Self>>,
+    ) -> Self::MapProcessorType {
+        Self::MapProcessorType {
+            map_packet: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    fn new_final_executor(global_data: &Arc<Self::GlobalExtraData>) -> Self::FinalExecutorType {
+        Self::FinalExecutorType {
+            positions_tmp: global_data.positions_buckets.as_ref().map(|buckets| {
+                BucketsThreadDispatcher::new(
+                    buckets,
+                    BucketsThreadBuffer::new(DEFAULT_PER_CPU_BUFFER_SIZE, buckets.count()),
+                )
+            }),
+            counts: CompareKmersCounts::default(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+struct ParallelKmersComparePreprocessor<H: HashFunctionFactory, MH: HashFunctionFactory> {
+    _phantom: PhantomData<(H, MH)>,
+}
+
+impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory>
+    KmersTransformPreprocessor<ParallelKmersCompareFactory<H, MH>>
+    for ParallelKmersComparePreprocessor<H, MH>
+{
+    fn get_sequence_bucket<C>(
+        &self,
+        global_data: &GlobalCompareMergeData,
+        seq_data: &(u8, u8, C, CompressedRead),
+        used_hash_bits: usize,
+        bucket_bits_count: usize,
+    ) -> BucketIndexType {
+        let read = &seq_data.3;
+
+        let hashes = H::new(read.sub_slice(0..global_data.k), global_data.m);
+
+        let minimizer = hashes
+            .iter()
+            .min_by_key(|k| H::get_full_minimizer(k.to_unextendable()))
+            .unwrap();
+
+        H::get_bucket(
+            used_hash_bits,
+            bucket_bits_count,
+            minimizer.to_unextendable(),
+        )
+    }
+}
+
+struct ParallelKmersCompareMapPacket<MH: HashFunctionFactory> {
+    /// Mask of the graphs containing each kmer
+    sides: HashMap<MH::HashTypeUnextendable, u8>,
+    positions: Vec<(MH::HashTypeUnextendable, CompareKmersData)>,
+}
+
+impl<MH: HashFunctionFactory> PoolObjectTrait for ParallelKmersCompareMapPacket<MH> {
+    type InitData = ();
+
+    fn allocate_new(_init_data: &Self::InitData) -> Self {
+        Self {
+            sides: HashMap::new(),
+            positions: Vec::new(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.sides = HashMap::with_capacity(32768);
+        self.positions.clear();
+        self.positions.shrink_to(32768);
+    }
+}
+
+impl<MH: HashFunctionFactory> PacketTrait for ParallelKmersCompareMapPacket<MH> {
+    fn get_size(&self) -> usize {
+        self.sides.len() * (size_of::<MH::HashTypeUnextendable>() + 1)
+            + self.positions.len() * size_of::<(MH::HashTypeUnextendable, CompareKmersData)>()
+    }
+}
+
+struct ParallelKmersCompareMapProcessor<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> {
+    map_packet: Option<Packet<ParallelKmersCompareMapPacket<MH>>>,
+    _phantom: PhantomData<H>,
+}
+
+impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory>
+    KmersTransformMapProcessor<ParallelKmersCompareFactory<H, MH>>
+    for ParallelKmersCompareMapProcessor<H, MH>
+{
+    type MapStruct = ParallelKmersCompareMapPacket<MH>;
+    const MAP_SIZE: usize = size_of::<MH::HashTypeUnextendable>() + 1;
+
+    fn process_group_start(
+        &mut self,
+        map_struct: Packet<Self::MapStruct>,
+        _global_data: &GlobalCompareMergeData,
+    ) {
+        self.map_packet = Some(map_struct);
+    }
+
+    fn process_group_batch_sequences(
+        &mut self,
+        global_data: &GlobalCompareMergeData,
+        batch: &Vec<(u8, CompareKmersData, CompressedReadIndipendent)>,
+        _extra_data_buffer: &(),
+        ref_sequences: &Vec<u8>,
+    ) -> GroupProcessStats {
+        let k = global_data.k;
+        let write_positions = global_data.positions_buckets.is_some();
+        let map_packet = self.map_packet.as_mut().unwrap();
+
+        let mut kmers_count = 0;
+
+        for (_, sequence_data, read) in batch.iter() {
+            let hashes = MH::new(read.as_reference(ref_sequences), k);
+
+            kmers_count += (read.bases_count() - k + 1) as u64;
+
+            for (index, hash) in hashes.iter().enumerate() {
+                let hash = hash.to_unextendable();
+                *map_packet.sides.entry(hash).or_insert(0) |= 1 << (sequence_data.side as u8);
+
+                if write_positions {
+                    map_packet.positions.push((
+                        hash,
+                        CompareKmersData {
+                            kmer_offset: sequence_data.kmer_offset + index as u64,
+                            ..*sequence_data
+                        },
+                    ));
+                }
+            }
+        }
+
+        GroupProcessStats {
+            total_kmers: kmers_count,
+            unique_kmers: kmers_count,
+        }
+    }
+
+    fn process_group_finalize(
+        &mut self,
+        _global_data: &GlobalCompareMergeData,
+    ) -> Packet<Self::MapStruct> {
+        self.map_packet.take().unwrap()
+    }
+}
+
+struct ParallelKmersCompareFinalExecutor<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> {
+    positions_tmp: Option<BucketsThreadDispatcher<LockFreeBinaryWriter>>,
+    counts: CompareKmersCounts,
+    _phantom: PhantomData<(H, MH)>,
+}
+
+impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory>
+    KmersTransformFinalExecutor<ParallelKmersCompareFactory<H, MH>>
+    for ParallelKmersCompareFinalExecutor<H, MH>
+{
+    type MapStruct = ParallelKmersCompareMapPacket<MH>;
+
+    fn process_map(
+        &mut self,
+        _global_data: &GlobalCompareMergeData,
+        map_struct: Packet<Self::MapStruct>,
+    ) -> Packet<Self::MapStruct> {
+        let map_struct_ref = map_struct.deref();
+
+        for sides in map_struct_ref.sides.values() {
+            match *sides {
+                SIDE_A => self.counts.only_a += 1,
+                SIDE_B => self.counts.only_b += 1,
+                _ => self.counts.shared += 1,
+            }
+        }
+
+        if let Some(positions_tmp) = &mut self.positions_tmp {
+            for (hash, position) in &map_struct_ref.positions {
+                if map_struct_ref.sides[hash] == SIDE_A | SIDE_B {
+                    continue;
+                }
+
+                positions_tmp.add_element(
+                    position.side as BucketIndexType,
+                    &(),
+                    &DifferingKmerPosition {
+                        sequence_index: position.sequence_index,
+                        kmer_offset: position.kmer_offset,
+                    },
+                );
+            }
+        }
+
+        map_struct
+    }
+
+    fn finalize(self, global_data: &GlobalCompareMergeData) {
+        global_data
+            .shared_kmers
+            .fetch_add(self.counts.shared, Ordering::Relaxed);
+        global_data
+            .only_a_kmers
+            .fetch_add(self.counts.only_a, Ordering::Relaxed);
+        global_data
+            .only_b_kmers
+            .fetch_add(self.counts.only_b, Ordering::Relaxed);
+
+        if let Some(positions_tmp) = self.positions_tmp {
+            positions_tmp.finalize();
+        }
+    }
+}
+
+/// Counts the kmers shared by the two graphs and the ones present in only one of them,
+/// optionally saving the positions of the latter in one bucket for each graph
+pub fn parallel_kmers_compare<
+    H: MinimizerHashFunctionFactory,
+    MH: HashFunctionFactory,
+    P: AsRef<Path> + Sync,
+>(
+    file_inputs: Vec<PathBuf>,
+    buckets_counters_path: PathBuf,
+    buckets_count: usize,
+    out_directory: P,
+    k: usize,
+    m: usize,
+    threads_count: usize,
+    save_positions: bool,
+) -> (CompareKmersCounts, Vec<PathBuf>) {
+    PHASES_TIMES_MONITOR
+        .write()
+        .start_phase("phase: kmers comparison".to_string());
+
+    let positions_buckets = if save_positions {
+        Some(Arc::new(MultiThreadBuckets::<LockFreeBinaryWriter>::new(
+            2,
+            out_directory.as_ref().join("differing-kmers"),
+            &(
+                get_memory_mode(SwapPriority::QueryCounters),
+                LockFreeBinaryWriter::CHECKPOINT_SIZE_UNLIMITED,
+            ),
+        )))
+    } else {
+        None
+    };
+
+    let global_data = Arc::new(GlobalCompareMergeData {
+        k,
+        m,
+        shared_kmers: AtomicU64::new(0),
+        only_a_kmers: AtomicU64::new(0),
+        only_b_kmers: AtomicU64::new(0),
+        positions_buckets,
+        global_resplit_data: Arc::new(MinimizerBucketingCommonData::new(
+            k,
+            if k > RESPLITTING_MAX_K_M_DIFFERENCE + 1 {
+                k - RESPLITTING_MAX_K_M_DIFFERENCE
+            } else {
+                min(m, 2)
+            }, // m
+            buckets_count,
+            1,
+            (),
+        )),
+    });
+
+    KmersTransform::<ParallelKmersCompareFactory<H, MH>>::new(
+        file_inputs,
+        out_directory.as_ref(),
+        buckets_counters_path,
+        buckets_count,
+        global_data.clone(),
+        threads_count,
+        k,
+        MINIMUM_SUBBUCKET_KMERS_COUNT as u64,
+    )
+    .parallel_kmers_transform();
+
+    let global_data =
+        Arc::try_unwrap(global_data).unwrap_or_else(|_| panic!("Cannot unwrap global data!"));
+
+    (
+        CompareKmersCounts {
+            shared: global_data.shared_kmers.load(Ordering::Relaxed),
+            only_a: global_data.only_a_kmers.load(Ordering::Relaxed),
+            only_b: global_data.only_b_kmers.load(Ordering::Relaxed),
+        },
+        global_data
+            .positions_buckets
+            .map(|buckets| buckets.finalize())
+            .unwrap_or_default(),
+    )
+}
@@ -141,7 +141,8 @@ impl<H: MinimizerHashFunctionFactory, CX: ColorsManager>
             FileType::Graph => {
                 let color = MinimizerBucketingSeqColorDataType::<CX>::create(
                     SingleSequenceInfo {
-                        file_index: 0, // FIXME: Change this to support querying of raw reads
+                        file_color: 0, // FIXME: Change this to support querying of raw reads
+                        record_colors: None,
                         sequence_ident: sequence.ident,
                     },
                     &mut preprocess_info.colors_buffer.0,
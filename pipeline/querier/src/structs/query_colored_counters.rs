@@ -32,11 +32,13 @@ pub struct QueryColorDesc {
 pub struct QueryColoredCounters<'a> {
     pub queries: &'a [QueryColorDesc],
     pub colors: &'a [ColorIndexType],
+    /// Quantized abundance of each colors range, empty if the colormap has no abundances
+    pub abundances: &'a [u8],
 }
 
 impl<'a> BucketItem for QueryColoredCounters<'a> {
     type ExtraData = ();
-    type ReadBuffer = (Vec<QueryColorDesc>, Vec<ColorIndexType>);
+    type ReadBuffer = (Vec<QueryColorDesc>, Vec<ColorIndexType>, Vec<u8>);
     type ExtraDataBuffer = ();
     type ReadType<'b> = QueryColoredCounters<'b>;
 
@@ -54,6 +56,9 @@ impl<'a> BucketItem for QueryColoredCounters<'a> {
 
         assert_eq!(self.colors.len() % 2, 0);
         ColorIndexSerializer::serialize_colors(bucket, &self.colors);
+
+        encode_varint(|b| bucket.extend_from_slice(b), self.abundances.len() as u64);
+        bucket.extend_from_slice(self.abundances);
     }
 
     fn read_from<'b, S: Read>(
@@ -63,6 +68,7 @@ impl<'a> BucketItem for QueryColoredCounters<'a> {
     ) -> Option<Self::ReadType<'b>> {
         read_buffer.0.clear();
         read_buffer.1.clear();
+        read_buffer.2.clear();
 
         let queries_count = decode_varint(|| stream.read_u8().ok())?;
         for _ in 0..queries_count {
@@ -71,14 +77,20 @@ impl<'a> BucketItem for QueryColoredCounters<'a> {
             read_buffer.0.push(QueryColorDesc { query_index, count });
         }
 
-        ColorIndexSerializer::deserialize_colors(stream, &mut read_buffer.1)?;
+        ColorIndexSerializer::deserialize_colors(&mut stream, &mut read_buffer.1)?;
+
+        let abundances_count = decode_varint(|| stream.read_u8().ok())? as usize;
+        read_buffer.2.resize(abundances_count, 0);
+        stream.read_exact(&mut read_buffer.2[..]).ok()?;
+
         Some(QueryColoredCounters {
             queries: &read_buffer.0,
             colors: &read_buffer.1,
+            abundances: &read_buffer.2,
         })
     }
 
     fn get_size(&self, _extra: &Self::ExtraData) -> usize {
-        (self.colors.len() + self.queries.len() + 1) * VARINT_MAX_SIZE * 4
+        (self.colors.len() + self.queries.len() + 2) * VARINT_MAX_SIZE * 4 + self.abundances.len()
     }
 }